@@ -0,0 +1,225 @@
+use dex_protocol_core::routing::RouteError;
+use dex_protocol_core::{LiquidityError, PrecisionError, SwapError};
+use warp::http::StatusCode;
+use warp::reply::{Json, WithStatus};
+
+/// Machine-readable error codes sent as `code` in every error body. The
+/// numbers are part of the API contract: never renumber or reuse one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum ErrorCode {
+    PoolNotFound = 1001,
+    InsufficientLiquidity = 1002,
+    SlippageExceeded = 1003,
+    InvalidAmount = 1004,
+    TokenNotFound = 1005,
+    UnsupportedPoolType = 1006,
+    PriceOutOfRange = 1007,
+    ReserveFloorBreached = 1008,
+    PriceImpactTooHigh = 1009,
+    InvalidReserves = 1010,
+    Unauthorized = 1011,
+    InvalidRequest = 1012,
+    NotFound = 1013,
+    MethodNotAllowed = 1014,
+    VersionConflict = 1015,
+    WouldEmptyReserve = 1016,
+    QuoteExpired = 1017,
+    InvariantViolated = 1018,
+    EmergencyModeDisabled = 1019,
+    ConvergenceFailed = 1020,
+    InternalError = 1021,
+    PoolAlreadyExists = 1022,
+    InsufficientLpTokens = 1023,
+}
+
+impl ErrorCode {
+    pub fn as_u16(self) -> u16 {
+        self as u16
+    }
+
+    pub fn status(self) -> StatusCode {
+        match self {
+            ErrorCode::PoolNotFound | ErrorCode::TokenNotFound | ErrorCode::NotFound => {
+                StatusCode::NOT_FOUND
+            }
+            ErrorCode::InvalidAmount | ErrorCode::InvalidReserves | ErrorCode::InvalidRequest => {
+                StatusCode::BAD_REQUEST
+            }
+            ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorCode::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            ErrorCode::VersionConflict | ErrorCode::PoolAlreadyExists => StatusCode::CONFLICT,
+            ErrorCode::EmergencyModeDisabled => StatusCode::FORBIDDEN,
+            ErrorCode::InvariantViolated | ErrorCode::InternalError => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            ErrorCode::InsufficientLiquidity
+            | ErrorCode::SlippageExceeded
+            | ErrorCode::UnsupportedPoolType
+            | ErrorCode::PriceOutOfRange
+            | ErrorCode::ReserveFloorBreached
+            | ErrorCode::PriceImpactTooHigh
+            | ErrorCode::WouldEmptyReserve
+            | ErrorCode::QuoteExpired
+            | ErrorCode::ConvergenceFailed
+            | ErrorCode::InsufficientLpTokens => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+    }
+}
+
+impl From<&SwapError> for ErrorCode {
+    fn from(error: &SwapError) -> Self {
+        match error {
+            SwapError::TokenNotFound => ErrorCode::TokenNotFound,
+            SwapError::InsufficientLiquidity => ErrorCode::InsufficientLiquidity,
+            SwapError::UnsupportedPoolType => ErrorCode::UnsupportedPoolType,
+            SwapError::PriceOutOfRange => ErrorCode::PriceOutOfRange,
+            SwapError::ReserveFloorBreached => ErrorCode::ReserveFloorBreached,
+            SwapError::WouldEmptyReserve => ErrorCode::WouldEmptyReserve,
+            SwapError::SlippageExceeded { .. } => ErrorCode::SlippageExceeded,
+            SwapError::InvariantViolated => ErrorCode::InvariantViolated,
+            SwapError::AmountOverflow => ErrorCode::InvalidAmount,
+            SwapError::ConvergenceFailed => ErrorCode::ConvergenceFailed,
+            SwapError::PositionNotFound => ErrorCode::NotFound,
+            // Only a corrupt pool gets past `Pool::try_new` with such a fee
+            SwapError::InvalidFeeRate(_) => ErrorCode::InternalError,
+        }
+    }
+}
+
+impl From<&LiquidityError> for ErrorCode {
+    fn from(error: &LiquidityError) -> Self {
+        match error {
+            LiquidityError::TokenNotFound => ErrorCode::TokenNotFound,
+            LiquidityError::InsufficientLiquidity => ErrorCode::InsufficientLiquidity,
+            LiquidityError::InsufficientLPTokens => ErrorCode::InsufficientLpTokens,
+            LiquidityError::EmergencyModeDisabled => ErrorCode::EmergencyModeDisabled,
+            LiquidityError::ReserveFloorBreached => ErrorCode::ReserveFloorBreached,
+        }
+    }
+}
+
+/// An error as sent to clients: `{"code": 1001, "error": "<message>"}`
+/// plus any details.
+#[derive(Debug)]
+pub struct ErrorResponse {
+    pub code: ErrorCode,
+    pub message: String,
+    details: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ErrorResponse {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        ErrorResponse {
+            code,
+            message: message.into(),
+            details: serde_json::Map::new(),
+        }
+    }
+
+    /// Adds a field to the body next to `code` and `error`.
+    pub fn with_detail(mut self, key: &str, value: impl Into<serde_json::Value>) -> Self {
+        self.details.insert(key.to_string(), value.into());
+        self
+    }
+
+    pub fn reply(&self) -> WithStatus<Json> {
+        let mut body = self.details.clone();
+        body.insert("code".to_string(), self.code.as_u16().into());
+        body.insert("error".to_string(), self.message.clone().into());
+        warp::reply::with_status(warp::reply::json(&body), self.code.status())
+    }
+}
+
+impl From<SwapError> for ErrorResponse {
+    fn from(error: SwapError) -> Self {
+        ErrorResponse::new((&error).into(), error.to_string())
+    }
+}
+
+impl From<LiquidityError> for ErrorResponse {
+    fn from(error: LiquidityError) -> Self {
+        ErrorResponse::new((&error).into(), error.to_string())
+    }
+}
+
+impl From<RouteError> for ErrorResponse {
+    fn from(error: RouteError) -> Self {
+        match error {
+            RouteError::Swap(error) => error.into(),
+            RouteError::InvalidPath => {
+                ErrorResponse::new(ErrorCode::InvalidRequest, error.to_string())
+            }
+        }
+    }
+}
+
+impl From<PrecisionError> for ErrorResponse {
+    fn from(error: PrecisionError) -> Self {
+        ErrorResponse::new(ErrorCode::InvalidAmount, error.to_string())
+    }
+}
+
+/// Replies with `body` on success or the error's status and body.
+pub fn reply<T: serde::Serialize>(result: Result<T, ErrorResponse>) -> WithStatus<Json> {
+    match result {
+        Ok(body) => warp::reply::with_status(warp::reply::json(&body), StatusCode::OK),
+        Err(error) => error.reply(),
+    }
+}
+
+/// Gives warp's own rejections (unknown route, malformed body or query)
+/// the same error body as the handlers.
+pub async fn handle_rejection(
+    rejection: warp::Rejection,
+) -> Result<WithStatus<Json>, warp::Rejection> {
+    let error = if rejection.is_not_found() {
+        ErrorResponse::new(ErrorCode::NotFound, "not found")
+    } else if rejection.find::<warp::reject::MethodNotAllowed>().is_some() {
+        ErrorResponse::new(ErrorCode::MethodNotAllowed, "method not allowed")
+    } else {
+        ErrorResponse::new(ErrorCode::InvalidRequest, "invalid request")
+    };
+    Ok(error.reply())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_codes_keep_their_numbers() {
+        let documented = [
+            (ErrorCode::PoolNotFound, 1001),
+            (ErrorCode::InsufficientLiquidity, 1002),
+            (ErrorCode::SlippageExceeded, 1003),
+            (ErrorCode::InvalidAmount, 1004),
+            (ErrorCode::TokenNotFound, 1005),
+            (ErrorCode::UnsupportedPoolType, 1006),
+            (ErrorCode::PriceOutOfRange, 1007),
+            (ErrorCode::ReserveFloorBreached, 1008),
+            (ErrorCode::PriceImpactTooHigh, 1009),
+            (ErrorCode::InvalidReserves, 1010),
+            (ErrorCode::Unauthorized, 1011),
+            (ErrorCode::InvalidRequest, 1012),
+            (ErrorCode::NotFound, 1013),
+            (ErrorCode::MethodNotAllowed, 1014),
+            (ErrorCode::VersionConflict, 1015),
+            (ErrorCode::WouldEmptyReserve, 1016),
+            (ErrorCode::QuoteExpired, 1017),
+            (ErrorCode::InvariantViolated, 1018),
+            (ErrorCode::EmergencyModeDisabled, 1019),
+            (ErrorCode::ConvergenceFailed, 1020),
+            (ErrorCode::InternalError, 1021),
+            (ErrorCode::PoolAlreadyExists, 1022),
+            (ErrorCode::InsufficientLpTokens, 1023),
+        ];
+        for (code, number) in documented {
+            assert_eq!(code.as_u16(), number, "{:?}", code);
+        }
+        assert_eq!(
+            ErrorCode::from(&SwapError::ReserveFloorBreached),
+            ErrorCode::ReserveFloorBreached
+        );
+    }
+}
@@ -0,0 +1,56 @@
+use dex_protocol_core::SwapReceipt;
+use num_bigint::BigUint;
+use std::collections::HashMap;
+use tokio::sync::broadcast;
+
+// Events held for a subscriber that falls behind before it starts missing them
+pub const EVENT_CAPACITY: usize = 1024;
+
+/// A pool change, published once the change has been committed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PoolEvent {
+    Swap {
+        pool_id: String,
+        input_token: String,
+        output_token: String,
+        input_amount: BigUint,
+        output_amount: BigUint,
+        fee: BigUint,
+        fee_token: String,
+    },
+    LiquidityAdded {
+        pool_id: String,
+        token_amounts: HashMap<String, BigUint>,
+        lp_tokens: BigUint,
+    },
+    LiquidityRemoved {
+        pool_id: String,
+        token_amounts: HashMap<String, BigUint>,
+        lp_tokens: BigUint,
+    },
+}
+
+impl From<&SwapReceipt> for PoolEvent {
+    fn from(receipt: &SwapReceipt) -> Self {
+        PoolEvent::Swap {
+            pool_id: receipt.pool_id.clone(),
+            input_token: receipt.input_token.clone(),
+            output_token: receipt.output_token.clone(),
+            input_amount: receipt.input_amount.clone(),
+            output_amount: receipt.output_amount.clone(),
+            fee: receipt.fee.clone(),
+            fee_token: receipt.fee_token.clone(),
+        }
+    }
+}
+
+pub type PoolEvents = broadcast::Sender<PoolEvent>;
+
+pub fn channel() -> PoolEvents {
+    broadcast::channel(EVENT_CAPACITY).0
+}
+
+// Sending only fails when nobody is subscribed, and then there is nobody to tell
+pub fn publish(events: &PoolEvents, event: PoolEvent) {
+    let _ = events.send(event);
+}
@@ -1,7 +1,22 @@
+mod errors;
+mod events;
+mod metrics;
+mod pool_config;
+mod quote_cache;
+
 use warp::Filter;
 use serde::{Deserialize, Serialize};
 use dex_protocol_core::*;
-use std::collections::HashMap;
+use dex_protocol_core::candles::{build_candles, ReserveSnapshot};
+use dex_protocol_core::rewards::{calculate_rewards, fee_apy, reward_apy, RewardProgram};
+use dex_protocol_core::routing::{aggregate_depth, available_fee_tiers, find_best_route_with, Route, RoutingConfig};
+use dex_protocol_core::valuation::StaticPriceFeed;
+use errors::{reply, ErrorCode, ErrorResponse};
+use events::{PoolEvent, PoolEvents};
+use metrics::MetricsCollector;
+use quote_cache::{QuoteKey, WarmQuotes};
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -10,221 +25,3190 @@ struct SwapRequest {
     input_token: String,
     output_token: String,
     input_amount: String,
-    slippage_tolerance: f64,
+    slippage_tolerance: f64, // percent, e.g. 0.5 accepts 0.5% less than expected
+    #[serde(default)]
+    quote_timestamp: Option<u64>, // `quoted_at` of the quote being executed
+    #[serde(default)]
+    expected_output: Option<String>, // `expected_output` of that quote, a stricter reference than the pool's own quote when higher
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct SwapResponse {
-    output_amount: String,
-    price_impact: f64,
-    fee: String,
-    route: Vec<String>,
+    output_amount: String, // same as expected_output, kept for older clients
+    expected_output: String, // raw units of the output token at the current price
+    minimum_output: String, // expected_output less the slippage tolerance
+    price_impact: String, // percent
+    fee: String, // raw units of fee_token
+    fee_formatted: String, // fee in whole fee_token units
+    fee_token: Token, // the token the curve takes its fee in: the output for stable pools, else the input
+    total_fee_bps: f64,
+    route: Vec<RouteHop>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    receipt_id: Option<String>,
+    // Unix seconds; set on quotes, which swaps may echo as quote_timestamp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    quoted_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    valid_until: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    warning: Option<String>,
+}
+
+/// One swap of a route: `token_in` for `token_out` in pool `pool_id`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RouteHop {
+    pool_id: String,
+    pool_version: u64,
+    token_in: Token,
+    token_out: Token,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PairQuoteQuery {
+    token_a: String,
+    token_b: String,
+    amount: String,
+    #[serde(default)]
+    slippage_tolerance: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PairQuoteResponse {
+    a_to_b: SwapResponse,
+    b_to_a: SwapResponse,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct AddLiquidityRequest {
     pool_id: String,
     token_amounts: HashMap<String, String>,
+    #[serde(default)]
+    owner: Option<String>,
+}
+
+/// A pool to add at runtime, validated like a startup pool config.
+/// Reserves are decimal strings keyed by token address.
+#[derive(Debug, Serialize, Deserialize)]
+struct CreatePoolRequest {
+    id: String,
+    tokens: Vec<pool_config::TokenSpec>,
+    initial_reserves: HashMap<String, String>,
+    #[serde(default)]
+    fee_rate: Option<u64>, // bps; the pool type's default when unset
+    pool_type: String,
+}
+
+impl CreatePoolRequest {
+    fn into_config(self, token_decimals: &pool_config::TokenDecimals) -> Result<pool_config::PoolConfig, String> {
+        let tokens = self.tokens.into_iter()
+            .map(|spec| token_decimals.resolve(spec))
+            .collect::<Result<_, _>>()?;
+        Ok(pool_config::PoolConfig {
+            id: self.id,
+            tokens,
+            reserves: self.initial_reserves,
+            fee_rate: self.fee_rate,
+            pool_type: self.pool_type,
+            amplification: None,
+            tags: Vec::new(),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RemoveLiquidityRequest {
+    pool_id: String,
+    lp_tokens: String,
+    #[serde(default)]
+    owner: Option<String>, // whose position to burn from; without one, only unowned LP tokens
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct EmergencyRemoveRequest {
+    pool_id: String,
+    lp_tokens: String,
+    owner: String, // whose position the tokens are burned from
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RemoveLiquidityResponse {
+    amounts: HashMap<String, String>, // withdrawn, by token address
+    pool_version: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BatchLiquidityResult {
+    pool_id: String,
+    success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lp_tokens: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    code: Option<u16>, // ErrorCode of the failure
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PriceQuery {
+    base: String,
+    quote: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PriceResponse {
+    pool_id: String,
+    base: String,
+    quote: String,
+    price: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PairPriceRequest {
+    token_a: String,
+    token_b: String,
+}
+
+/// Spot price of `token_a` in `token_b`, or why it could not be priced.
+#[derive(Debug, Serialize, Deserialize)]
+struct PairPriceResult {
+    token_a: String,
+    token_b: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    price: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pools: Vec<String>, // pools the price was derived through
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    code: Option<u16>, // ErrorCode of the failure
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PoolsQuery {
+    // Only pools carrying this tag when set
+    tag: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DepthQuery {
+    token_a: String,
+    token_b: String,
+    levels: String, // comma-separated prices of token_a in token_b
+    // All pools trading the pair when unset
+    pool_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DepthLevel {
+    price: String,
+    depth: String, // token_a absorbed before the price falls to `price`
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CandlesQuery {
+    interval: u64, // seconds
+    count: usize,
+    // Default to the pool's first two tokens
+    base: Option<String>,
+    quote: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RewardsQuery {
+    total_reward: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RewardsResponse {
+    pool_id: String,
+    owner: String,
+    lp_tokens: String,
+    pending_rewards: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ApyResponse {
+    pool_id: String,
+    // Percentages, like PoolInfo::apy
+    fee_apy: f64,
+    reward_apy: f64,
+    total_apy: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct RewardProgramConfig {
+    token: String,
+    per_year: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct PoolInfo {
     id: String,
+    pool_type: String,
     tokens: Vec<Token>,
     reserves: HashMap<String, String>,
     total_supply: String,
     fee_rate: u64,
     apy: f64,
     volume_24h: String,
+    cumulative_fees: HashMap<String, String>,
+    utilization: HashMap<String, String>, // how far each reserve sits below its peak, 0 to 1
+    tags: Vec<String>,
+    version: u64, // send back as If-Match to detect concurrent changes
+}
+
+/// A pool as another deployment reports it; `GET /pools` output qualifies.
+#[derive(Debug, Serialize, Deserialize)]
+struct PoolBaseline {
+    id: String,
+    reserves: HashMap<String, String>,
+    total_supply: String,
+    fee_rate: u64,
+}
+
+/// How a pool differs from its baseline. Only differing fields are listed,
+/// and a pool missing on one side has `None` there for every field.
+#[derive(Debug, Serialize, Deserialize)]
+struct PoolDiff {
+    pool_id: String,
+    reserves_match: bool, // by reserves_checksum
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    reserves: HashMap<String, Change<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    total_supply: Option<Change<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fee_rate: Option<Change<u64>>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Change<T> {
+    baseline: Option<T>,
+    current: Option<T>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PoolTvl {
+    pool_id: String,
+    tvl: Option<String>, // None when a token has no configured price
 }
 
 type PoolStorage = Arc<RwLock<HashMap<String, Pool>>>;
+type ReserveHistory = Arc<RwLock<HashMap<String, Vec<ReserveSnapshot>>>>;
+type QuoteCache = Arc<RwLock<WarmQuotes>>;
+
+// Longest route a quote may take, in pools
+const MAX_ROUTE_HOPS: usize = 3;
+
+// Oldest snapshots are dropped beyond this many per pool
+const MAX_SNAPSHOTS_PER_POOL: usize = 10_000;
+
+// How long a quote may be executed for unless configured otherwise
+const DEFAULT_QUOTE_TTL_SECS: u64 = 30;
+
+// Price impact above which responses carry a warning unless configured otherwise
+const DEFAULT_PRICE_IMPACT_WARNING_BPS: u64 = 500;
+
+// Share of the input reserve above which a trade earns a warning unless configured otherwise
+const DEFAULT_SWAP_SIZE_WARNING_BPS: u64 = 1000;
+
+/// Operator settings that apply to every pool.
+#[derive(Debug, Clone, Default)]
+struct ApiConfig {
+    // Swaps moving the input reserve by more than this are refused
+    max_price_impact_bps: Option<u64>,
+    // Liquidity-mining programs by pool id
+    reward_programs: HashMap<String, RewardProgram>,
+    // Bearer token for admin routes; they refuse every request when unset
+    admin_token: Option<String>,
+    // Prices per whole token used to value reserves
+    token_prices: StaticPriceFeed,
+    // Seconds a quote stays executable, DEFAULT_QUOTE_TTL_SECS when unset
+    quote_ttl_secs: Option<u64>,
+    // Impact that earns a warning, DEFAULT_PRICE_IMPACT_WARNING_BPS when unset
+    price_impact_warning_bps: Option<u64>,
+    // Share of the input reserve that earns a warning, DEFAULT_SWAP_SIZE_WARNING_BPS when unset
+    swap_size_warning_bps: Option<u64>,
+    // Whole-token sizes quoted ahead of requests, quote_cache::DEFAULT_WARM_SIZES when unset
+    quote_warm_sizes: Option<Vec<u64>>,
+    // Seconds between warming passes, quote_cache::DEFAULT_WARM_INTERVAL_SECS when unset
+    quote_warm_interval_secs: Option<u64>,
+    // Pools quotes and swaps may be routed through
+    routing: RoutingConfig,
+    // Decimals for tokens of new pools given without them
+    token_decimals: pool_config::TokenDecimals,
+}
+
+impl ApiConfig {
+    fn from_env() -> Self {
+        ApiConfig {
+            max_price_impact_bps: std::env::var("DEX_MAX_PRICE_IMPACT_BPS")
+                .ok()
+                .and_then(|v| v.parse().ok()),
+            reward_programs: std::env::var("DEX_REWARD_PROGRAMS")
+                .ok()
+                .map(|v| parse_reward_programs(&v))
+                .unwrap_or_default(),
+            admin_token: std::env::var("DEX_ADMIN_TOKEN").ok().filter(|v| !v.is_empty()),
+            token_prices: std::env::var("DEX_TOKEN_PRICES")
+                .ok()
+                .map(|v| parse_token_prices(&v))
+                .unwrap_or_default(),
+            quote_ttl_secs: std::env::var("DEX_QUOTE_TTL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok()),
+            price_impact_warning_bps: std::env::var("DEX_PRICE_IMPACT_WARNING_BPS")
+                .ok()
+                .and_then(|v| v.parse().ok()),
+            swap_size_warning_bps: std::env::var("DEX_SWAP_SIZE_WARNING_BPS")
+                .ok()
+                .and_then(|v| v.parse().ok()),
+            quote_warm_sizes: std::env::var("DEX_QUOTE_WARM_SIZES")
+                .ok()
+                .map(|v| quote_cache::parse_warm_sizes(&v)),
+            quote_warm_interval_secs: std::env::var("DEX_QUOTE_WARM_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok()),
+            routing: RoutingConfig {
+                excluded_pool_ids: std::env::var("DEX_EXCLUDED_POOL_IDS")
+                    .map(|v| parse_pool_ids(&v))
+                    .unwrap_or_default(),
+                only_pool_ids: std::env::var("DEX_ONLY_POOL_IDS").ok().map(|v| parse_pool_ids(&v)),
+            },
+            token_decimals: std::env::var("DEX_TOKEN_DECIMALS")
+                .ok()
+                .map(|v| pool_config::TokenDecimals::with_overrides(&v))
+                .unwrap_or_default(),
+        }
+    }
+    
+    fn is_admin(&self, authorization: Option<&str>) -> bool {
+        match (&self.admin_token, authorization.and_then(|h| h.strip_prefix("Bearer "))) {
+            (Some(expected), Some(given)) => expected == given,
+            _ => false,
+        }
+    }
+    
+    fn quote_ttl_secs(&self) -> u64 {
+        self.quote_ttl_secs.unwrap_or(DEFAULT_QUOTE_TTL_SECS)
+    }
+    
+    fn quote_warm_sizes(&self) -> &[u64] {
+        self.quote_warm_sizes.as_deref().unwrap_or(&quote_cache::DEFAULT_WARM_SIZES)
+    }
+    
+    // At least a second, as tokio refuses a zero interval
+    fn quote_warm_interval(&self) -> std::time::Duration {
+        let secs = self.quote_warm_interval_secs.unwrap_or(quote_cache::DEFAULT_WARM_INTERVAL_SECS);
+        std::time::Duration::from_secs(secs.max(1))
+    }
+    
+    // Advisory only: unlike max_price_impact_bps it never refuses the swap
+    fn price_impact_warning(&self, price_impact: f64) -> Option<String> {
+        let threshold = self.price_impact_warning_bps.unwrap_or(DEFAULT_PRICE_IMPACT_WARNING_BPS);
+        (price_impact * 100.0 > threshold as f64).then(|| format!("High price impact: {:.1}%", price_impact))
+    }
+    
+    // Compared exactly, so a trade of exactly the threshold share is not flagged
+    fn swap_size_warning(&self, input_reserve: &num_bigint::BigUint, input_amount: &num_bigint::BigUint) -> Option<String> {
+        let threshold = self.swap_size_warning_bps.unwrap_or(DEFAULT_SWAP_SIZE_WARNING_BPS);
+        if *input_reserve == num_bigint::BigUint::from(0u64) || input_amount * 10000u64 <= input_reserve * threshold {
+            return None;
+        }
+        let share_bps = input_amount * 10000u64 / input_reserve;
+        Some(format!("Large swap: {:.1}% of the input reserve", share_bps.to_f64().unwrap_or(f64::MAX) / 100.0))
+    }
+    
+    // Every warning that applies to the trade, joined into the response's one field
+    fn swap_warning(&self, pool: &Pool, input_token: &str, input_amount: &num_bigint::BigUint, price_impact: f64) -> Option<String> {
+        let swap_size = pool.reserves.get(input_token)
+            .and_then(|input_reserve| self.swap_size_warning(input_reserve, input_amount));
+        let warnings: Vec<String> = [self.price_impact_warning(price_impact), swap_size].into_iter().flatten().collect();
+        (!warnings.is_empty()).then(|| warnings.join("; "))
+    }
+}
+
+// `{"<pool id>": {"token": "<address>", "per_year": "<amount>"}}`; malformed
+// entries are skipped
+fn parse_reward_programs(json: &str) -> HashMap<String, RewardProgram> {
+    let configs: HashMap<String, RewardProgramConfig> = serde_json::from_str(json).unwrap_or_default();
+    configs.into_iter().filter_map(|(pool_id, config)| {
+        let per_year = config.per_year.parse().ok()?;
+        Some((pool_id, RewardProgram { token: config.token, per_year }))
+    }).collect()
+}
+
+// "<pool id>,<pool id>"; blank entries are skipped
+fn parse_pool_ids(list: &str) -> HashSet<String> {
+    list.split(',').map(str::trim).filter(|id| !id.is_empty()).map(String::from).collect()
+}
+
+// `{"<address>": "<price>"}`; malformed entries are skipped
+fn parse_token_prices(json: &str) -> StaticPriceFeed {
+    let configs: HashMap<String, String> = serde_json::from_str(json).unwrap_or_default();
+    StaticPriceFeed::new(configs.into_iter().filter_map(|(token, price)| {
+        Some((token, price.parse().ok()?))
+    }).collect())
+}
 
 #[tokio::main]
 async fn main() {
     let pools: PoolStorage = Arc::new(RwLock::new(HashMap::new()));
+    let history: ReserveHistory = Arc::new(RwLock::new(HashMap::new()));
+    let quote_cache: QuoteCache = Arc::new(RwLock::new(WarmQuotes::default()));
+    let events = events::channel();
+    let metrics = MetricsCollector::new();
+    metrics.subscribe(events.subscribe());
+    let config = ApiConfig::from_env();
+    
+    // Pools come from the file given with --pools or POOLS_FILE, else the samples.
+    // A file that cannot be read or parsed stops startup; pools in it that fail
+    // validation are reported and skipped, and the rest load
+    let file = pool_config::pools_file(std::env::args().skip(1));
+    let configs = pool_config::startup_pool_configs(file.as_deref()).unwrap_or_else(|e| {
+        eprintln!("Cannot load pools from {}: {}", file.unwrap_or_default().display(), e);
+        std::process::exit(1);
+    });
+    initialize_pools(&pools, configs).await;
+    // Incident switch: while set, admins can exit LPs through
+    // /liquidity/emergency-remove without fees or reserve floors
+    set_emergency_mode(std::env::var("DEX_EMERGENCY_MODE").is_ok_and(|v| v == "1" || v == "true"));
+    for pool in pools.read().await.values() {
+        record_snapshot(&history, pool).await;
+    }
     
-    // Initialize some sample pools
-    initialize_sample_pools(&pools).await;
+    tokio::spawn(run_quote_warming(pools.clone(), quote_cache.clone(), config.clone()));
     
+    println!("DEX API server starting on http://localhost:3030");
+    let service = warp::service(routes(pools, history, quote_cache, events, metrics, config));
+    let make_service = warp::hyper::service::make_service_fn(move |_| {
+        let service = service.clone();
+        async move {
+            Ok::<_, std::convert::Infallible>(warp::hyper::service::service_fn(move |request| catch_panics(service.clone(), request)))
+        }
+    });
+    if let Err(e) = warp::hyper::Server::bind(&([127, 0, 0, 1], 3030).into()).serve(make_service).await {
+        eprintln!("Server error: {}", e);
+    }
+}
+
+// Runs each request on its own task, so a handler that panics answers 500
+// instead of taking the connection down with it
+async fn catch_panics<S>(mut service: S, request: warp::hyper::Request<warp::hyper::Body>) -> Result<warp::reply::Response, std::convert::Infallible>
+where
+    S: warp::hyper::service::Service<warp::hyper::Request<warp::hyper::Body>, Response = warp::reply::Response, Error = std::convert::Infallible>,
+    S::Future: Send + 'static,
+{
+    match tokio::spawn(service.call(request)).await {
+        Ok(response) => response,
+        Err(e) => {
+            eprintln!("Request handler failed: {}", e);
+            Ok(warp::Reply::into_response(ErrorResponse::new(ErrorCode::InternalError, "internal error").reply()))
+        }
+    }
+}
+
+// Applies `change` to a copy of the pool and keeps it only on success, so
+// neither an error nor a panic part way through leaves the pool half-updated
+fn commit<T, E>(pool: &mut Pool, change: impl FnOnce(&mut Pool) -> Result<T, E>) -> Result<T, E> {
+    let mut updated = pool.clone();
+    let result = change(&mut updated)?;
+    *pool = updated;
+    Ok(result)
+}
+
+// `commit` for changes spanning several pools: `change` runs on copies of
+// the pools in `ids`, which replace them only if it succeeds
+fn commit_all<T, E>(
+    pools: &mut HashMap<String, Pool>,
+    ids: &[String],
+    change: impl FnOnce(&mut HashMap<String, Pool>) -> Result<T, E>,
+) -> Result<T, E> {
+    let mut updated: HashMap<String, Pool> = ids.iter()
+        .filter_map(|id| pools.get(id).map(|pool| (id.clone(), pool.clone())))
+        .collect();
+    let result = change(&mut updated)?;
+    pools.extend(updated);
+    Ok(result)
+}
+
+// Re-quotes the standard sizes every interval so common requests hit the cache
+async fn run_quote_warming(pools: PoolStorage, quote_cache: QuoteCache, config: ApiConfig) {
+    let mut interval = tokio::time::interval(config.quote_warm_interval());
+    loop {
+        interval.tick().await;
+        warm_quote_cache(&pools, &quote_cache, &config).await;
+    }
+}
+
+// Replaces the cache wholesale, dropping routes for pool versions now gone
+async fn warm_quote_cache(pools: &PoolStorage, quote_cache: &QuoteCache, config: &ApiConfig) {
+    let quotes = quote_cache::warm_quotes(&*pools.read().await, config.quote_warm_sizes(), MAX_ROUTE_HOPS, &config.routing);
+    *quote_cache.write().await = quotes;
+}
+
+fn routes(pools: PoolStorage, history: ReserveHistory, quote_cache: QuoteCache, events: PoolEvents, metrics: MetricsCollector, config: ApiConfig) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     let cors = warp::cors()
         .allow_any_origin()
-        .allow_headers(vec!["content-type"])
+        .allow_headers(vec!["content-type", "authorization", "if-match"])
         .allow_methods(vec!["GET", "POST", "PUT", "DELETE"]);
     
     // Routes
     let quote_route = warp::path("quote")
+        .and(warp::path::end())
         .and(warp::post())
         .and(warp::body::json())
         .and(with_pools(pools.clone()))
+        .and(with_quote_cache(quote_cache.clone()))
+        .and(with_config(config.clone()))
         .and_then(handle_quote);
     
+    let pair_quote_route = warp::path!("quote" / "pair")
+        .and(warp::get())
+        .and(warp::query::<PairQuoteQuery>())
+        .and(with_pools(pools.clone()))
+        .and(with_quote_cache(quote_cache.clone()))
+        .and(with_config(config.clone()))
+        .and_then(handle_pair_quote);
+    
     let swap_route = warp::path("swap")
         .and(warp::post())
         .and(warp::body::json())
+        .and(warp::header::optional::<String>("if-match"))
         .and(with_pools(pools.clone()))
+        .and(with_history(history.clone()))
+        .and(with_events(events.clone()))
+        .and(with_config(config.clone()))
         .and_then(handle_swap);
     
     let pools_route = warp::path("pools")
+        .and(warp::path::end())
         .and(warp::get())
+        .and(warp::query::<PoolsQuery>())
         .and(with_pools(pools.clone()))
+        .and(with_config(config.clone()))
         .and_then(handle_get_pools);
     
+    let create_pool_route = warp::path("pools")
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::body::json())
+        .and(with_pools(pools.clone()))
+        .and(with_history(history.clone()))
+        .and(with_config(config.clone()))
+        .and_then(handle_create_pool);
+    
+    let pool_route = warp::path!("pools" / String)
+        .and(warp::get())
+        .and(with_pools(pools.clone()))
+        .and(with_config(config.clone()))
+        .and_then(handle_get_pool);
+    
     let add_liquidity_route = warp::path("liquidity")
+        .and(warp::path::end())
         .and(warp::post())
         .and(warp::body::json())
+        .and(warp::header::optional::<String>("if-match"))
         .and(with_pools(pools.clone()))
+        .and(with_history(history.clone()))
+        .and(with_events(events.clone()))
         .and_then(handle_add_liquidity);
     
-    let routes = quote_route
+    let batch_liquidity_route = warp::path!("liquidity" / "batch")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_pools(pools.clone()))
+        .and(with_history(history.clone()))
+        .and(with_events(events.clone()))
+        .and_then(handle_batch_add_liquidity);
+    
+    let remove_liquidity_route = warp::path!("liquidity" / "remove")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(warp::header::optional::<String>("if-match"))
+        .and(with_pools(pools.clone()))
+        .and(with_history(history.clone()))
+        .and(with_events(events.clone()))
+        .and_then(handle_remove_liquidity);
+    
+    let emergency_remove_route = warp::path!("liquidity" / "emergency-remove")
+        .and(warp::post())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::body::json())
+        .and(with_pools(pools.clone()))
+        .and(with_history(history.clone()))
+        .and(with_events(events.clone()))
+        .and(with_config(config.clone()))
+        .and_then(handle_emergency_remove);
+    
+    let tokens_route = warp::path("tokens")
+        .and(warp::get())
+        .and(with_pools(pools.clone()))
+        .and_then(handle_get_tokens);
+    
+    let price_route = warp::path!("pools" / String / "price")
+        .and(warp::get())
+        .and(warp::query::<PriceQuery>())
+        .and(with_pools(pools.clone()))
+        .and_then(handle_get_price);
+    
+    let batch_price_route = warp::path("prices")
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_pools(pools.clone()))
+        .and_then(handle_batch_prices);
+    
+    let rewards_route = warp::path!("pools" / String / "rewards" / String)
+        .and(warp::get())
+        .and(warp::query::<RewardsQuery>())
+        .and(with_pools(pools.clone()))
+        .and_then(handle_get_rewards);
+    
+    let apy_route = warp::path!("pools" / String / "apy")
+        .and(warp::get())
+        .and(with_pools(pools.clone()))
+        .and(with_config(config.clone()))
+        .and_then(handle_get_apy);
+    
+    let fee_tiers_route = warp::path!("pairs" / String / String / "fee-tiers")
+        .and(warp::get())
+        .and(with_pools(pools.clone()))
+        .and_then(handle_get_fee_tiers);
+    
+    let depth_route = warp::path("depth")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query::<DepthQuery>())
+        .and(with_pools(pools.clone()))
+        .and_then(handle_get_depth);
+    
+    let tvl_route = warp::path!("pools" / "tvl")
+        .and(warp::get())
+        .and(with_pools(pools.clone()))
+        .and(with_config(config.clone()))
+        .and_then(handle_get_tvl);
+    
+    let candles_route = warp::path!("pools" / String / "candles")
+        .and(warp::get())
+        .and(warp::query::<CandlesQuery>())
+        .and(with_pools(pools.clone()))
+        .and(with_history(history.clone()))
+        .and_then(handle_get_candles);
+    
+    let set_reserves_route = warp::path!("pools" / String / "reserves")
+        .and(warp::put())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::header::optional::<String>("if-match"))
+        .and(warp::body::json())
+        .and(with_pools(pools.clone()))
+        .and(with_history(history.clone()))
+        .and(with_config(config.clone()))
+        .and_then(handle_set_reserves);
+    
+    let metrics_route = warp::path("metrics")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::any().map(move || metrics.clone()))
+        .and_then(handle_get_metrics);
+    
+    let diff_route = warp::path!("admin" / "diff")
+        .and(warp::post())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::body::json())
+        .and(with_pools(pools.clone()))
+        .and(with_config(config.clone()))
+        .and_then(handle_diff);
+    
+    quote_route
+        .or(pair_quote_route)
         .or(swap_route)
         .or(pools_route)
+        .or(create_pool_route)
         .or(add_liquidity_route)
-        .with(cors);
-    
-    println!("DEX API server starting on http://localhost:3030");
-    warp::serve(routes).run(([127, 0, 0, 1], 3030)).await;
+        .or(batch_liquidity_route)
+        .or(remove_liquidity_route)
+        .or(emergency_remove_route)
+        .or(tokens_route)
+        .or(price_route)
+        .or(batch_price_route)
+        .or(rewards_route)
+        .or(candles_route)
+        .or(apy_route)
+        .or(tvl_route)
+        // After /pools/tvl, which would otherwise read as a pool id
+        .or(pool_route)
+        .or(fee_tiers_route)
+        .or(depth_route)
+        .or(set_reserves_route)
+        .or(diff_route)
+        .or(metrics_route)
+        .recover(errors::handle_rejection)
+        .with(cors)
 }
 
 fn with_pools(pools: PoolStorage) -> impl Filter<Extract = (PoolStorage,), Error = std::convert::Infallible> + Clone {
     warp::any().map(move || pools.clone())
 }
 
+fn with_history(history: ReserveHistory) -> impl Filter<Extract = (ReserveHistory,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || history.clone())
+}
+
+fn with_quote_cache(quote_cache: QuoteCache) -> impl Filter<Extract = (QuoteCache,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || quote_cache.clone())
+}
+
+fn with_events(events: PoolEvents) -> impl Filter<Extract = (PoolEvents,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || events.clone())
+}
+
+fn with_config(config: ApiConfig) -> impl Filter<Extract = (ApiConfig,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || config.clone())
+}
+
 async fn handle_quote(
     request: SwapRequest,
     pools: PoolStorage,
+    quote_cache: QuoteCache,
+    config: ApiConfig,
 ) -> Result<impl warp::Reply, warp::Rejection> {
     let pools_read = pools.read().await;
+    let cache_read = quote_cache.read().await;
     
-    // Find appropriate pool (simplified - in reality you'd have routing logic)
-    let pool = pools_read.values().find(|p| {
-        p.tokens.iter().any(|t| t.address == request.input_token) &&
-        p.tokens.iter().any(|t| t.address == request.output_token)
+    let response = parse_amount(&request.input_amount).and_then(|input_amount| {
+        quote_single(&pools_read, &cache_read, &request.input_token, &request.output_token, &input_amount, request.slippage_tolerance, &config)
     });
     
-    if let Some(pool) = pool {
-        let input_amount = request.input_amount.parse::<num_bigint::BigUint>()
-            .map_err(|_| warp::reject::reject())?;
-        
-        match pool.calculate_swap_output(&request.input_token, &request.output_token, &input_amount) {
-            Ok(output_amount) => {
-                let response = SwapResponse {
-                    output_amount: output_amount.to_string(),
-                    price_impact: calculate_price_impact(&pool, &request.input_token, &input_amount),
-                    fee: (input_amount.clone() * pool.fee_rate / 10000u64).to_string(),
-                    route: vec![request.input_token, request.output_token],
-                };
-                Ok(warp::reply::json(&response))
-            }
-            Err(_) => Err(warp::reject::reject()),
-        }
-    } else {
-        Err(warp::reject::reject())
-    }
+    Ok(reply(response))
 }
 
-async fn handle_swap(
-    request: SwapRequest,
+async fn handle_pair_quote(
+    query: PairQuoteQuery,
     pools: PoolStorage,
+    quote_cache: QuoteCache,
+    config: ApiConfig,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    // This would integrate with the smart contract layer
-    // For now, we'll return a mock response
-    handle_quote(request, pools).await
-}
-
-async fn handle_get_pools(pools: PoolStorage) -> Result<impl warp::Reply, warp::Rejection> {
     let pools_read = pools.read().await;
-    let pool_infos: Vec<PoolInfo> = pools_read.values().map(|pool| {
-        PoolInfo {
-            id: pool.id.clone(),
-            tokens: pool.tokens.clone(),
-            reserves: pool.reserves.iter().map(|(k, v)| (k.clone(), v.to_string())).collect(),
-            total_supply: pool.total_supply.to_string(),
-            fee_rate: pool.fee_rate,
-            apy: calculate_apy(&pool),
-            volume_24h: "1000000".to_string(), // Mock data
-        }
-    }).collect();
+    let cache_read = quote_cache.read().await;
     
-    Ok(warp::reply::json(&pool_infos))
+    // Both sides are quoted at the same size so the UI can show the spread
+    let response = parse_amount(&query.amount).and_then(|amount| {
+        Ok(PairQuoteResponse {
+            a_to_b: quote_single(&pools_read, &cache_read, &query.token_a, &query.token_b, &amount, query.slippage_tolerance, &config)?,
+            b_to_a: quote_single(&pools_read, &cache_read, &query.token_b, &query.token_a, &amount, query.slippage_tolerance, &config)?,
+        })
+    });
+    
+    Ok(reply(response))
 }
 
-async fn handle_add_liquidity(
-    request: AddLiquidityRequest,
-    pools: PoolStorage,
-) -> Result<impl warp::Reply, warp::Rejection> {
-    let mut pools_write = pools.write().await;
-    
-    if let Some(pool) = pools_write.get_mut(&request.pool_id) {
-        let mut token_amounts = HashMap::new();
-        
-        for (token, amount_str) in request.token_amounts {
-            let amount = amount_str.parse::<num_bigint::BigUint>()
-                .map_err(|_| warp::reject::reject())?;
-            token_amounts.insert(token, amount);
-        }
-        
-        match pool.add_liquidity(token_amounts) {
-            Ok(lp_tokens) => {
-                let response = serde_json::json!({
-                    "lp_tokens": lp_tokens.to_string(),
-                    "success": true
-                });
-                Ok(warp::reply::json(&response))
-            }
-            Err(_) => Err(warp::reject::reject()),
-        }
-    } else {
-        Err(warp::reject::reject())
-    }
+// `expected` less `slippage_tolerance` percent, rounded down
+// The tolerance goes through Decimal, which keeps it as the client wrote
+// it, so the minimum is exact rather than rounded to whole bps
+fn minimum_output(expected: &num_bigint::BigUint, slippage_tolerance: f64) -> Result<num_bigint::BigUint, ErrorResponse> {
+    rust_decimal::Decimal::from_f64(slippage_tolerance)
+        .and_then(|tolerance| min_output_for_slippage(expected, tolerance))
+        .ok_or_else(|| ErrorResponse::new(ErrorCode::InvalidAmount, format!("Invalid slippage tolerance: {}", slippage_tolerance)))
 }
 
-async fn initialize_sample_pools(pools: &PoolStorage) {
-    let mut pools_write = pools.write().await;
-    
-    // ETH/USDC pool
-    let eth_token = Token {
-        address: "0x0000000000000000000000000000000000000000".to_string(),
-        symbol: "ETH".to_string(),
-        decimals: 18,
-    };
-    
-    let usdc_token = Token {
-        address: "0xA0b86a33E6441B8C5c4EA1E18AA41bE2d5E27ad2".to_string(),
-        symbol: "USDC".to_string(),
-        decimals: 6,
+fn parse_amount(amount: &str) -> Result<num_bigint::BigUint, ErrorResponse> {
+    amount.parse().map_err(|_| ErrorResponse::new(ErrorCode::InvalidAmount, format!("Invalid amount: {}", amount)))
+}
+
+// Refuses the change if the client saw an older version of the pool than
+// the current one. Accepts the version bare or quoted like an ETag.
+fn check_version(pool: &Pool, if_match: Option<&str>) -> Result<(), ErrorResponse> {
+    let expected = match if_match {
+        Some(expected) => expected.trim().trim_matches('"'),
+        None => return Ok(()),
     };
+    let expected: u64 = expected.parse()
+        .map_err(|_| ErrorResponse::new(ErrorCode::InvalidRequest, "If-Match must be a pool version"))?;
     
-    let mut reserves = HashMap::new();
-    reserves.insert(eth_token.address.clone(), num_bigint::BigUint::from(1000000000000000000u64)); // 1 ETH
-    reserves.insert(usdc_token.address.clone(), num_bigint::BigUint::from(2000000000u64)); // 2000 USDC
-    
-    let pool = Pool::new(
-        "ETH-USDC".to_string(),
-        vec![eth_token, usdc_token],
-        reserves,
-        300, // 3% fee
-        PoolType::ConstantProduct,
-    );
-    
-    pools_write.insert("ETH-USDC".to_string(), pool);
+    if expected != pool.version {
+        return Err(ErrorResponse::new(
+            ErrorCode::VersionConflict,
+            format!("Pool {} is at version {}, not {}", pool.id, pool.version, expected),
+        )
+        .with_detail("version", pool.version));
+    }
+    Ok(())
 }
 
-fn calculate_price_impact(pool: &Pool, input_token: &str, input_amount: &num_bigint::BigUint) -> f64 {
-    // Simplified price impact calculation
-    if let Some(input_reserve) = pool.reserves.get(input_token) {
-        let impact = input_amount.clone() * 100u64 / input_reserve;
-        impact.to_string().parse::<f64>().unwrap_or(0.0)
-    } else {
-        0.0
-    }
+fn no_pool_for_pair(input_token: &str, output_token: &str) -> ErrorResponse {
+    ErrorResponse::new(ErrorCode::PoolNotFound, format!("No pool trades {} for {}", input_token, output_token))
+}
+
+// Pools sorted by id, so ties between routes go the same way every time
+fn sorted_pools(pools: &HashMap<String, Pool>) -> Vec<&Pool> {
+    let mut sorted: Vec<&Pool> = pools.values().collect();
+    sorted.sort_by(|a, b| a.id.cmp(&b.id));
+    sorted
+}
+
+// The pools' own spelling of each token, which routes are keyed by
+fn route_addresses(pools: &[&Pool], input_token: &str, output_token: &str) -> Result<(String, String), ErrorResponse> {
+    let address = |token: &str| pools.iter().find_map(|pool| pool.find_token(token)).map(|t| t.address.clone());
+    address(input_token).zip(address(output_token))
+        .ok_or_else(|| no_pool_for_pair(input_token, output_token))
 }
 
-fn calculate_apy(pool: &Pool) -> f64 {
-    // Mock APY calculation - in reality this would use historical data
-    match pool.pool_type {
-        PoolType::ConstantProduct => 12.5,
-        PoolType::StableSwap => 8.2,
-        PoolType::ConcentratedLiquidity => 25.7,
+// The router's best route through up to MAX_ROUTE_HOPS allowed pools
+fn best_route(pools: &[&Pool], input: &str, output: &str, input_amount: &num_bigint::BigUint, config: &ApiConfig) -> Result<Route, ErrorResponse> {
+    if let Some(route) = find_best_route_with(pools, input, output, input_amount, MAX_ROUTE_HOPS, &config.routing) {
+        return Ok(route);
     }
-}
\ No newline at end of file
+    // Nothing quotes, so report why the direct pool cannot, if there is one
+    let pool = pools.iter().find(|p| config.routing.allows(p) && p.supports_pair(input, output))
+        .ok_or_else(|| no_pool_for_pair(input, output))?;
+    Ok(Route::quote(&[pool], &[input, output], input_amount)?)
+}
+
+// The fee, price impact and warning describe the first hop, which is the
+// one taking the client's input
+fn quote_single(
+    pools: &HashMap<String, Pool>,
+    quote_cache: &WarmQuotes,
+    input_token: &str,
+    output_token: &str,
+    input_amount: &num_bigint::BigUint,
+    slippage_tolerance: f64,
+    config: &ApiConfig,
+) -> Result<SwapResponse, ErrorResponse> {
+    let candidates = sorted_pools(pools);
+    let (input, output) = route_addresses(&candidates, input_token, output_token)?;
+    let route = match quote_cache.get(pools, &QuoteKey::new(&input, &output, input_amount)) {
+        Some(route) => route.clone(),
+        None => best_route(&candidates, &input, &output, input_amount, config)?,
+    };
+    let pool = find_pool(pools, &route.pools[0])?;
+    let (input, output) = (&route.path[0], &route.path[1]);
+    let (_, fee) = pool.quote_with_fee(input, output, input_amount)?;
+    let fee_token = pool_token(pool, &fee.token)?;
+    let fee = fee.amount;
+    let price_impact = calculate_price_impact(pool, input, input_amount)?;
+    let now = unix_now();
+    
+    Ok(SwapResponse {
+        output_amount: route.expected_output.to_string(),
+        expected_output: route.expected_output.to_string(),
+        minimum_output: minimum_output(&route.expected_output, slippage_tolerance)?.to_string(),
+        price_impact: price_impact.to_string(),
+        fee: fee.to_string(),
+        fee_formatted: format_units(&fee, fee_token.decimals),
+        fee_token,
+        total_fee_bps: route.total_fee_bps().to_f64().unwrap_or(0.0),
+        route: route_hops(pools, &route)?,
+        receipt_id: None,
+        quoted_at: Some(now),
+        valid_until: Some(now + config.quote_ttl_secs()),
+        warning: config.swap_warning(pool, input, input_amount, price_impact),
+    })
+}
+
+async fn handle_swap(
+    request: SwapRequest,
+    if_match: Option<String>,
+    pools: PoolStorage,
+    history: ReserveHistory,
+    events: PoolEvents,
+    config: ApiConfig,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(reply(execute_swap_request(request, if_match.as_deref(), &pools, &history, &events, &config).await))
+}
+
+async fn execute_swap_request(
+    request: SwapRequest,
+    if_match: Option<&str>,
+    pools: &PoolStorage,
+    history: &ReserveHistory,
+    events: &PoolEvents,
+    config: &ApiConfig,
+) -> Result<SwapResponse, ErrorResponse> {
+    if let Some(quoted_at) = request.quote_timestamp {
+        let valid_until = quoted_at.saturating_add(config.quote_ttl_secs());
+        if unix_now() > valid_until {
+            return Err(ErrorResponse::new(ErrorCode::QuoteExpired, "quote has expired; request a new one")
+                .with_detail("valid_until", valid_until));
+        }
+    }
+    
+    // Executes against the in-memory pools; on-chain settlement is the contracts layer's job
+    let mut pools_write = pools.write().await;
+    
+    // The route `/quote` gives, requoted under the same lock the swap
+    // executes under; the execution may still come out lower, e.g. when
+    // the fee moves in between
+    let input_amount = parse_amount(&request.input_amount)?;
+    let route = {
+        let candidates = sorted_pools(&pools_write);
+        let (input, output) = route_addresses(&candidates, &request.input_token, &request.output_token)?;
+        best_route(&candidates, &input, &output, &input_amount, config)?
+    };
+    let quoted = route.expected_output.clone();
+    let mut min_output = minimum_output(&quoted, request.slippage_tolerance)?;
+    // A quote the client saw earlier can only raise the bar
+    if let Some(expected) = &request.expected_output {
+        min_output = min_output.max(minimum_output(&parse_amount(expected)?, request.slippage_tolerance)?);
+    }
+    
+    // The version, impact and warning are the first hop's, as in `quote_single`
+    let pool = find_pool(&pools_write, &route.pools[0])?;
+    let input_token = &route.path[0];
+    check_version(pool, if_match)?;
+    // Checked before the impact is converted, so oversized swaps still get the 422
+    if let Some(max_bps) = config.max_price_impact_bps {
+        let impact_bps = price_impact_bps(pool, input_token, &input_amount);
+        if impact_bps > max_bps.into() {
+            return Err(ErrorResponse::new(ErrorCode::PriceImpactTooHigh, "price impact exceeds maximum")
+                .with_detail("price_impact_bps", impact_bps.to_string())
+                .with_detail("max_price_impact_bps", max_bps));
+        }
+    }
+    
+    let price_impact = calculate_price_impact(pool, input_token, &input_amount)?;
+    // Taken against the reserves the swap trades on, before it moves them
+    let warning = config.swap_warning(pool, input_token, &input_amount, price_impact);
+    let receipts = commit_all(&mut pools_write, &route.pools, |pools| {
+        let now = unix_now();
+        let mut receipts: Vec<SwapReceipt> = Vec::with_capacity(route.pools.len());
+        for (pool_id, hop) in route.pools.iter().zip(route.path.windows(2)) {
+            let pool = pools.get_mut(pool_id)
+                .ok_or_else(|| ErrorResponse::new(ErrorCode::PoolNotFound, format!("Pool {} not found", pool_id)))?;
+            let amount = receipts.last().map_or(&input_amount, |receipt| &receipt.output_amount);
+            // Closes the oracle interval at the price it ran at
+            pool.accumulate_price(now);
+            let receipt = pool.execute_swap_with_receipt(&hop[0], &hop[1], amount)?;
+            receipts.push(receipt);
+        }
+        let output_amount = &receipts[receipts.len() - 1].output_amount;
+        if *output_amount < min_output {
+            return Err(ErrorResponse::from(SwapError::SlippageExceeded {
+                expected: min_output.clone(),
+                actual: output_amount.clone(),
+            }));
+        }
+        Ok(receipts)
+    })?;
+    for receipt in &receipts {
+        record_snapshot(history, &pools_write[&receipt.pool_id]).await;
+        events::publish(events, PoolEvent::from(receipt));
+    }
+    let (first, last) = (&receipts[0], &receipts[receipts.len() - 1]);
+    let fee_token = pool_token(&pools_write[&first.pool_id], &first.fee_token)?;
+    
+    Ok(SwapResponse {
+        output_amount: last.output_amount.to_string(),
+        expected_output: quoted.to_string(),
+        minimum_output: min_output.to_string(),
+        price_impact: price_impact.to_string(),
+        fee: first.fee.to_string(),
+        fee_formatted: format_units(&first.fee, fee_token.decimals),
+        fee_token,
+        total_fee_bps: route.total_fee_bps().to_f64().unwrap_or(0.0),
+        route: route_hops(&pools_write, &route)?,
+        // The first hop's, which took the client's input
+        receipt_id: Some(first.id.to_string()),
+        quoted_at: None,
+        valid_until: None,
+        warning,
+    })
+}
+
+// Describes each hop of `route` with the pool and full token details
+fn route_hops(pools: &HashMap<String, Pool>, route: &Route) -> Result<Vec<RouteHop>, ErrorResponse> {
+    route.pools.iter().zip(route.path.windows(2)).map(|(pool_id, hop)| {
+        let pool = find_pool(pools, pool_id)?;
+        route_hop(pool, &hop[0], &hop[1])
+    }).collect()
+}
+
+fn route_hop(pool: &Pool, token_in: &str, token_out: &str) -> Result<RouteHop, ErrorResponse> {
+    Ok(RouteHop {
+        pool_id: pool.id.clone(),
+        pool_version: pool.version,
+        token_in: pool_token(pool, token_in)?,
+        token_out: pool_token(pool, token_out)?,
+    })
+}
+
+fn find_pool<'a>(pools: &'a HashMap<String, Pool>, pool_id: &str) -> Result<&'a Pool, ErrorResponse> {
+    pools.get(pool_id).ok_or_else(|| ErrorResponse::new(ErrorCode::PoolNotFound, format!("Pool {} not found", pool_id)))
+}
+
+fn pool_token(pool: &Pool, token: &str) -> Result<Token, ErrorResponse> {
+    pool.find_token(token).cloned().ok_or_else(|| {
+        ErrorResponse::new(ErrorCode::TokenNotFound, format!("Token {} is not in pool {}", token, pool.id))
+    })
+}
+
+// The pool's own spelling of each address, which its reserves are keyed by
+fn pool_addresses(pool: &Pool, input_token: &str, output_token: &str) -> Result<(String, String), ErrorResponse> {
+    Ok((pool_token(pool, input_token)?.address, pool_token(pool, output_token)?.address))
+}
+
+async fn handle_get_pools(query: PoolsQuery, pools: PoolStorage, config: ApiConfig) -> Result<impl warp::Reply, warp::Rejection> {
+    let pools_read = pools.read().await;
+    let pool_infos: Vec<PoolInfo> = pools_read.values()
+        .filter(|pool| query.tag.as_ref().is_none_or(|tag| pool.tags.contains(tag)))
+        .map(|pool| pool_info(pool, &config))
+        .collect();
+    
+    Ok(warp::reply::json(&pool_infos))
+}
+
+async fn handle_get_pool(pool_id: String, pools: PoolStorage, config: ApiConfig) -> Result<impl warp::Reply, warp::Rejection> {
+    let pools_read = pools.read().await;
+    
+    Ok(reply(find_pool(&pools_read, &pool_id).map(|pool| pool_info(pool, &config))))
+}
+
+fn pool_info(pool: &Pool, config: &ApiConfig) -> PoolInfo {
+    PoolInfo {
+        id: pool.id.clone(),
+        pool_type: pool.pool_type.to_string(),
+        tokens: pool.tokens.clone(),
+        reserves: pool.reserves.iter().map(|(k, v)| (k.clone(), v.to_string())).collect(),
+        total_supply: pool.total_supply.to_string(),
+        fee_rate: pool.fee_rate,
+        apy: calculate_apy(pool, config).total_apy,
+        volume_24h: "1000000".to_string(), // Mock data
+        cumulative_fees: pool.cumulative_fees.iter().map(|(k, v)| (k.clone(), v.to_string())).collect(),
+        utilization: pool.reserves.keys().map(|token| (token.clone(), pool.utilization(token).to_string())).collect(),
+        tags: pool.tags.clone(),
+        version: pool.version,
+    }
+}
+
+// Admin-only: adds a pool without restarting the server
+async fn handle_create_pool(
+    authorization: Option<String>,
+    request: CreatePoolRequest,
+    pools: PoolStorage,
+    history: ReserveHistory,
+    config: ApiConfig,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if !config.is_admin(authorization.as_deref()) {
+        return Ok(ErrorResponse::new(ErrorCode::Unauthorized, "admin token required").reply());
+    }
+    
+    let mut pools_write = pools.write().await;
+    let result = create_pool_in(&mut pools_write, request, &config.token_decimals);
+    if let Ok(pool) = &result {
+        println!("Admin created pool: {}", pool.describe());
+        record_snapshot(&history, pool).await;
+    }
+    
+    Ok(reply(result.map(|pool| pool_info(pool, &config))))
+}
+
+fn create_pool_in<'a>(
+    pools: &'a mut HashMap<String, Pool>,
+    request: CreatePoolRequest,
+    token_decimals: &pool_config::TokenDecimals,
+) -> Result<&'a Pool, ErrorResponse> {
+    if pools.contains_key(&request.id) {
+        return Err(ErrorResponse::new(ErrorCode::PoolAlreadyExists, format!("Pool {} already exists", request.id)));
+    }
+    let pool = request.into_config(token_decimals)
+        .and_then(dex_protocol_core::config::build_pool)
+        .map_err(|reason| ErrorResponse::new(ErrorCode::InvalidRequest, reason))?;
+    Ok(pools.entry(pool.id.clone()).or_insert(pool))
+}
+
+// Admin-only: overwrites a pool's reserves, for seeding test deployments
+async fn handle_set_reserves(
+    pool_id: String,
+    authorization: Option<String>,
+    if_match: Option<String>,
+    reserves: HashMap<String, String>,
+    pools: PoolStorage,
+    history: ReserveHistory,
+    config: ApiConfig,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if !config.is_admin(authorization.as_deref()) {
+        return Ok(ErrorResponse::new(ErrorCode::Unauthorized, "admin token required").reply());
+    }
+    
+    let mut pools_write = pools.write().await;
+    let result = set_reserves_of(&mut pools_write, &pool_id, reserves, if_match.as_deref());
+    if let Ok(pool) = &result {
+        println!("Admin set reserves: {}", pool.describe());
+        record_snapshot(&history, pool).await;
+    }
+    
+    Ok(reply(result.map(|pool| pool_info(pool, &config))))
+}
+
+fn set_reserves_of<'a>(
+    pools: &'a mut HashMap<String, Pool>,
+    pool_id: &str,
+    reserves: HashMap<String, String>,
+    if_match: Option<&str>,
+) -> Result<&'a Pool, ErrorResponse> {
+    let mut amounts = HashMap::new();
+    for (token, amount) in reserves {
+        amounts.insert(token, parse_amount(&amount)?);
+    }
+    
+    let pool = pools.get_mut(pool_id)
+        .ok_or_else(|| ErrorResponse::new(ErrorCode::PoolNotFound, format!("Pool {} not found", pool_id)))?;
+    check_version(pool, if_match)?;
+    commit(pool, |pool| pool.set_reserves(amounts)).map_err(|e| match e {
+        LiquidityError::InsufficientLiquidity => ErrorResponse::new(ErrorCode::InvalidReserves, "every reserve must be nonzero"),
+        _ => ErrorResponse::new(ErrorCode::InvalidReserves, "reserves must list each pool token exactly once"),
+    })?;
+    Ok(pool)
+}
+
+// Admin-only: compares the live pools with a baseline, e.g. another
+// deployment's `GET /pools`, and reports the pools that differ
+async fn handle_diff(
+    authorization: Option<String>,
+    baseline: Vec<PoolBaseline>,
+    pools: PoolStorage,
+    config: ApiConfig,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if !config.is_admin(authorization.as_deref()) {
+        return Ok(ErrorResponse::new(ErrorCode::Unauthorized, "admin token required").reply());
+    }
+    
+    let pools_read = pools.read().await;
+    Ok(reply(diff_pools(&pools_read, baseline)))
+}
+
+fn diff_pools(pools: &HashMap<String, Pool>, baseline: Vec<PoolBaseline>) -> Result<Vec<PoolDiff>, ErrorResponse> {
+    let mut baselines = HashMap::new();
+    for entry in baseline {
+        let mut reserves = HashMap::new();
+        for (token, amount) in &entry.reserves {
+            reserves.insert(token.clone(), parse_amount(amount)?);
+        }
+        let total_supply = parse_amount(&entry.total_supply)?;
+        baselines.insert(entry.id, (reserves, total_supply, entry.fee_rate));
+    }
+    
+    let ids: BTreeSet<&String> = pools.keys().chain(baselines.keys()).collect();
+    let mut diffs = Vec::new();
+    for id in ids {
+        let current = pools.get(id);
+        let baseline = baselines.get(id);
+        let empty = HashMap::new();
+        let current_reserves = current.map_or(&empty, |pool| &pool.reserves);
+        let baseline_reserves = baseline.map_or(&empty, |(reserves, _, _)| reserves);
+        let tokens: BTreeSet<&String> = current_reserves.keys().chain(baseline_reserves.keys()).collect();
+        let reserves: HashMap<String, Change<String>> = tokens.into_iter()
+            .filter_map(|token| {
+                let change = change(
+                    baseline_reserves.get(token).map(|amount| amount.to_string()),
+                    current_reserves.get(token).map(|amount| amount.to_string()),
+                )?;
+                Some((token.clone(), change))
+            })
+            .collect();
+        
+        let diff = PoolDiff {
+            pool_id: id.clone(),
+            reserves_match: current.is_some() && baseline.is_some()
+                && reserves_checksum(current_reserves) == reserves_checksum(baseline_reserves),
+            reserves,
+            total_supply: change(
+                baseline.map(|(_, total_supply, _)| total_supply.to_string()),
+                current.map(|pool| pool.total_supply.to_string()),
+            ),
+            fee_rate: change(baseline.map(|(_, _, fee_rate)| *fee_rate), current.map(|pool| pool.fee_rate)),
+        };
+        if !diff.reserves_match || diff.total_supply.is_some() || diff.fee_rate.is_some() {
+            diffs.push(diff);
+        }
+    }
+    Ok(diffs)
+}
+
+fn change<T: PartialEq>(baseline: Option<T>, current: Option<T>) -> Option<Change<T>> {
+    (baseline != current).then_some(Change { baseline, current })
+}
+
+async fn handle_get_tokens(pools: PoolStorage) -> Result<impl warp::Reply, warp::Rejection> {
+    let pools_read = pools.read().await;
+    
+    // The same token can be listed by several pools; report it once
+    let mut seen = HashSet::new();
+    let mut tokens: Vec<Token> = Vec::new();
+    for pool in pools_read.values() {
+        for token in &pool.tokens {
+            if seen.insert(normalize_address(&token.address)) {
+                tokens.push(token.clone());
+            }
+        }
+    }
+    tokens.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+    
+    Ok(warp::reply::json(&tokens))
+}
+
+async fn handle_add_liquidity(
+    request: AddLiquidityRequest,
+    if_match: Option<String>,
+    pools: PoolStorage,
+    history: ReserveHistory,
+    events: PoolEvents,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let mut pools_write = pools.write().await;
+    let pool_id = request.pool_id.clone();
+    
+    let result = add_liquidity_to(&mut pools_write, request, if_match.as_deref(), &events);
+    if result.is_ok() {
+        record_snapshot(&history, &pools_write[&pool_id]).await;
+    }
+    
+    Ok(reply(result.map(|lp_tokens| serde_json::json!({
+        "lp_tokens": lp_tokens.to_string(),
+        "pool_version": pools_write[&pool_id].version,
+        "success": true
+    }))))
+}
+
+async fn handle_batch_add_liquidity(
+    requests: Vec<AddLiquidityRequest>,
+    pools: PoolStorage,
+    history: ReserveHistory,
+    events: PoolEvents,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let mut pools_write = pools.write().await;
+    
+    // Each pool either takes its whole deposit or none of it; one failing
+    // pool does not undo the others
+    let results: Vec<BatchLiquidityResult> = requests.into_iter().map(|request| {
+        let pool_id = request.pool_id.clone();
+        match add_liquidity_to(&mut pools_write, request, None, &events) {
+            Ok(lp_tokens) => BatchLiquidityResult {
+                pool_id,
+                success: true,
+                lp_tokens: Some(lp_tokens.to_string()),
+                error: None,
+                code: None,
+            },
+            Err(error) => BatchLiquidityResult {
+                pool_id,
+                success: false,
+                lp_tokens: None,
+                error: Some(error.message),
+                code: Some(error.code.as_u16()),
+            },
+        }
+    }).collect();
+    
+    for result in results.iter().filter(|r| r.success) {
+        record_snapshot(&history, &pools_write[&result.pool_id]).await;
+    }
+    
+    Ok(warp::reply::json(&results))
+}
+
+fn add_liquidity_to(
+    pools: &mut HashMap<String, Pool>,
+    request: AddLiquidityRequest,
+    if_match: Option<&str>,
+    events: &PoolEvents,
+) -> Result<num_bigint::BigUint, ErrorResponse> {
+    let pool = pools.get_mut(&request.pool_id)
+        .ok_or_else(|| ErrorResponse::new(ErrorCode::PoolNotFound, format!("Pool {} not found", request.pool_id)))?;
+    check_version(pool, if_match)?;
+    
+    // All amounts are parsed before the pool is touched
+    let mut token_amounts = HashMap::new();
+    for (token, amount_str) in request.token_amounts {
+        let amount = amount_str.parse::<num_bigint::BigUint>().map_err(|_| {
+            ErrorResponse::new(ErrorCode::InvalidAmount, format!("Invalid amount for {}: {}", token, amount_str))
+        })?;
+        token_amounts.insert(token, amount);
+    }
+    
+    let lp_tokens = commit(pool, |pool| match &request.owner {
+        Some(owner) => pool.add_liquidity_for(owner, token_amounts.clone()),
+        None => pool.add_liquidity(token_amounts.clone()),
+    })?;
+    events::publish(events, PoolEvent::LiquidityAdded {
+        pool_id: request.pool_id,
+        token_amounts,
+        lp_tokens: lp_tokens.clone(),
+    });
+    Ok(lp_tokens)
+}
+
+async fn handle_remove_liquidity(
+    request: RemoveLiquidityRequest,
+    if_match: Option<String>,
+    pools: PoolStorage,
+    history: ReserveHistory,
+    events: PoolEvents,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let mut pools_write = pools.write().await;
+    
+    let result = remove_liquidity_from(&mut pools_write, request, if_match.as_deref(), &events);
+    if let Ok((pool, _)) = &result {
+        record_snapshot(&history, pool).await;
+    }
+    
+    Ok(reply(result.map(|(pool, amounts)| RemoveLiquidityResponse {
+        amounts: amounts.iter().map(|(token, amount)| (token.clone(), amount.to_string())).collect(),
+        pool_version: pool.version,
+    })))
+}
+
+fn remove_liquidity_from<'a>(
+    pools: &'a mut HashMap<String, Pool>,
+    request: RemoveLiquidityRequest,
+    if_match: Option<&str>,
+    events: &PoolEvents,
+) -> Result<(&'a Pool, HashMap<String, num_bigint::BigUint>), ErrorResponse> {
+    let lp_tokens = parse_amount(&request.lp_tokens)?;
+    let pool = pools.get_mut(&request.pool_id)
+        .ok_or_else(|| ErrorResponse::new(ErrorCode::PoolNotFound, format!("Pool {} not found", request.pool_id)))?;
+    check_version(pool, if_match)?;
+    
+    let amounts = commit(pool, |pool| match &request.owner {
+        Some(owner) => pool.remove_liquidity_for(owner, &lp_tokens),
+        None => pool.remove_liquidity(&lp_tokens),
+    })?;
+    events::publish(events, PoolEvent::LiquidityRemoved {
+        pool_id: request.pool_id,
+        token_amounts: amounts.clone(),
+        lp_tokens,
+    });
+    Ok((pool, amounts))
+}
+
+async fn handle_emergency_remove(
+    authorization: Option<String>,
+    request: EmergencyRemoveRequest,
+    pools: PoolStorage,
+    history: ReserveHistory,
+    events: PoolEvents,
+    config: ApiConfig,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if !config.is_admin(authorization.as_deref()) {
+        return Ok(ErrorResponse::new(ErrorCode::Unauthorized, "admin token required").reply());
+    }
+    
+    let mut pools_write = pools.write().await;
+    let result = emergency_remove_from(&mut pools_write, request, &events);
+    if let Ok((pool, _)) = &result {
+        println!("Admin emergency removal from pool: {}", pool.id);
+        record_snapshot(&history, pool).await;
+    }
+    
+    Ok(reply(result.map(|(pool, amounts)| RemoveLiquidityResponse {
+        amounts: amounts.iter().map(|(token, amount)| (token.clone(), amount.to_string())).collect(),
+        pool_version: pool.version,
+    })))
+}
+
+fn emergency_remove_from<'a>(
+    pools: &'a mut HashMap<String, Pool>,
+    request: EmergencyRemoveRequest,
+    events: &PoolEvents,
+) -> Result<(&'a Pool, HashMap<String, num_bigint::BigUint>), ErrorResponse> {
+    let lp_tokens = parse_amount(&request.lp_tokens)?;
+    let pool = pools.get_mut(&request.pool_id)
+        .ok_or_else(|| ErrorResponse::new(ErrorCode::PoolNotFound, format!("Pool {} not found", request.pool_id)))?;
+    
+    let amounts = commit(pool, |pool| pool.emergency_remove_liquidity(&lp_tokens, &request.owner))?;
+    events::publish(events, PoolEvent::LiquidityRemoved {
+        pool_id: request.pool_id,
+        token_amounts: amounts.clone(),
+        lp_tokens,
+    });
+    Ok((pool, amounts))
+}
+
+async fn handle_get_price(
+    pool_id: String,
+    query: PriceQuery,
+    pools: PoolStorage,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let pools_read = pools.read().await;
+    
+    // Any pair of the pool's tokens can be priced, not just the first two
+    let response = find_pool(&pools_read, &pool_id).and_then(|pool| {
+        let price = pool.spot_price(&query.base, &query.quote)?;
+        Ok(PriceResponse {
+            pool_id: pool.id.clone(),
+            base: query.base,
+            quote: query.quote,
+            price: price.to_string(),
+        })
+    });
+    Ok(reply(response))
+}
+
+async fn handle_batch_prices(
+    requests: Vec<PairPriceRequest>,
+    pools: PoolStorage,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let pools_read = pools.read().await;
+    
+    // A pair without a route fails on its own; the rest are still priced
+    let results: Vec<PairPriceResult> = requests.into_iter().map(|request| {
+        match pair_spot_price(&pools_read, &request.token_a, &request.token_b) {
+            Ok((price, pools)) => PairPriceResult {
+                token_a: request.token_a,
+                token_b: request.token_b,
+                price: Some(price.to_string()),
+                pools,
+                error: None,
+                code: None,
+            },
+            Err(error) => PairPriceResult {
+                token_a: request.token_a,
+                token_b: request.token_b,
+                price: None,
+                pools: Vec::new(),
+                error: Some(error.message),
+                code: Some(error.code.as_u16()),
+            },
+        }
+    }).collect();
+    
+    Ok(warp::reply::json(&results))
+}
+
+// Spot price of `base` in `quote` from a pool holding both, or else chained
+// through one intermediate token, returning the pools used
+fn pair_spot_price(
+    pools: &HashMap<String, Pool>,
+    base: &str,
+    quote: &str,
+) -> Result<(rust_decimal::Decimal, Vec<String>), ErrorResponse> {
+    let price_in = |pool: &Pool, base: &str, quote: &str| -> Result<rust_decimal::Decimal, ErrorResponse> {
+        let (base, quote) = pool_addresses(pool, base, quote)?;
+        Ok(pool.spot_price(&base, &quote)?)
+    };
+    
+    if let Some(pool) = pools.values().find(|p| p.supports_pair(base, quote)) {
+        return Ok((price_in(pool, base, quote)?, vec![pool.id.clone()]));
+    }
+    
+    for first in pools.values().filter(|p| p.supports_token(base)) {
+        for middle in first.tokens.iter().filter(|t| !is_same_token(&t.address, base)) {
+            let second = pools.values().find(|p| p.id != first.id && p.supports_pair(&middle.address, quote));
+            if let Some(second) = second {
+                let price = price_in(first, base, &middle.address)? * price_in(second, &middle.address, quote)?;
+                return Ok((price, vec![first.id.clone(), second.id.clone()]));
+            }
+        }
+    }
+    
+    Err(no_pool_for_pair(base, quote))
+}
+
+fn is_same_token(a: &str, b: &str) -> bool {
+    normalize_address(a) == normalize_address(b)
+}
+
+async fn handle_get_rewards(
+    pool_id: String,
+    owner: String,
+    query: RewardsQuery,
+    pools: PoolStorage,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let pools_read = pools.read().await;
+    
+    let response = parse_amount(&query.total_reward).and_then(|total_reward| {
+        let pool = find_pool(&pools_read, &pool_id)?;
+        Ok(RewardsResponse {
+            pool_id: pool.id.clone(),
+            lp_tokens: pool.lp_positions.get(&owner).cloned().unwrap_or_default().to_string(),
+            pending_rewards: calculate_rewards(pool, &total_reward, &owner).to_string(),
+            owner,
+        })
+    });
+    Ok(reply(response))
+}
+
+async fn handle_get_apy(
+    pool_id: String,
+    pools: PoolStorage,
+    config: ApiConfig,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let pools_read = pools.read().await;
+    
+    Ok(reply(find_pool(&pools_read, &pool_id).map(|pool| calculate_apy(pool, &config))))
+}
+
+async fn handle_get_depth(
+    query: DepthQuery,
+    pools: PoolStorage,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let pools_read = pools.read().await;
+    
+    let response = parse_price_levels(&query.levels).and_then(|levels| {
+        let depth = match &query.pool_id {
+            Some(pool_id) => {
+                let pool = find_pool(&pools_read, pool_id)?;
+                let (base, quote) = pool_addresses(pool, &query.token_a, &query.token_b)?;
+                levels.iter()
+                    .map(|&price| Ok((price, pool.depth_at_price(&base, &quote, price)?)))
+                    .collect::<Result<Vec<_>, ErrorResponse>>()?
+            }
+            None => {
+                let pools: Vec<Pool> = pools_read.values().cloned().collect();
+                aggregate_depth(&pools, &query.token_a, &query.token_b, &levels)
+            }
+        };
+        Ok(depth.into_iter()
+            .map(|(price, depth)| DepthLevel { price: price.to_string(), depth: depth.to_string() })
+            .collect::<Vec<_>>())
+    });
+    Ok(reply(response))
+}
+
+fn parse_price_levels(levels: &str) -> Result<Vec<rust_decimal::Decimal>, ErrorResponse> {
+    levels.split(',').map(|level| {
+        level.trim().parse()
+            .map_err(|_| ErrorResponse::new(ErrorCode::InvalidAmount, format!("Invalid price level: {}", level)))
+    }).collect()
+}
+
+async fn handle_get_fee_tiers(
+    token_a: String,
+    token_b: String,
+    pools: PoolStorage,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let pools_read = pools.read().await;
+    let pools: Vec<Pool> = pools_read.values().cloned().collect();
+    
+    Ok(warp::reply::json(&available_fee_tiers(&pools, &token_a, &token_b)))
+}
+
+// Pools by total value locked, highest first; unpriced pools come last
+async fn handle_get_tvl(
+    pools: PoolStorage,
+    config: ApiConfig,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let pools_read = pools.read().await;
+    
+    let mut ranked: Vec<_> = pools_read.values()
+        .map(|pool| (pool.id.clone(), pool.total_value(&config.token_prices)))
+        .collect();
+    ranked.sort_by(|(a_id, a), (b_id, b)| b.cmp(a).then_with(|| a_id.cmp(b_id)));
+    
+    let response: Vec<PoolTvl> = ranked.into_iter()
+        .map(|(pool_id, tvl)| PoolTvl { pool_id, tvl: tvl.map(|v| v.to_string()) })
+        .collect();
+    Ok(warp::reply::json(&response))
+}
+
+async fn handle_get_candles(
+    pool_id: String,
+    query: CandlesQuery,
+    pools: PoolStorage,
+    history: ReserveHistory,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let pools_read = pools.read().await;
+    let pair = find_pool(&pools_read, &pool_id).and_then(|pool| {
+        let default_token = |index: usize| {
+            pool.tokens.get(index).map(|t| t.address.clone())
+                .ok_or_else(|| ErrorResponse::new(ErrorCode::TokenNotFound, "Pool has fewer than two tokens"))
+        };
+        let base = query.base.clone().map_or_else(|| default_token(0), Ok)?;
+        let quote = query.quote.clone().map_or_else(|| default_token(1), Ok)?;
+        Ok((base, quote))
+    });
+    
+    let history_read = history.read().await;
+    let snapshots = history_read.get(&pool_id).map(Vec::as_slice).unwrap_or(&[]);
+    let candles = pair.map(|(base, quote)| build_candles(snapshots, &base, &quote, query.interval, query.count));
+    
+    Ok(reply(candles))
+}
+
+async fn handle_get_metrics(metrics: MetricsCollector) -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::json(&metrics.get_metrics().await))
+}
+
+async fn record_snapshot(history: &ReserveHistory, pool: &Pool) {
+    let mut history_write = history.write().await;
+    let snapshots = history_write.entry(pool.id.clone()).or_default();
+    snapshots.push(pool.snapshot());
+    if snapshots.len() > MAX_SNAPSHOTS_PER_POOL {
+        let excess = snapshots.len() - MAX_SNAPSHOTS_PER_POOL;
+        snapshots.drain(..excess);
+    }
+}
+
+async fn initialize_pools(pools: &PoolStorage, configs: Vec<pool_config::PoolConfig>) {
+    let (loaded, report) = pool_config::load_pools(configs);
+    report.log();
+    insert_pools(pools, loaded).await;
+}
+
+async fn insert_pools(pools: &PoolStorage, loaded: Vec<Pool>) {
+    let mut pools_write = pools.write().await;
+    for pool in loaded {
+        println!("  loaded {}", pool.describe());
+        pools_write.insert(pool.id.clone(), pool);
+    }
+}
+
+fn calculate_price_impact(
+    pool: &Pool,
+    input_token: &str,
+    input_amount: &num_bigint::BigUint,
+) -> Result<f64, PrecisionError> {
+    // Simplified price impact calculation, in percent of the input reserve.
+    // The ratio is taken in integer bps first so only the normalized value hits f64.
+    let impact_bps = price_impact_bps(pool, input_token, input_amount);
+    Ok(checked_biguint_to_f64(&impact_bps)? / 100.0)
+}
+
+fn price_impact_bps(
+    pool: &Pool,
+    input_token: &str,
+    input_amount: &num_bigint::BigUint,
+) -> num_bigint::BigUint {
+    match pool.reserves.get(input_token) {
+        Some(input_reserve) if input_reserve > &num_bigint::BigUint::from(0u64) => {
+            input_amount.clone() * 10000u64 / input_reserve
+        }
+        _ => num_bigint::BigUint::from(0u64),
+    }
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn calculate_apy(pool: &Pool, config: &ApiConfig) -> ApyResponse {
+    let now = unix_now();
+    let percent = |apy: rust_decimal::Decimal| (apy * rust_decimal::Decimal::from(100)).to_f64().unwrap_or(0.0);
+    
+    let fee = fee_apy(pool, now);
+    let reward = config.reward_programs.get(&pool.id)
+        .map(|program| reward_apy(pool, program))
+        .unwrap_or_default();
+    
+    ApyResponse {
+        pool_id: pool.id.clone(),
+        fee_apy: percent(fee),
+        reward_apy: percent(reward),
+        total_apy: percent(fee + reward),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn token(address: &str, symbol: &str, decimals: u8) -> Token {
+        Token {
+            address: address.to_string(),
+            symbol: symbol.to_string(),
+            decimals,
+        }
+    }
+    
+    fn create_pool(id: &str, tokens: Vec<Token>, reserve: u64) -> Pool {
+        let reserves = tokens
+            .iter()
+            .map(|t| (t.address.clone(), num_bigint::BigUint::from(reserve)))
+            .collect();
+        Pool::new(id.to_string(), tokens, reserves, 30, PoolType::ConstantProduct)
+    }
+    
+    async fn storage_with(pools: Vec<Pool>) -> PoolStorage {
+        let storage: PoolStorage = Arc::new(RwLock::new(HashMap::new()));
+        for pool in pools {
+            storage.write().await.insert(pool.id.clone(), pool);
+        }
+        storage
+    }
+    
+    fn empty_history() -> ReserveHistory {
+        Arc::new(RwLock::new(HashMap::new()))
+    }
+    
+    fn empty_quote_cache() -> QuoteCache {
+        Arc::new(RwLock::new(WarmQuotes::default()))
+    }
+    
+    #[test]
+    fn test_price_impact_precision_guard() {
+        // Reserves above 2^53 are fine: only the normalized ratio is converted
+        let reserve = 100u64 << 56;
+        let pool = create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], reserve);
+        
+        let impact = calculate_price_impact(&pool, "ETH", &num_bigint::BigUint::from(reserve / 100)).unwrap();
+        assert_eq!(impact, 1.0);
+        
+        // An input so large that even the ratio overflows the exact range is refused
+        let huge_input = num_bigint::BigUint::from(reserve) << 60u32;
+        assert!(calculate_price_impact(&pool, "ETH", &huge_input).is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_rewards_for_liquidity_provider() {
+        let pool = create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1000);
+        let pools = storage_with(vec![pool]).await;
+        let api = routes(pools, empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), ApiConfig::default());
+        
+        // Doubling the pool's liquidity gives alice half of the supply
+        let response = warp::test::request()
+            .method("POST")
+            .path("/liquidity")
+            .json(&serde_json::json!({
+                "pool_id": "ETH-USDC",
+                "token_amounts": { "ETH": "1000", "USDC": "1000" },
+                "owner": "alice"
+            }))
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), 200);
+        
+        let response = warp::test::request()
+            .method("GET")
+            .path("/pools/ETH-USDC/rewards/alice?total_reward=5000")
+            .reply(&api)
+            .await;
+        
+        assert_eq!(response.status(), 200);
+        let rewards: RewardsResponse = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(rewards.lp_tokens, "1000");
+        assert_eq!(rewards.pending_rewards, "2500");
+    }
+    
+    #[tokio::test]
+    async fn test_added_liquidity_can_be_removed() {
+        let pools = storage_with(vec![create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1000)]).await;
+        let api = routes(pools.clone(), empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), ApiConfig::default());
+        
+        let response = warp::test::request()
+            .method("POST")
+            .path("/liquidity")
+            .json(&serde_json::json!({
+                "pool_id": "ETH-USDC",
+                "token_amounts": { "ETH": "1000", "USDC": "1000" }
+            }))
+            .reply(&api)
+            .await;
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["lp_tokens"], "1000");
+        
+        // A quarter of the supply takes a quarter of each reserve
+        let remove = |lp_tokens: &str| {
+            warp::test::request()
+                .method("POST")
+                .path("/liquidity/remove")
+                .json(&serde_json::json!({ "pool_id": "ETH-USDC", "lp_tokens": lp_tokens }))
+        };
+        let response = remove("500").reply(&api).await;
+        assert_eq!(response.status(), 200);
+        let removed: RemoveLiquidityResponse = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(removed.amounts["ETH"], "500");
+        assert_eq!(removed.amounts["USDC"], "500");
+        let pools_read = pools.read().await;
+        assert_eq!(removed.pool_version, pools_read["ETH-USDC"].version);
+        assert_eq!(pools_read["ETH-USDC"].reserves["ETH"], num_bigint::BigUint::from(1500u64));
+        assert_eq!(pools_read["ETH-USDC"].total_supply, num_bigint::BigUint::from(1500u64));
+        drop(pools_read);
+        
+        let response = remove("1501").reply(&api).await;
+        assert_eq!(response.status(), 422);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["code"], ErrorCode::InsufficientLpTokens.as_u16());
+        assert_eq!(pools.read().await["ETH-USDC"].total_supply, num_bigint::BigUint::from(1500u64));
+    }
+    
+    #[tokio::test]
+    async fn test_three_token_pool_info_and_quote() {
+        let tokens = vec![token("USDC", "USDC", 18), token("USDT", "USDT", 18), token("DAI", "DAI", 18)];
+        let reserves = tokens
+            .iter()
+            .map(|t| (t.address.clone(), num_bigint::BigUint::from(1_000_000u64) * 10u64.pow(18)))
+            .collect();
+        let pool = Pool::new("STABLE-3".to_string(), tokens, reserves, 4, PoolType::StableSwap);
+        let api = routes(storage_with(vec![pool]).await, empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), ApiConfig::default());
+        
+        let response = warp::test::request().method("GET").path("/pools").reply(&api).await;
+        assert_eq!(response.status(), 200);
+        let infos: Vec<PoolInfo> = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(infos[0].tokens.len(), 3);
+        assert_eq!(infos[0].reserves.len(), 3);
+        assert_eq!(infos[0].pool_type, "stable_swap");
+        assert_eq!(infos[0].utilization["DAI"], "0");
+        
+        // Quote between the second and third token
+        let response = warp::test::request()
+            .method("POST")
+            .path("/quote")
+            .json(&serde_json::json!({
+                "input_token": "USDT",
+                "output_token": "DAI",
+                "input_amount": "1000000000000000000000",
+                "slippage_tolerance": 0.5
+            }))
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), 200);
+        let quote: SwapResponse = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(quote.route.len(), 1);
+        assert_eq!(quote.route[0].pool_id, "STABLE-3");
+        assert_eq!(quote.route[0].token_in.symbol, "USDT");
+        assert_eq!(quote.route[0].token_out.symbol, "DAI");
+        let output: num_bigint::BigUint = quote.output_amount.parse().unwrap();
+        assert!(output > num_bigint::BigUint::from(999u64) * 10u64.pow(18));
+        assert!(output < num_bigint::BigUint::from(1000u64) * 10u64.pow(18));
+        
+        let response = warp::test::request()
+            .method("GET")
+            .path("/pools/STABLE-3/price?base=DAI&quote=USDC")
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), 200);
+        let price: PriceResponse = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(price.price, "1");
+    }
+    
+    #[tokio::test]
+    async fn test_quote_shows_expected_and_guaranteed_output() {
+        let pool = create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1_000_000);
+        let pools = storage_with(vec![pool]).await;
+        let api = routes(pools, empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), ApiConfig::default());
+        
+        let quote = |slippage_tolerance: f64| warp::test::request()
+            .method("POST")
+            .path("/quote")
+            .json(&serde_json::json!({
+                "input_token": "ETH",
+                "output_token": "USDC",
+                "input_amount": "10000",
+                "slippage_tolerance": slippage_tolerance
+            }))
+            .reply(&api);
+        let response = quote(0.5).await;
+        assert_eq!(response.status(), 200);
+        let quote_body: SwapResponse = serde_json::from_slice(response.body()).unwrap();
+        
+        assert_eq!(quote_body.expected_output, quote_body.output_amount);
+        let expected: num_bigint::BigUint = quote_body.expected_output.parse().unwrap();
+        let minimum: num_bigint::BigUint = quote_body.minimum_output.parse().unwrap();
+        assert_eq!(expected, num_bigint::BigUint::from(9871u64));
+        // 0.5% below 9871, rounded down
+        assert_eq!(minimum, num_bigint::BigUint::from(9821u64));
+        assert_eq!(quote_body.fee, "30");
+        assert_eq!(quote_body.price_impact, "1");
+        
+        let response = quote(150.0).await;
+        assert_eq!(response.status(), 400);
+    }
+    
+    #[tokio::test]
+    async fn test_quote_warns_about_high_price_impact() {
+        let pool = create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1_000_000);
+        let pools = storage_with(vec![pool]).await;
+        let api = routes(pools, empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), ApiConfig::default());
+        
+        let quote = |input_amount: &str| warp::test::request()
+            .method("POST")
+            .path("/quote")
+            .json(&serde_json::json!({
+                "input_token": "ETH",
+                "output_token": "USDC",
+                "input_amount": input_amount,
+                "slippage_tolerance": 0.5
+            }))
+            .reply(&api);
+        
+        let response = quote("83000").await;
+        assert_eq!(response.status(), 200);
+        let large: SwapResponse = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(large.warning.as_deref(), Some("High price impact: 8.3%"));
+        
+        let response = quote("1000").await;
+        let small: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert!(small.get("warning").is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_panicking_handler_answers_500() {
+        let request = |path: &str| warp::hyper::Request::get(path).body(warp::hyper::Body::empty()).unwrap();
+        let panicking = warp::path("boom").map(|| -> &'static str { panic!("induced handler failure") });
+        
+        let response = catch_panics(warp::service(panicking), request("/boom")).await.unwrap();
+        assert_eq!(response.status(), 500);
+        let body = warp::hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], ErrorCode::InternalError.as_u16());
+        
+        // Requests that do not panic pass through untouched
+        let pool = create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1000);
+        let api = routes(storage_with(vec![pool]).await, empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), ApiConfig::default());
+        let response = catch_panics(warp::service(api), request("/pools")).await.unwrap();
+        assert_eq!(response.status(), 200);
+    }
+    
+    #[tokio::test]
+    async fn test_failed_swap_leaves_pool_untouched() {
+        let mut pool = create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1000);
+        pool.min_reserves = Some(HashMap::from([("USDC".to_string(), num_bigint::BigUint::from(900u64))]));
+        let pools = storage_with(vec![pool]).await;
+        let before = pools.read().await["ETH-USDC"].clone();
+        let api = routes(pools.clone(), empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), ApiConfig::default());
+        
+        // Would take USDC below its floor
+        let response = warp::test::request()
+            .method("POST")
+            .path("/swap")
+            .json(&serde_json::json!({
+                "input_token": "ETH",
+                "output_token": "USDC",
+                "input_amount": "500",
+                "slippage_tolerance": 0.5
+            }))
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), 422);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["code"], ErrorCode::ReserveFloorBreached.as_u16());
+        
+        // Not even the oracle clock, which the swap starts before failing
+        let pools_read = pools.read().await;
+        let after = &pools_read["ETH-USDC"];
+        assert_eq!(after.last_update_timestamp, before.last_update_timestamp);
+        assert_eq!(after.reserves, before.reserves);
+        assert_eq!(after.version, before.version);
+    }
+    
+    #[tokio::test]
+    async fn test_handlers_feed_metrics_through_events() {
+        let pools = storage_with(vec![create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1_000_000)]).await;
+        let events = events::channel();
+        let metrics = MetricsCollector::new();
+        metrics.subscribe(events.subscribe());
+        let api = routes(pools, empty_history(), empty_quote_cache(), events, metrics.clone(), ApiConfig::default());
+        
+        let swap = warp::test::request()
+            .method("POST")
+            .path("/swap")
+            .json(&serde_json::json!({
+                "input_token": "ETH",
+                "output_token": "USDC",
+                "input_amount": "1000",
+                "slippage_tolerance": 0.5
+            }))
+            .reply(&api)
+            .await;
+        assert_eq!(swap.status(), 200);
+        let deposit = warp::test::request()
+            .method("POST")
+            .path("/liquidity")
+            .json(&serde_json::json!({
+                "pool_id": "ETH-USDC",
+                "token_amounts": {"ETH": "1000", "USDC": "1000"}
+            }))
+            .reply(&api)
+            .await;
+        assert_eq!(deposit.status(), 200);
+        
+        // The collector catches up on its own task
+        for _ in 0..100 {
+            if !metrics.get_metrics().await.total_liquidity.is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let response = warp::test::request().path("/metrics").reply(&api).await;
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["total_swaps"], 1);
+        assert_eq!(body["total_volume"]["ETH"], "1000");
+        assert_eq!(body["total_fees_collected"]["ETH"], "3");
+        assert_eq!(body["total_liquidity"]["USDC"], "1000");
+    }
+    
+    #[tokio::test]
+    async fn test_routing_config_skips_excluded_pools() {
+        let tokens = || vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)];
+        let pools = storage_with(vec![create_pool("OLD", tokens(), 1_000_000), create_pool("NEW", tokens(), 1_000_000)]).await;
+        let swap = serde_json::json!({
+            "input_token": "ETH",
+            "output_token": "USDC",
+            "input_amount": "1000",
+            "slippage_tolerance": 0.5
+        });
+        let request = |path: &str| warp::test::request().method("POST").path(path).json(&swap);
+        
+        let routing = RoutingConfig { excluded_pool_ids: HashSet::from(["OLD".to_string()]), ..RoutingConfig::default() };
+        let config = ApiConfig { routing, ..ApiConfig::default() };
+        let api = routes(pools.clone(), empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), config);
+        for path in ["/quote", "/swap"] {
+            let response = request(path).reply(&api).await;
+            assert_eq!(response.status(), 200);
+            let body: SwapResponse = serde_json::from_slice(response.body()).unwrap();
+            assert_eq!(body.route[0].pool_id, "NEW");
+        }
+        
+        // An only-list naming no pool for the pair leaves nothing to route through
+        let routing = RoutingConfig { only_pool_ids: Some(HashSet::from(["ETH-DAI".to_string()])), ..RoutingConfig::default() };
+        let config = ApiConfig { routing, ..ApiConfig::default() };
+        let api = routes(pools, empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), config);
+        let response = request("/quote").reply(&api).await;
+        assert_eq!(response.status(), 404);
+        assert_eq!(parse_pool_ids(" OLD, ,NEW"), HashSet::from(["OLD".to_string(), "NEW".to_string()]));
+    }
+    
+    #[tokio::test]
+    async fn test_quote_warns_about_large_swaps() {
+        let pool = create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1_000_000);
+        let pools = storage_with(vec![pool]).await;
+        // Keeps the price impact warning out of the way
+        let config = ApiConfig { price_impact_warning_bps: Some(10000), ..ApiConfig::default() };
+        let api = routes(pools, empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), config);
+        
+        let warning = |input_amount: &'static str| {
+            let api = api.clone();
+            async move {
+                let response = warp::test::request()
+                    .method("POST")
+                    .path("/quote")
+                    .json(&serde_json::json!({
+                        "input_token": "ETH",
+                        "output_token": "USDC",
+                        "input_amount": input_amount,
+                        "slippage_tolerance": 0.5
+                    }))
+                    .reply(&api)
+                    .await;
+                assert_eq!(response.status(), 200);
+                let body: SwapResponse = serde_json::from_slice(response.body()).unwrap();
+                body.warning
+            }
+        };
+        
+        // Exactly 10% of the input reserve is still fine; one unit more is not
+        assert_eq!(warning("100000").await, None);
+        assert_eq!(warning("100001").await.as_deref(), Some("Large swap: 10.0% of the input reserve"));
+        
+        let config = ApiConfig { swap_size_warning_bps: Some(2000), ..ApiConfig::default() };
+        let pool = create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1_000_000);
+        let reserves = pool.reserves["ETH"].clone();
+        assert_eq!(config.swap_size_warning(&reserves, &num_bigint::BigUint::from(150_000u64)), None);
+        assert_eq!(
+            config.swap_warning(&pool, "ETH", &num_bigint::BigUint::from(250_000u64), 25.0).as_deref(),
+            Some("High price impact: 25.0%; Large swap: 25.0% of the input reserve")
+        );
+    }
+    
+    #[tokio::test]
+    async fn test_swap_returns_unique_receipt_ids() {
+        let pool = create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1_000_000);
+        let pools = storage_with(vec![pool]).await;
+        let api = routes(pools.clone(), empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), ApiConfig::default());
+        
+        let swap = serde_json::json!({
+            "input_token": "ETH",
+            "output_token": "USDC",
+            "input_amount": "1000",
+            "slippage_tolerance": 0.5
+        });
+        let first = warp::test::request().method("POST").path("/swap").json(&swap).reply(&api).await;
+        let second = warp::test::request().method("POST").path("/swap").json(&swap).reply(&api).await;
+        assert_eq!(first.status(), 200);
+        assert_eq!(second.status(), 200);
+        
+        let first: SwapResponse = serde_json::from_slice(first.body()).unwrap();
+        let second: SwapResponse = serde_json::from_slice(second.body()).unwrap();
+        assert!(first.receipt_id.is_some());
+        assert_ne!(first.receipt_id, second.receipt_id);
+        
+        // The swaps were applied to the pool
+        let pools_read = pools.read().await;
+        assert_eq!(pools_read["ETH-USDC"].reserves["ETH"], num_bigint::BigUint::from(1_002_000u64));
+    }
+    
+    #[tokio::test]
+    async fn test_swap_of_expired_quote_is_rejected() {
+        let pool = create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1_000_000);
+        let pools = storage_with(vec![pool]).await;
+        let config = ApiConfig { quote_ttl_secs: Some(60), ..ApiConfig::default() };
+        let api = routes(pools.clone(), empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), config);
+        
+        let request = |quote_timestamp: u64| serde_json::json!({
+            "input_token": "ETH",
+            "output_token": "USDC",
+            "input_amount": "1000",
+            "slippage_tolerance": 0.5,
+            "quote_timestamp": quote_timestamp
+        });
+        
+        let response = warp::test::request().method("POST").path("/quote").json(&request(0)).reply(&api).await;
+        let quote: SwapResponse = serde_json::from_slice(response.body()).unwrap();
+        let quoted_at = quote.quoted_at.unwrap();
+        assert_eq!(quote.valid_until, Some(quoted_at + 60));
+        
+        let response = warp::test::request().method("POST").path("/swap").json(&request(quoted_at - 61)).reply(&api).await;
+        assert_eq!(response.status(), 422);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["code"], ErrorCode::QuoteExpired.as_u16());
+        assert_eq!(pools.read().await["ETH-USDC"].version, 0);
+        
+        let response = warp::test::request().method("POST").path("/swap").json(&request(quoted_at)).reply(&api).await;
+        assert_eq!(response.status(), 200);
+    }
+    
+    #[tokio::test]
+    async fn test_swap_below_quoted_output_is_rejected() {
+        let pool = create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1_000_000);
+        let pools = storage_with(vec![pool]).await;
+        let api = routes(pools.clone(), empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), ApiConfig::default());
+        let trade = |expected_output: Option<&str>| serde_json::json!({
+            "input_token": "ETH",
+            "output_token": "USDC",
+            "input_amount": "100000",
+            "slippage_tolerance": 0.01,
+            "expected_output": expected_output
+        });
+        let quote = |api| async move {
+            let response = warp::test::request().method("POST").path("/quote").json(&trade(None)).reply(api).await;
+            serde_json::from_slice::<SwapResponse>(response.body()).unwrap().expected_output
+        };
+        
+        // Another large trade lands between the quote and the swap
+        let quoted = quote(&api).await;
+        let response = warp::test::request().method("POST").path("/swap").json(&trade(None)).reply(&api).await;
+        assert_eq!(response.status(), 200);
+        let before = pools.read().await["ETH-USDC"].clone();
+        
+        let response = warp::test::request().method("POST").path("/swap").json(&trade(Some(&quoted))).reply(&api).await;
+        assert_eq!(response.status(), 422);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["code"], ErrorCode::SlippageExceeded.as_u16());
+        let pools_read = pools.read().await;
+        assert_eq!(pools_read["ETH-USDC"].reserves, before.reserves);
+        assert_eq!(pools_read["ETH-USDC"].version, before.version);
+        drop(pools_read);
+        
+        // A fresh quote executes
+        let quoted = quote(&api).await;
+        let response = warp::test::request().method("POST").path("/swap").json(&trade(Some(&quoted))).reply(&api).await;
+        assert_eq!(response.status(), 200);
+        let swapped: SwapResponse = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(swapped.output_amount, quoted);
+    }
+    
+    // Fee that rises on every use, as a dynamic fee updated between a
+    // swap's quote and its execution would
+    #[derive(Debug, Default)]
+    struct RisingFee(std::sync::atomic::AtomicU64);
+    
+    impl dex_protocol_core::fees::FeeStrategy for RisingFee {
+        fn fee_bps(&self, _direction: dex_protocol_core::fees::Direction) -> u64 {
+            30 + self.0.fetch_add(100, std::sync::atomic::Ordering::SeqCst)
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_swap_below_own_quote_is_rejected() {
+        let mut pool = create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1_000_000);
+        pool.fee_strategy = Some(Arc::new(RisingFee::default()));
+        let pools = storage_with(vec![pool]).await;
+        let before = pools.read().await["ETH-USDC"].clone();
+        let api = routes(pools.clone(), empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), ApiConfig::default());
+        
+        // No expected_output: the swap is held to the quote taken as it runs
+        let response = warp::test::request()
+            .method("POST")
+            .path("/swap")
+            .json(&serde_json::json!({
+                "input_token": "ETH",
+                "output_token": "USDC",
+                "input_amount": "100000",
+                "slippage_tolerance": 0.01
+            }))
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), 422);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["code"], ErrorCode::SlippageExceeded.as_u16());
+        let pools_read = pools.read().await;
+        assert_eq!(pools_read["ETH-USDC"].reserves, before.reserves);
+        assert_eq!(pools_read["ETH-USDC"].version, before.version);
+    }
+    
+    #[tokio::test]
+    async fn test_swap_rejected_above_max_price_impact() {
+        let pool = create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1_000_000);
+        let pools = storage_with(vec![pool]).await;
+        let config = ApiConfig { max_price_impact_bps: Some(100), ..ApiConfig::default() };
+        let api = routes(pools.clone(), empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), config);
+        
+        let swap = |amount: &str| serde_json::json!({
+            "input_token": "ETH",
+            "output_token": "USDC",
+            "input_amount": amount,
+            "slippage_tolerance": 0.5
+        });
+        
+        // 0.5% of the reserve is under the 1% cap
+        let response = warp::test::request().method("POST").path("/swap").json(&swap("5000")).reply(&api).await;
+        assert_eq!(response.status(), 200);
+        
+        // 5% is over it and leaves the pool untouched
+        let response = warp::test::request().method("POST").path("/swap").json(&swap("50250")).reply(&api).await;
+        assert_eq!(response.status(), 422);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["code"], ErrorCode::PriceImpactTooHigh.as_u16());
+        assert_eq!(body["price_impact_bps"], "500");
+        assert_eq!(body["max_price_impact_bps"], 100);
+        assert_eq!(pools.read().await["ETH-USDC"].reserves["ETH"], num_bigint::BigUint::from(1_005_000u64));
+    }
+    
+    #[tokio::test]
+    async fn test_pair_quote_returns_both_directions() {
+        let pool = create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1_000_000);
+        let api = routes(storage_with(vec![pool]).await, empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), ApiConfig::default());
+        
+        let response = warp::test::request()
+            .method("GET")
+            .path("/quote/pair?token_a=ETH&token_b=USDC&amount=1000")
+            .reply(&api)
+            .await;
+        
+        assert_eq!(response.status(), 200);
+        let quotes: PairQuoteResponse = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(quotes.a_to_b.route[0].token_in.symbol, "ETH");
+        assert_eq!(quotes.b_to_a.route[0].token_in.symbol, "USDC");
+        
+        // Going there and back loses at least the fee on both legs
+        let a_to_b: f64 = quotes.a_to_b.output_amount.parse().unwrap();
+        let b_to_a: f64 = quotes.b_to_a.output_amount.parse().unwrap();
+        let round_trip = (a_to_b / 1000.0) * (b_to_a / 1000.0);
+        assert!(round_trip < 0.997 * 0.997);
+        assert!(round_trip > 0.98);
+    }
+    
+    #[tokio::test]
+    async fn test_batch_liquidity_reports_partial_success() {
+        let pools = storage_with(vec![
+            create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1000),
+            create_pool("ETH-DAI", vec![token("ETH", "ETH", 18), token("DAI", "DAI", 18)], 1000),
+        ])
+        .await;
+        let api = routes(pools.clone(), empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), ApiConfig::default());
+        
+        let response = warp::test::request()
+            .method("POST")
+            .path("/liquidity/batch")
+            .json(&serde_json::json!([
+                { "pool_id": "ETH-USDC", "token_amounts": { "ETH": "1000", "USDC": "1000" } },
+                { "pool_id": "MISSING", "token_amounts": { "ETH": "1000" } },
+                { "pool_id": "ETH-DAI", "token_amounts": { "ETH": "1000", "DAI": "lots" } }
+            ]))
+            .reply(&api)
+            .await;
+        
+        assert_eq!(response.status(), 200);
+        let results: Vec<BatchLiquidityResult> = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(results.len(), 3);
+        assert!(results[0].success);
+        assert_eq!(results[0].lp_tokens.as_deref(), Some("1000"));
+        assert!(!results[1].success);
+        assert!(results[1].error.as_deref().unwrap().contains("MISSING"));
+        assert!(!results[2].success);
+        
+        // Only the valid deposit reached its pool
+        let pools_read = pools.read().await;
+        assert_eq!(pools_read["ETH-USDC"].reserves["ETH"], num_bigint::BigUint::from(2000u64));
+        assert_eq!(pools_read["ETH-DAI"].reserves["ETH"], num_bigint::BigUint::from(1000u64));
+    }
+    
+    #[tokio::test]
+    async fn test_batch_prices_direct_routed_and_missing() {
+        let tokens = |a: (&str, u64), b: (&str, u64)| {
+            vec![(token(a.0, a.0, 18), a.1), (token(b.0, b.0, 18), b.1)]
+        };
+        let pool = |id: &str, reserves: Vec<(Token, u64)>| {
+            let amounts = reserves.iter().map(|(t, r)| (t.address.clone(), num_bigint::BigUint::from(*r))).collect();
+            Pool::new(id.to_string(), reserves.into_iter().map(|(t, _)| t).collect(), amounts, 30, PoolType::ConstantProduct)
+        };
+        let api = routes(
+            storage_with(vec![
+                pool("ETH-USDC", tokens(("ETH", 1_000), ("USDC", 2_000_000))),
+                pool("USDC-DAI", tokens(("USDC", 1_000_000), ("DAI", 2_000_000))),
+                pool("WBTC-RENBTC", tokens(("WBTC", 10), ("RENBTC", 10))),
+            ])
+            .await,
+            empty_history(),
+            empty_quote_cache(),
+            events::channel(),
+            MetricsCollector::new(),
+            ApiConfig::default(),
+        );
+        
+        let response = warp::test::request()
+            .method("POST")
+            .path("/prices")
+            .json(&serde_json::json!([
+                { "token_a": "ETH", "token_b": "USDC" },
+                { "token_a": "ETH", "token_b": "DAI" },
+                { "token_a": "ETH", "token_b": "WBTC" }
+            ]))
+            .reply(&api)
+            .await;
+        
+        assert_eq!(response.status(), 200);
+        let results: Vec<PairPriceResult> = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(results[0].price.as_deref(), Some("2000"));
+        assert_eq!(results[0].pools, vec!["ETH-USDC"]);
+        // 2000 USDC per ETH, then 2 DAI per USDC
+        assert_eq!(results[1].price.as_deref(), Some("4000"));
+        assert_eq!(results[1].pools, vec!["ETH-USDC", "USDC-DAI"]);
+        assert!(results[2].price.is_none());
+        assert_eq!(results[2].code, Some(ErrorCode::PoolNotFound.as_u16()));
+    }
+    
+    #[tokio::test]
+    async fn test_stale_liquidity_update_is_rejected() {
+        let pool = create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1000);
+        let api = routes(storage_with(vec![pool]).await, empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), ApiConfig::default());
+        
+        let response = warp::test::request().method("GET").path("/pools").reply(&api).await;
+        let infos: Vec<PoolInfo> = serde_json::from_slice(response.body()).unwrap();
+        let seen = infos[0].version.to_string();
+        
+        // Two clients deposit against the version they both read
+        let deposit = || {
+            warp::test::request()
+                .method("POST")
+                .path("/liquidity")
+                .header("if-match", format!("\"{}\"", seen))
+                .json(&serde_json::json!({ "pool_id": "ETH-USDC", "token_amounts": { "ETH": "100", "USDC": "100" } }))
+        };
+        let first = deposit().reply(&api).await;
+        assert_eq!(first.status(), 200);
+        let body: serde_json::Value = serde_json::from_slice(first.body()).unwrap();
+        assert_eq!(body["pool_version"], infos[0].version + 1);
+        
+        let second = deposit().reply(&api).await;
+        assert_eq!(second.status(), 409);
+        let body: serde_json::Value = serde_json::from_slice(second.body()).unwrap();
+        assert_eq!(body["code"], ErrorCode::VersionConflict.as_u16());
+        assert_eq!(body["version"], infos[0].version + 1);
+    }
+    
+    #[tokio::test]
+    async fn test_candles_from_reserve_snapshots() {
+        let pool = create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1000);
+        let pools = storage_with(vec![pool]).await;
+        
+        let snapshot = |timestamp: u64, usdc: u64| {
+            let mut reserves = HashMap::new();
+            reserves.insert("ETH".to_string(), num_bigint::BigUint::from(100u64));
+            reserves.insert("USDC".to_string(), num_bigint::BigUint::from(usdc));
+            ReserveSnapshot { timestamp, reserves }
+        };
+        let history = empty_history();
+        history.write().await.insert("ETH-USDC".to_string(), vec![
+            snapshot(0, 200),
+            snapshot(30, 400),
+            snapshot(45, 100),
+            snapshot(150, 300),
+        ]);
+        let api = routes(pools, history, empty_quote_cache(), events::channel(), MetricsCollector::new(), ApiConfig::default());
+        
+        let response = warp::test::request()
+            .method("GET")
+            .path("/pools/ETH-USDC/candles?interval=60&count=3")
+            .reply(&api)
+            .await;
+        
+        assert_eq!(response.status(), 200);
+        let candles: Vec<serde_json::Value> = serde_json::from_slice(response.body()).unwrap();
+        let ohlc: Vec<[&str; 4]> = candles.iter().map(|c| [
+            c["open"].as_str().unwrap(),
+            c["high"].as_str().unwrap(),
+            c["low"].as_str().unwrap(),
+            c["close"].as_str().unwrap(),
+        ]).collect();
+        assert_eq!(ohlc, vec![
+            ["2", "4", "1", "1"],
+            ["1", "1", "1", "1"], // no snapshots, previous close carried forward
+            ["3", "3", "3", "3"],
+        ]);
+        assert_eq!(candles[1]["open_time"], 60);
+    }
+    
+    #[tokio::test]
+    async fn test_quote_matches_token_addresses_case_insensitively() {
+        let pool = create_pool("ETH-USDC", vec![token("0xAbC1", "ETH", 18), token("0xDeF2", "USDC", 6)], 1_000_000);
+        let api = routes(storage_with(vec![pool]).await, empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), ApiConfig::default());
+        
+        let response = warp::test::request()
+            .method("POST")
+            .path("/quote")
+            .json(&serde_json::json!({
+                "input_token": "0xabc1",
+                "output_token": "0xDEF2",
+                "input_amount": "1000",
+                "slippage_tolerance": 0.5
+            }))
+            .reply(&api)
+            .await;
+        
+        assert_eq!(response.status(), 200);
+        let quote: SwapResponse = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(quote.route[0].token_in.address, "0xAbC1");
+        assert_eq!(quote.route[0].token_out.address, "0xDeF2");
+    }
+    
+    #[tokio::test]
+    async fn test_quote_fee_is_reported_in_the_charged_token() {
+        let pool = create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1_000_000_000_000);
+        let api = routes(storage_with(vec![pool]).await, empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), ApiConfig::default());
+        
+        let response = warp::test::request()
+            .method("POST")
+            .path("/quote")
+            .json(&serde_json::json!({
+                "input_token": "USDC",
+                "output_token": "ETH",
+                "input_amount": "1234567",
+                "slippage_tolerance": 0.5
+            }))
+            .reply(&api)
+            .await;
+        
+        assert_eq!(response.status(), 200);
+        let quote: SwapResponse = serde_json::from_slice(response.body()).unwrap();
+        // 1.234567 USDC at 0.3% keeps 1234567 - floor(1234567 * 0.997) raw units
+        assert_eq!(quote.fee, "3704");
+        assert_eq!(quote.fee_formatted, "0.003704");
+        assert_eq!(quote.fee_token.symbol, "USDC");
+        assert_eq!(quote.fee_token.decimals, 6);
+        
+        // Stable pools take their fee from the output, in quotes and swaps alike
+        let tokens = vec![token("USDC", "USDC", 6), token("DAI", "DAI", 18)];
+        let reserves = [("USDC", 1_000_000u64 * 10u64.pow(6)), ("DAI", 1_000_000u64 * 10u64.pow(6))]
+            .iter()
+            .map(|(t, r)| (t.to_string(), num_bigint::BigUint::from(*r)))
+            .collect();
+        let stable = Pool::new("USDC-DAI".to_string(), tokens, reserves, 4, PoolType::StableSwap);
+        let (_, expected_fee) = stable.quote_with_fee("USDC", "DAI", &num_bigint::BigUint::from(1_000_000u64)).unwrap();
+        let api = routes(storage_with(vec![stable]).await, empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), ApiConfig::default());
+        let trade = serde_json::json!({
+            "input_token": "USDC",
+            "output_token": "DAI",
+            "input_amount": "1000000",
+            "slippage_tolerance": 0.5
+        });
+        for path in ["/quote", "/swap"] {
+            let response = warp::test::request().method("POST").path(path).json(&trade).reply(&api).await;
+            assert_eq!(response.status(), 200, "{}", path);
+            let quote: SwapResponse = serde_json::from_slice(response.body()).unwrap();
+            assert_eq!(quote.fee, expected_fee.amount.to_string(), "{}", path);
+            assert_eq!(quote.fee_token.symbol, "DAI", "{}", path);
+            assert_eq!(quote.fee_token.decimals, 18, "{}", path);
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_admin_seeds_reserves_for_later_quotes() {
+        let pool = create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1000);
+        let pools = storage_with(vec![pool]).await;
+        let config = ApiConfig { admin_token: Some("secret".to_string()), ..ApiConfig::default() };
+        let api = routes(pools.clone(), empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), config);
+        let seed = serde_json::json!({ "ETH": "1000000", "USDC": "4000000" });
+        
+        let response = warp::test::request()
+            .method("PUT")
+            .path("/pools/ETH-USDC/reserves")
+            .header("authorization", "Bearer wrong")
+            .json(&seed)
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), 401);
+        
+        // A zero reserve would leave the pool unable to quote
+        let response = warp::test::request()
+            .method("PUT")
+            .path("/pools/ETH-USDC/reserves")
+            .header("authorization", "Bearer secret")
+            .json(&serde_json::json!({ "ETH": "1000000", "USDC": "0" }))
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), 400);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["code"], ErrorCode::InvalidReserves.as_u16());
+        assert_eq!(pools.read().await["ETH-USDC"].version, 0);
+        
+        let response = warp::test::request()
+            .method("PUT")
+            .path("/pools/ETH-USDC/reserves")
+            .header("authorization", "Bearer secret")
+            .json(&seed)
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), 200);
+        let info: PoolInfo = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(info.reserves["USDC"], "4000000");
+        assert_eq!(info.total_supply, "2000000");
+        
+        let response = warp::test::request()
+            .method("POST")
+            .path("/quote")
+            .json(&serde_json::json!({
+                "input_token": "ETH",
+                "output_token": "USDC",
+                "input_amount": "1000",
+                "slippage_tolerance": 0.5
+            }))
+            .reply(&api)
+            .await;
+        let quote: SwapResponse = serde_json::from_slice(response.body()).unwrap();
+        let expected = pools.read().await["ETH-USDC"]
+            .calculate_swap_output("ETH", "USDC", &num_bigint::BigUint::from(1000u64))
+            .unwrap();
+        assert_eq!(quote.output_amount, expected.to_string());
+        assert!(expected > num_bigint::BigUint::from(3900u64));
+    }
+    
+    #[tokio::test]
+    async fn test_admin_creates_pool_at_runtime() {
+        let pool = create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1000);
+        let pools = storage_with(vec![pool]).await;
+        let config = ApiConfig { admin_token: Some("secret".to_string()), ..ApiConfig::default() };
+        let api = routes(pools.clone(), empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), config);
+        let create = |id: &str| {
+            warp::test::request()
+                .method("POST")
+                .path("/pools")
+                .header("authorization", "Bearer secret")
+                .json(&serde_json::json!({
+                    "id": id,
+                    "tokens": [token("WBTC", "WBTC", 8), token("DAI", "DAI", 18)],
+                    "initial_reserves": { "WBTC": "100000000", "DAI": "60000000000000000000000" },
+                    "fee_rate": 30,
+                    "pool_type": "constant_product"
+                }))
+        };
+        
+        let response = create("WBTC-DAI").reply(&api).await;
+        assert_eq!(response.status(), 200);
+        let info: PoolInfo = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(info.reserves["DAI"], "60000000000000000000000");
+        assert_eq!(pools.read().await["WBTC-DAI"].fee_rate, 30);
+        
+        // Quotable straight away
+        let response = warp::test::request()
+            .method("POST")
+            .path("/quote")
+            .json(&serde_json::json!({
+                "input_token": "WBTC",
+                "output_token": "DAI",
+                "input_amount": "1000",
+                "slippage_tolerance": 0.5
+            }))
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), 200);
+        
+        // An existing id is refused and the pool left as it was
+        let response = create("ETH-USDC").reply(&api).await;
+        assert_eq!(response.status(), 409);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["code"], ErrorCode::PoolAlreadyExists.as_u16());
+        assert_eq!(pools.read().await["ETH-USDC"].tokens[0].address, "ETH");
+        
+        // Validated like a startup config
+        let response = warp::test::request()
+            .method("POST")
+            .path("/pools")
+            .header("authorization", "Bearer secret")
+            .json(&serde_json::json!({
+                "id": "ETH-DAI",
+                "tokens": [token("ETH", "ETH", 18), token("DAI", "DAI", 18)],
+                "initial_reserves": { "ETH": "1000", "DAI": "0" },
+                "pool_type": "constant_product"
+            }))
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), 400);
+        assert!(!pools.read().await.contains_key("ETH-DAI"));
+    }
+    
+    #[tokio::test]
+    async fn test_pool_created_by_symbol_gets_registry_decimals() {
+        let pools = storage_with(vec![]).await;
+        let config = ApiConfig { admin_token: Some("secret".to_string()), ..ApiConfig::default() };
+        let api = routes(pools.clone(), empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), config);
+        let create = |tokens: serde_json::Value, reserves: serde_json::Value| {
+            warp::test::request()
+                .method("POST")
+                .path("/pools")
+                .header("authorization", "Bearer secret")
+                .json(&serde_json::json!({
+                    "id": "ETH-USDC",
+                    "tokens": tokens,
+                    "initial_reserves": reserves,
+                    "pool_type": "constant_product"
+                }))
+        };
+        
+        // Unknown symbols are refused rather than given a guessed default
+        let response = create(
+            serde_json::json!([{ "symbol": "ETH" }, { "symbol": "FOO" }]),
+            serde_json::json!({ "ETH": "1000", "FOO": "1000" }),
+        ).reply(&api).await;
+        assert_eq!(response.status(), 400);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert!(body["error"].as_str().unwrap().contains("FOO"));
+        
+        let response = create(
+            serde_json::json!([{ "symbol": "ETH" }, { "symbol": "USDC" }]),
+            serde_json::json!({ "ETH": "1000000000000000000", "USDC": "2000000000" }),
+        ).reply(&api).await;
+        assert_eq!(response.status(), 200);
+        let pools_read = pools.read().await;
+        let pool = &pools_read["ETH-USDC"];
+        assert_eq!(pool.find_token("ETH").unwrap().decimals, 18);
+        assert_eq!(pool.find_token("USDC").unwrap().decimals, 6);
+    }
+    
+    #[tokio::test]
+    async fn test_diff_against_baseline_lists_differing_pools() {
+        let eth_usdc = create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1000);
+        let wbtc_dai = create_pool("WBTC-DAI", vec![token("WBTC", "WBTC", 8), token("DAI", "DAI", 18)], 1000);
+        let pools = storage_with(vec![eth_usdc, wbtc_dai]).await;
+        let config = ApiConfig { admin_token: Some("secret".to_string()), ..ApiConfig::default() };
+        let api = routes(pools.clone(), empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), config);
+        
+        // The other deployment's /pools output, with one reserve and one fee apart
+        let response = warp::test::request().method("GET").path("/pools").reply(&api).await;
+        let mut baseline: Vec<serde_json::Value> = serde_json::from_slice(response.body()).unwrap();
+        for pool in baseline.iter_mut().filter(|pool| pool["id"] == "ETH-USDC") {
+            pool["reserves"]["USDC"] = "999".into();
+            pool["fee_rate"] = 5.into();
+        }
+        baseline.push(serde_json::json!({
+            "id": "OLD", "reserves": { "ETH": "1" }, "total_supply": "1", "fee_rate": 30
+        }));
+        
+        let diff = |authorization: &'static str| warp::test::request()
+            .method("POST")
+            .path("/admin/diff")
+            .header("authorization", authorization)
+            .json(&baseline)
+            .reply(&api);
+        assert_eq!(diff("Bearer wrong").await.status(), 401);
+        let response = diff("Bearer secret").await;
+        assert_eq!(response.status(), 200);
+        let diffs: Vec<PoolDiff> = serde_json::from_slice(response.body()).unwrap();
+        
+        assert_eq!(diffs.len(), 2);
+        assert_eq!(diffs[0].pool_id, "ETH-USDC");
+        assert!(!diffs[0].reserves_match);
+        assert_eq!(diffs[0].reserves.len(), 1);
+        assert_eq!(diffs[0].reserves["USDC"], Change { baseline: Some("999".to_string()), current: Some("1000".to_string()) });
+        assert_eq!(diffs[0].fee_rate, Some(Change { baseline: Some(5), current: Some(30) }));
+        assert!(diffs[0].total_supply.is_none());
+        
+        // Known only to the baseline
+        assert_eq!(diffs[1].pool_id, "OLD");
+        assert_eq!(diffs[1].total_supply, Some(Change { baseline: Some("1".to_string()), current: None }));
+    }
+    
+    #[tokio::test]
+    async fn test_removal_burns_from_the_owners_position() {
+        let mut pool = create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1000);
+        pool.lp_positions.insert("alice".to_string(), num_bigint::BigUint::from(600u64));
+        let pools = storage_with(vec![pool]).await;
+        let api = routes(pools.clone(), empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), ApiConfig::default());
+        let remove = |lp_tokens: &str, owner: Option<&str>| {
+            warp::test::request()
+                .method("POST")
+                .path("/liquidity/remove")
+                .json(&serde_json::json!({ "pool_id": "ETH-USDC", "lp_tokens": lp_tokens, "owner": owner }))
+        };
+        
+        // Alice's 600 of the 1000 are hers alone; bob holds nothing
+        for (lp_tokens, owner) in [("401", None), ("601", Some("alice")), ("1", Some("bob"))] {
+            let response = remove(lp_tokens, owner).reply(&api).await;
+            assert_eq!(response.status(), 422, "{} by {:?}", lp_tokens, owner);
+            let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+            assert_eq!(body["code"], ErrorCode::InsufficientLpTokens.as_u16());
+        }
+        
+        assert_eq!(remove("250", Some("alice")).reply(&api).await.status(), 200);
+        assert_eq!(remove("400", None).reply(&api).await.status(), 200);
+        let pools_read = pools.read().await;
+        assert_eq!(pools_read["ETH-USDC"].lp_positions["alice"], num_bigint::BigUint::from(350u64));
+        assert_eq!(pools_read["ETH-USDC"].total_supply, num_bigint::BigUint::from(350u64));
+    }
+    
+    #[tokio::test]
+    async fn test_emergency_removal_is_admin_only_and_ignores_floors() {
+        let mut pool = create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1000);
+        pool.min_reserves = Some(HashMap::from([("ETH".to_string(), num_bigint::BigUint::from(900u64))]));
+        pool.lp_positions.insert("alice".to_string(), pool.total_supply.clone());
+        let config = ApiConfig { admin_token: Some("secret".to_string()), ..ApiConfig::default() };
+        let pools = storage_with(vec![pool]).await;
+        let api = routes(pools.clone(), empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), config);
+        let body = serde_json::json!({ "pool_id": "ETH-USDC", "lp_tokens": "1000", "owner": "alice" });
+        let emergency = |authorization: &str| {
+            warp::test::request()
+                .method("POST")
+                .path("/liquidity/emergency-remove")
+                .header("authorization", authorization)
+                .json(&body)
+        };
+        
+        // A normal exit of the whole supply would breach the ETH floor
+        let response = warp::test::request()
+            .method("POST")
+            .path("/liquidity/remove")
+            .json(&serde_json::json!({ "pool_id": "ETH-USDC", "lp_tokens": "1000", "owner": "alice" }))
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), 422);
+        let error: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(error["code"], ErrorCode::ReserveFloorBreached.as_u16());
+        assert_eq!(emergency("Bearer wrong").reply(&api).await.status(), 401);
+        let response = emergency("Bearer secret").reply(&api).await;
+        assert_eq!(response.status(), 403);
+        let error: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(error["code"], ErrorCode::EmergencyModeDisabled.as_u16());
+        
+        set_emergency_mode(true);
+        let response = emergency("Bearer secret").reply(&api).await;
+        set_emergency_mode(false);
+        assert_eq!(response.status(), 200);
+        let removed: RemoveLiquidityResponse = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(removed.amounts["ETH"], "1000");
+        let pools_read = pools.read().await;
+        assert_eq!(pools_read["ETH-USDC"].total_supply, num_bigint::BigUint::from(0u8));
+        assert_eq!(pools_read["ETH-USDC"].lp_positions["alice"], num_bigint::BigUint::from(0u8));
+    }
+    
+    #[tokio::test]
+    async fn test_error_paths_return_documented_codes() {
+        let empty = create_pool("DRY", vec![token("WBTC", "WBTC", 8), token("DAI", "DAI", 18)], 0);
+        let pool = create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1_000_000);
+        let mut floored = create_pool("FLOORED", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1_000_000);
+        floored.min_reserves = Some(HashMap::from([("ETH".to_string(), num_bigint::BigUint::from(600_000u64))]));
+        let config = ApiConfig { admin_token: Some("secret".to_string()), ..ApiConfig::default() };
+        let api = routes(storage_with(vec![pool, empty, floored]).await, empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), config);
+        let swap = |input: &str, output: &str, amount: &str| serde_json::json!({
+            "input_token": input,
+            "output_token": output,
+            "input_amount": amount,
+            "slippage_tolerance": 0.5
+        });
+        
+        let liquidity = |pool_id: &str, amount: &str| serde_json::json!({
+            "pool_id": pool_id,
+            "token_amounts": { "WBTC": amount, "DAI": amount }
+        });
+        
+        let cases = vec![
+            ("GET", "/pools/NOPE/apy", None, 404, ErrorCode::PoolNotFound),
+            ("POST", "/quote", Some(swap("ETH", "WBTC", "1000")), 404, ErrorCode::PoolNotFound),
+            ("POST", "/quote", Some(swap("WBTC", "DAI", "1000")), 422, ErrorCode::InsufficientLiquidity),
+            ("POST", "/quote", Some(swap("ETH", "USDC", "-1")), 400, ErrorCode::InvalidAmount),
+            ("POST", "/swap", Some(swap("ETH", "USDC", "lots")), 400, ErrorCode::InvalidAmount),
+            ("POST", "/swap", Some(swap("ETH", "WBTC", "1000")), 404, ErrorCode::PoolNotFound),
+            ("POST", "/swap", Some(swap("WBTC", "DAI", "1000")), 422, ErrorCode::InsufficientLiquidity),
+            ("POST", "/liquidity", Some(liquidity("DRY", "1.5")), 400, ErrorCode::InvalidAmount),
+            ("POST", "/liquidity", Some(liquidity("NOPE", "1000")), 404, ErrorCode::PoolNotFound),
+            ("POST", "/liquidity", Some(liquidity("ETH-USDC", "1000")), 404, ErrorCode::TokenNotFound),
+            ("POST", "/liquidity/remove", Some(serde_json::json!({ "pool_id": "NOPE", "lp_tokens": "1" })), 404, ErrorCode::PoolNotFound),
+            ("POST", "/liquidity/remove", Some(serde_json::json!({ "pool_id": "DRY", "lp_tokens": "1" })), 422, ErrorCode::InsufficientLpTokens),
+            ("POST", "/liquidity/remove", Some(serde_json::json!({ "pool_id": "FLOORED", "lp_tokens": "500000" })), 422, ErrorCode::ReserveFloorBreached),
+            ("GET", "/pools/ETH-USDC/price?base=ETH&quote=DAI", None, 404, ErrorCode::TokenNotFound),
+            ("PUT", "/pools/ETH-USDC/reserves", Some(serde_json::json!({ "ETH": "5" })), 401, ErrorCode::Unauthorized),
+            ("POST", "/liquidity", Some(serde_json::json!({ "pool_id": "ETH-USDC" })), 400, ErrorCode::InvalidRequest),
+            ("GET", "/nowhere", None, 404, ErrorCode::NotFound),
+        ];
+        for (method, path, body, status, code) in cases {
+            let mut request = warp::test::request().method(method).path(path);
+            if let Some(body) = body {
+                request = request.json(&body);
+            }
+            let response = request.reply(&api).await;
+            
+            assert_eq!(response.status(), status, "{} {}", method, path);
+            let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+            assert_eq!(body["code"], code.as_u16(), "{} {}", method, path);
+            assert!(body["error"].is_string());
+        }
+        
+        let response = warp::test::request()
+            .method("PUT")
+            .path("/pools/ETH-USDC/reserves")
+            .header("authorization", "Bearer secret")
+            .json(&serde_json::json!({ "ETH": "5" }))
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), 400);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["code"], ErrorCode::InvalidReserves.as_u16());
+    }
+    
+    #[test]
+    fn test_route_hops_describe_multi_hop_route() {
+        let eth_usdc = create_pool("ETH-USDC", vec![token("0xe7", "ETH", 18), token("0xc0", "USDC", 6)], 1_000_000);
+        let usdc_dai = create_pool("USDC-DAI", vec![token("0xc0", "USDC", 6), token("0xda", "DAI", 18)], 1_000_000);
+        let route = Route::quote(&[&eth_usdc, &usdc_dai], &["0xe7", "0xc0", "0xda"], &num_bigint::BigUint::from(1000u64)).unwrap();
+        let pools: HashMap<String, Pool> = [eth_usdc, usdc_dai].into_iter().map(|p| (p.id.clone(), p)).collect();
+        
+        let hops = route_hops(&pools, &route).unwrap();
+        
+        let described: Vec<(&str, &str, &str)> = hops.iter()
+            .map(|h| (h.pool_id.as_str(), h.token_in.symbol.as_str(), h.token_out.symbol.as_str()))
+            .collect();
+        assert_eq!(described, vec![("ETH-USDC", "ETH", "USDC"), ("USDC-DAI", "USDC", "DAI")]);
+        assert_eq!(hops[1].token_in.address, "0xc0");
+        assert_eq!(hops[1].token_out.decimals, 18);
+    }
+    
+    #[tokio::test]
+    async fn test_quote_routes_through_two_pools() {
+        // No pool trades ETH for DAI, so the quote has to go through USDC
+        let eth_usdc = create_pool("ETH-USDC", vec![token("0xe7", "ETH", 18), token("0xc0", "USDC", 6)], 1_000_000);
+        let usdc_dai = create_pool("USDC-DAI", vec![token("0xc0", "USDC", 6), token("0xda", "DAI", 18)], 1_000_000);
+        let amount = num_bigint::BigUint::from(1000u64);
+        let expected = Route::quote(&[&eth_usdc, &usdc_dai], &["0xe7", "0xc0", "0xda"], &amount).unwrap();
+        let pools = storage_with(vec![eth_usdc, usdc_dai]).await;
+        
+        let response = warp::test::request()
+            .method("POST")
+            .path("/quote")
+            .json(&serde_json::json!({
+                "input_token": "0xe7",
+                "output_token": "0xda",
+                "input_amount": amount.to_string(),
+                "slippage_tolerance": 0.5
+            }))
+            .reply(&routes(pools, empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), ApiConfig::default()))
+            .await;
+        
+        assert_eq!(response.status(), 200);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        let path: Vec<(&str, &str, &str)> = body["route"].as_array().unwrap().iter()
+            .map(|hop| (hop["pool_id"].as_str().unwrap(), hop["token_in"]["symbol"].as_str().unwrap(), hop["token_out"]["symbol"].as_str().unwrap()))
+            .collect();
+        assert_eq!(path, vec![("ETH-USDC", "ETH", "USDC"), ("USDC-DAI", "USDC", "DAI")]);
+        assert_eq!(body["expected_output"], expected.expected_output.to_string());
+    }
+    
+    #[tokio::test]
+    async fn test_swap_executes_the_quoted_route() {
+        // Two pools trade ETH for USDC; the deeper one gives more
+        let shallow = create_pool("A-SHALLOW", vec![token("0xe7", "ETH", 18), token("0xc0", "USDC", 6)], 10_000);
+        let deep = create_pool("B-DEEP", vec![token("0xe7", "ETH", 18), token("0xc0", "USDC", 6)], 1_000_000);
+        let pools = storage_with(vec![shallow.clone(), deep]).await;
+        let api = routes(pools.clone(), empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), ApiConfig::default());
+        let request = |path: &str| {
+            warp::test::request()
+                .method("POST")
+                .path(path)
+                .json(&serde_json::json!({
+                    "input_token": "0xe7",
+                    "output_token": "0xc0",
+                    "input_amount": "1000",
+                    "slippage_tolerance": 0.5
+                }))
+        };
+        
+        let quote: SwapResponse = serde_json::from_slice(request("/quote").reply(&api).await.body()).unwrap();
+        let response = request("/swap").reply(&api).await;
+        assert_eq!(response.status(), 200);
+        let swap: SwapResponse = serde_json::from_slice(response.body()).unwrap();
+        
+        assert_eq!(quote.route[0].pool_id, "B-DEEP");
+        assert_eq!(swap.route[0].pool_id, "B-DEEP");
+        assert_eq!(swap.output_amount, quote.expected_output);
+        let pools_read = pools.read().await;
+        assert_eq!(pools_read["A-SHALLOW"].reserves, shallow.reserves);
+        assert_eq!(pools_read["B-DEEP"].reserves["0xe7"], num_bigint::BigUint::from(1_001_000u64));
+    }
+    
+    #[tokio::test]
+    async fn test_swap_executes_multi_hop_routes() {
+        // No pool trades ETH for DAI, so the swap has to go through USDC
+        let eth_usdc = create_pool("ETH-USDC", vec![token("0xe7", "ETH", 18), token("0xc0", "USDC", 6)], 1_000_000);
+        let usdc_dai = create_pool("USDC-DAI", vec![token("0xc0", "USDC", 6), token("0xda", "DAI", 18)], 1_000_000);
+        let amount = num_bigint::BigUint::from(1000u64);
+        let expected = Route::quote(&[&eth_usdc, &usdc_dai], &["0xe7", "0xc0", "0xda"], &amount).unwrap();
+        let pools = storage_with(vec![eth_usdc, usdc_dai]).await;
+        let api = routes(pools.clone(), empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), ApiConfig::default());
+        let swap = |min_output: &str| {
+            warp::test::request()
+                .method("POST")
+                .path("/swap")
+                .json(&serde_json::json!({
+                    "input_token": "0xe7",
+                    "output_token": "0xda",
+                    "input_amount": amount.to_string(),
+                    "slippage_tolerance": 0.0,
+                    "expected_output": min_output
+                }))
+        };
+        
+        // Asking for more than the route gives fails on the last hop and
+        // leaves both pools as they were
+        let response = swap(&(&expected.expected_output + 1u8).to_string()).reply(&api).await;
+        assert_eq!(response.status(), 422);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["code"], ErrorCode::SlippageExceeded.as_u16());
+        assert!(pools.read().await.values().all(|pool| pool.version == 0));
+        
+        let response = swap(&expected.expected_output.to_string()).reply(&api).await;
+        assert_eq!(response.status(), 200);
+        let body: SwapResponse = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body.output_amount, expected.expected_output.to_string());
+        let path: Vec<&str> = body.route.iter().map(|hop| hop.pool_id.as_str()).collect();
+        assert_eq!(path, vec!["ETH-USDC", "USDC-DAI"]);
+        
+        let pools_read = pools.read().await;
+        let usdc_out = num_bigint::BigUint::from(1_000_000u64) - &pools_read["ETH-USDC"].reserves["0xc0"];
+        assert_eq!(pools_read["ETH-USDC"].reserves["0xe7"], num_bigint::BigUint::from(1_001_000u64));
+        assert_eq!(pools_read["USDC-DAI"].reserves["0xc0"], num_bigint::BigUint::from(1_000_000u64) + usdc_out);
+        assert_eq!(pools_read["USDC-DAI"].reserves["0xda"], num_bigint::BigUint::from(1_000_000u64) - expected.expected_output);
+    }
+    
+    #[tokio::test]
+    async fn test_initialize_pools_keeps_only_valid_configs() {
+        let valid = pool_config::sample_pool_configs().remove(0);
+        let mut invalid = valid.clone();
+        invalid.id = "ONE-TOKEN".to_string();
+        invalid.tokens.truncate(1);
+        
+        let pools = storage_with(vec![]).await;
+        initialize_pools(&pools, vec![valid, invalid]).await;
+        
+        let pools_read = pools.read().await;
+        assert_eq!(pools_read.len(), 1);
+        assert!(pools_read.contains_key("ETH-USDC"));
+    }
+    
+    #[tokio::test]
+    async fn test_apy_breakdown() {
+        let mut pool = create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1_000_000);
+        // A year's worth of fees: 1% of the ETH side and 3% of the USDC side
+        pool.fees_since -= dex_protocol_core::rewards::SECONDS_PER_YEAR;
+        pool.cumulative_fees.insert("ETH".to_string(), num_bigint::BigUint::from(10_000u64));
+        pool.cumulative_fees.insert("USDC".to_string(), num_bigint::BigUint::from(30_000u64));
+        
+        let config = ApiConfig {
+            reward_programs: parse_reward_programs(r#"{"ETH-USDC": {"token": "USDC", "per_year": "100000"}}"#),
+            ..ApiConfig::default()
+        };
+        let api = routes(storage_with(vec![pool]).await, empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), config);
+        
+        let response = warp::test::request()
+            .method("GET")
+            .path("/pools/ETH-USDC/apy")
+            .reply(&api)
+            .await;
+        
+        assert_eq!(response.status(), 200);
+        let apy: ApyResponse = serde_json::from_slice(response.body()).unwrap();
+        assert!((apy.fee_apy - 2.0).abs() < 0.01);
+        assert_eq!(apy.reward_apy, 5.0);
+        assert!((apy.total_apy - 7.0).abs() < 0.01);
+        
+        let response = warp::test::request().method("GET").path("/pools").reply(&api).await;
+        let infos: Vec<PoolInfo> = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(infos[0].apy, apy.total_apy);
+    }
+    
+    #[tokio::test]
+    async fn test_depth_aggregates_pools_unless_one_is_named() {
+        let mut deep = create_pool("ETH-USDC-1", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1000);
+        deep.reserves.insert("USDC".to_string(), num_bigint::BigUint::from(4000u64));
+        let mut shallow = create_pool("ETH-USDC-2", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 500);
+        shallow.reserves.insert("USDC".to_string(), num_bigint::BigUint::from(2000u64));
+        let api = routes(storage_with(vec![deep, shallow]).await, empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), ApiConfig::default());
+        
+        let depths = |body: &[u8]| -> Vec<String> {
+            let levels: Vec<DepthLevel> = serde_json::from_slice(body).unwrap();
+            levels.into_iter().map(|level| level.depth).collect()
+        };
+        
+        let response = warp::test::request().method("GET").path("/depth?token_a=ETH&token_b=USDC&levels=4,1,0.25").reply(&api).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(depths(response.body()), vec!["0", "1500", "4500"]);
+        
+        let response = warp::test::request().method("GET").path("/depth?token_a=ETH&token_b=USDC&levels=1&pool_id=ETH-USDC-2").reply(&api).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(depths(response.body()), vec!["500"]);
+        
+        let response = warp::test::request().method("GET").path("/depth?token_a=ETH&token_b=USDC&levels=x").reply(&api).await;
+        assert_eq!(response.status(), 400);
+    }
+    
+    #[tokio::test]
+    async fn test_fee_tiers_for_pair() {
+        let mut low = create_pool("ETH-USDC-5", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1000);
+        low.fee_rate = 5;
+        let pools = storage_with(vec![
+            low,
+            create_pool("ETH-USDC-30", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1000),
+            create_pool("ETH-DAI-30", vec![token("ETH", "ETH", 18), token("DAI", "DAI", 18)], 1000),
+        ])
+        .await;
+        let api = routes(pools, empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), ApiConfig::default());
+        
+        let response = warp::test::request().method("GET").path("/pairs/USDC/ETH/fee-tiers").reply(&api).await;
+        assert_eq!(response.status(), 200);
+        let tiers: Vec<u64> = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(tiers, vec![5, 30]);
+    }
+    
+    #[tokio::test]
+    async fn test_tvl_ranking_puts_unpriced_pools_last() {
+        let pools = storage_with(vec![
+            create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 2_000_000),
+            create_pool("USDC-DAI", vec![token("USDC", "USDC", 6), token("DAI", "DAI", 6)], 5_000_000),
+            create_pool("USDC-XYZ", vec![token("USDC", "USDC", 6), token("XYZ", "XYZ", 6)], 9_000_000),
+        ])
+        .await;
+        let config = ApiConfig {
+            token_prices: parse_token_prices(r#"{"eth": "2000", "USDC": "1", "DAI": "1", "XYZ": "oops"}"#),
+            ..ApiConfig::default()
+        };
+        
+        let response = warp::test::request()
+            .method("GET")
+            .path("/pools/tvl")
+            .reply(&routes(pools, empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), config))
+            .await;
+        
+        assert_eq!(response.status(), 200);
+        let ranked: Vec<PoolTvl> = serde_json::from_slice(response.body()).unwrap();
+        let ids: Vec<&str> = ranked.iter().map(|p| p.pool_id.as_str()).collect();
+        assert_eq!(ids, vec!["USDC-DAI", "ETH-USDC", "USDC-XYZ"]);
+        assert_eq!(ranked[0].tvl.as_deref(), Some("10"));
+        assert_eq!(ranked[2].tvl, None);
+    }
+    
+    #[tokio::test]
+    async fn test_warming_populates_quote_cache() {
+        let pool = create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 10u64.pow(16));
+        let pools = storage_with(vec![pool]).await;
+        let quote_cache = empty_quote_cache();
+        let config = ApiConfig { quote_warm_sizes: Some(vec![1, 10]), ..ApiConfig::default() };
+        
+        warm_quote_cache(&pools, &quote_cache, &config).await;
+        
+        // Both directions at both sizes, in each input token's own units
+        let pools_read = pools.read().await;
+        let pool = &pools_read["ETH-USDC"];
+        let cached = quote_cache.read().await;
+        assert_eq!(cached.routes.len(), 4);
+        let one_eth = num_bigint::BigUint::from(10u64).pow(18);
+        let ten_usdc = num_bigint::BigUint::from(10u64 * 10u64.pow(6));
+        let eth_quote = cached.get(&pools_read, &QuoteKey::new("ETH", "USDC", &one_eth)).unwrap();
+        assert_eq!(*eth_quote, Route::quote(&[pool], &["ETH", "USDC"], &one_eth).unwrap());
+        assert!(cached.get(&pools_read, &QuoteKey::new("USDC", "ETH", &ten_usdc)).is_some());
+        let expected_output = eth_quote.expected_output.to_string();
+        drop(cached);
+        drop(pools_read);
+        
+        // A request at a warm size is served the cached route
+        let api = routes(pools, empty_history(), quote_cache, events::channel(), MetricsCollector::new(), config);
+        let response = warp::test::request()
+            .method("POST")
+            .path("/quote")
+            .json(&serde_json::json!({
+                "input_token": "ETH",
+                "output_token": "USDC",
+                "input_amount": one_eth.to_string(),
+                "slippage_tolerance": 0.5
+            }))
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), 200);
+        let quote: SwapResponse = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(quote.expected_output, expected_output);
+    }
+    
+    #[tokio::test]
+    async fn test_get_single_pool() {
+        let pool = create_pool("ETH-USDC", vec![token("ETH", "ETH", 18), token("USDC", "USDC", 6)], 1000);
+        let api = routes(storage_with(vec![pool]).await, empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), ApiConfig::default());
+        let get = |path: &str| warp::test::request().method("GET").path(path).reply(&api);
+        
+        let response = get("/pools/ETH-USDC").await;
+        assert_eq!(response.status(), 200);
+        let info: PoolInfo = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(info.id, "ETH-USDC");
+        assert_eq!(info.reserves["USDC"], "1000");
+        
+        let response = get("/pools/NOPE").await;
+        assert_eq!(response.status(), 404);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["code"], ErrorCode::PoolNotFound.as_u16());
+        
+        // Still the aggregate, not a pool called "tvl"
+        assert_eq!(get("/pools/tvl").await.status(), 200);
+    }
+    
+    #[tokio::test]
+    async fn test_get_pools_filters_by_tag() {
+        let tagged = |id: &str, tags: &[&str]| {
+            create_pool(id, vec![token(&format!("{}-A", id), "A", 18), token(&format!("{}-B", id), "B", 18)], 1000)
+                .with_tags(tags.iter().map(|tag| tag.to_string()).collect())
+                .unwrap()
+        };
+        let pools = storage_with(vec![
+            tagged("USDC-DAI", &["stablecoins"]),
+            tagged("ETH-USDC", &["blue-chip"]),
+            tagged("ETH-DAI", &["blue-chip", "experimental"]),
+        ])
+        .await;
+        let api = routes(pools, empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), ApiConfig::default());
+        
+        let ids = |path: &'static str| {
+            let api = api.clone();
+            async move {
+                let response = warp::test::request().method("GET").path(path).reply(&api).await;
+                assert_eq!(response.status(), 200);
+                let infos: Vec<PoolInfo> = serde_json::from_slice(response.body()).unwrap();
+                let mut ids: Vec<String> = infos.into_iter().map(|info| info.id).collect();
+                ids.sort();
+                ids
+            }
+        };
+        
+        assert_eq!(ids("/pools?tag=blue-chip").await, vec!["ETH-DAI", "ETH-USDC"]);
+        assert_eq!(ids("/pools?tag=stablecoins").await, vec!["USDC-DAI"]);
+        assert!(ids("/pools?tag=unknown").await.is_empty());
+        assert_eq!(ids("/pools").await.len(), 3);
+    }
+    
+    #[tokio::test]
+    async fn test_get_tokens_deduplicates_by_address() {
+        let pools = storage_with(vec![
+            create_pool("ETH-USDC", vec![token("0xAbC1", "ETH", 18), token("0xdef2", "USDC", 6)], 1000),
+            create_pool("ETH-DAI", vec![token("0xabc1", "ETH", 18), token("0x0333", "DAI", 18)], 1000),
+        ])
+        .await;
+        
+        let response = warp::test::request()
+            .method("GET")
+            .path("/tokens")
+            .reply(&routes(pools, empty_history(), empty_quote_cache(), events::channel(), MetricsCollector::new(), ApiConfig::default()))
+            .await;
+        
+        assert_eq!(response.status(), 200);
+        let tokens: Vec<Token> = serde_json::from_slice(response.body()).unwrap();
+        let symbols: Vec<&str> = tokens.iter().map(|t| t.symbol.as_str()).collect();
+        assert_eq!(symbols, vec!["DAI", "ETH", "USDC"]);
+        assert_eq!(tokens[2].decimals, 6);
+    }
+}
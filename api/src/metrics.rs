@@ -1,57 +1,125 @@
+use crate::events::PoolEvent;
+use num_bigint::BigUint;
 use std::collections::HashMap;
 use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError};
 use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Metrics {
     pub total_swaps: u64,
     pub total_volume: HashMap<String, String>, // token -> volume
     pub total_fees_collected: HashMap<String, String>,
     pub active_pools: u64,
-    pub total_liquidity: HashMap<String, String>,
+    pub total_liquidity: HashMap<String, String>, // token -> net amount deposited
     pub average_transaction_time: f64,
 }
 
+#[derive(Clone, Default)]
 pub struct MetricsCollector {
     metrics: Arc<RwLock<Metrics>>,
 }
 
 impl MetricsCollector {
     pub fn new() -> Self {
-        Self {
-            metrics: Arc::new(RwLock::new(Metrics {
-                total_swaps: 0,
-                total_volume: HashMap::new(),
-                total_fees_collected: HashMap::new(),
-                active_pools: 0,
-                total_liquidity: HashMap::new(),
-                average_transaction_time: 0.0,
-            })),
-        }
+        Self::default()
     }
     
-    pub async fn record_swap(&self, input_token: &str, output_token: &str, volume: &str, fee: &str) {
-        let mut metrics = self.metrics.write().await;
-        
-        metrics.total_swaps += 1;
-        
-        // Update volume
-        let current_volume = metrics.total_volume.get(input_token)
-            .and_then(|v| v.parse::<u64>().ok())
-            .unwrap_or(0);
-        let new_volume = current_volume + volume.parse::<u64>().unwrap_or(0);
-        metrics.total_volume.insert(input_token.to_string(), new_volume.to_string());
-        
-        // Update fees
-        let current_fees = metrics.total_fees_collected.get(input_token)
-            .and_then(|f| f.parse::<u64>().ok())
-            .unwrap_or(0);
-        let new_fees = current_fees + fee.parse::<u64>().unwrap_or(0);
-        metrics.total_fees_collected.insert(input_token.to_string(), new_fees.to_string());
+    /// Applies every event published on `events` until the channel closes.
+    /// Events missed by falling too far behind are skipped, not replayed.
+    pub fn subscribe(&self, mut events: broadcast::Receiver<PoolEvent>) -> JoinHandle<()> {
+        let metrics = self.metrics.clone();
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => metrics.write().await.record(&event),
+                    Err(RecvError::Lagged(missed)) => eprintln!("Metrics missed {} pool events", missed),
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        })
     }
     
     pub async fn get_metrics(&self) -> Metrics {
         self.metrics.read().await.clone()
     }
-}
\ No newline at end of file
+}
+
+impl Metrics {
+    fn record(&mut self, event: &PoolEvent) {
+        match event {
+            PoolEvent::Swap { input_token, input_amount, fee, fee_token, .. } => {
+                self.total_swaps += 1;
+                add_to(&mut self.total_volume, input_token, input_amount);
+                add_to(&mut self.total_fees_collected, fee_token, fee);
+            }
+            PoolEvent::LiquidityAdded { token_amounts, .. } => {
+                for (token, amount) in token_amounts {
+                    add_to(&mut self.total_liquidity, token, amount);
+                }
+            }
+            PoolEvent::LiquidityRemoved { token_amounts, .. } => {
+                for (token, amount) in token_amounts {
+                    subtract_from(&mut self.total_liquidity, token, amount);
+                }
+            }
+        }
+    }
+}
+
+// Totals are kept as decimal strings so they never overflow
+fn add_to(totals: &mut HashMap<String, String>, token: &str, amount: &BigUint) {
+    let current = totals.get(token)
+        .and_then(|total| total.parse::<BigUint>().ok())
+        .unwrap_or_default();
+    totals.insert(token.to_string(), (current + amount).to_string());
+}
+
+// Floored at zero: withdrawals can include fees that were never deposited
+fn subtract_from(totals: &mut HashMap<String, String>, token: &str, amount: &BigUint) {
+    let current = totals.get(token)
+        .and_then(|total| total.parse::<BigUint>().ok())
+        .unwrap_or_default();
+    let remaining = if current > *amount { current - amount } else { BigUint::default() };
+    totals.insert(token.to_string(), remaining.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[tokio::test]
+    async fn test_metrics_follow_pool_events() {
+        let events = crate::events::channel();
+        let collector = MetricsCollector::new();
+        let subscription = collector.subscribe(events.subscribe());
+        
+        for _ in 0..2 {
+            crate::events::publish(&events, PoolEvent::Swap {
+                pool_id: "ETH-USDC".to_string(),
+                input_token: "ETH".to_string(),
+                output_token: "USDC".to_string(),
+                input_amount: BigUint::from(1000u64),
+                output_amount: BigUint::from(1900u64),
+                fee: BigUint::from(3u64),
+                fee_token: "ETH".to_string(),
+            });
+        }
+        crate::events::publish(&events, PoolEvent::LiquidityAdded {
+            pool_id: "ETH-USDC".to_string(),
+            token_amounts: HashMap::from([("USDC".to_string(), BigUint::from(u64::MAX))]),
+            lp_tokens: BigUint::from(100u64),
+        });
+        // Closing the channel lets the subscription drain and finish
+        drop(events);
+        subscription.await.unwrap();
+        
+        let metrics = collector.get_metrics().await;
+        assert_eq!(metrics.total_swaps, 2);
+        assert_eq!(metrics.total_volume["ETH"], "2000");
+        assert_eq!(metrics.total_fees_collected["ETH"], "6");
+        assert_eq!(metrics.total_liquidity["USDC"], u64::MAX.to_string());
+    }
+}
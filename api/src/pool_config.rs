@@ -0,0 +1,284 @@
+pub use dex_protocol_core::config::PoolConfig;
+use dex_protocol_core::config::{build_pool, read_pool_configs, ConfigError};
+use dex_protocol_core::{Pool, PoolType, Token};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+// Decimals of well-known tokens by symbol, for tokens given without them
+pub const DEFAULT_TOKEN_DECIMALS: [(&str, u8); 6] = [
+    ("ETH", 18),
+    ("WETH", 18),
+    ("USDC", 6),
+    ("USDT", 6),
+    ("DAI", 18),
+    ("WBTC", 8),
+];
+
+/// A token as a client may describe it: the address defaults to the
+/// symbol and the decimals to the registry's entry for the symbol.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenSpec {
+    #[serde(default)]
+    pub address: Option<String>,
+    pub symbol: String,
+    #[serde(default)]
+    pub decimals: Option<u8>,
+}
+
+/// Symbol -> decimals registry consulted for tokens given without
+/// decimals. Symbols match case-insensitively.
+#[derive(Debug, Clone)]
+pub struct TokenDecimals(HashMap<String, u8>);
+
+impl Default for TokenDecimals {
+    fn default() -> Self {
+        TokenDecimals(
+            DEFAULT_TOKEN_DECIMALS
+                .iter()
+                .map(|(symbol, decimals)| (symbol.to_string(), *decimals))
+                .collect(),
+        )
+    }
+}
+
+impl TokenDecimals {
+    /// The defaults with `{"<symbol>": <decimals>}` entries on top;
+    /// malformed JSON leaves just the defaults.
+    pub fn with_overrides(json: &str) -> Self {
+        let mut registry = TokenDecimals::default();
+        let overrides: HashMap<String, u8> = serde_json::from_str(json).unwrap_or_default();
+        for (symbol, decimals) in overrides {
+            registry.0.insert(symbol.to_uppercase(), decimals);
+        }
+        registry
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<u8> {
+        self.0.get(&symbol.to_uppercase()).copied()
+    }
+
+    // A guessed default would misprice every amount, so an unknown symbol is an error
+    pub fn resolve(&self, spec: TokenSpec) -> Result<Token, String> {
+        let decimals = match spec.decimals {
+            Some(decimals) => decimals,
+            None => self.get(&spec.symbol).ok_or_else(|| {
+                format!(
+                    "decimals for {} are unknown; give them explicitly",
+                    spec.symbol
+                )
+            })?,
+        };
+        Ok(Token {
+            address: spec.address.unwrap_or_else(|| spec.symbol.clone()),
+            symbol: spec.symbol,
+            decimals,
+        })
+    }
+}
+
+/// Which configured pools were loaded and why the others were rejected.
+#[derive(Debug, Default)]
+pub struct InitReport {
+    pub loaded: Vec<String>,
+    pub rejected: Vec<(String, String)>, // (pool id, reason)
+}
+
+impl InitReport {
+    pub fn log(&self) {
+        println!(
+            "Loaded {} pool(s), rejected {}",
+            self.loaded.len(),
+            self.rejected.len()
+        );
+        for (id, reason) in &self.rejected {
+            println!("  rejected {}: {}", id, reason);
+        }
+    }
+}
+
+/// Pool file named by a `--pools <file>` (or `--pools=<file>`) argument,
+/// else by `POOLS_FILE`.
+pub fn pools_file(mut args: impl Iterator<Item = String>) -> Option<PathBuf> {
+    while let Some(arg) = args.next() {
+        if arg == "--pools" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--pools=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+    std::env::var_os("POOLS_FILE").map(PathBuf::from)
+}
+
+/// Pool configs to start with: those in `file` when one is given, else the
+/// built-in samples. Only a file that cannot be read or parsed is an error;
+/// the pools in it are validated one by one by `load_pools`.
+pub fn startup_pool_configs(file: Option<&Path>) -> Result<Vec<PoolConfig>, ConfigError> {
+    match file {
+        Some(path) => read_pool_configs(path),
+        None => Ok(sample_pool_configs()),
+    }
+}
+
+pub fn sample_pool_configs() -> Vec<PoolConfig> {
+    let eth = Token {
+        address: "0x0000000000000000000000000000000000000000".to_string(),
+        symbol: "ETH".to_string(),
+        decimals: 18,
+    };
+    let usdc = Token {
+        address: "0xA0b86a33E6441B8C5c4EA1E18AA41bE2d5E27ad2".to_string(),
+        symbol: "USDC".to_string(),
+        decimals: 6,
+    };
+
+    let mut reserves = HashMap::new();
+    reserves.insert(eth.address.clone(), "1000000000000000000".to_string()); // 1 ETH
+    reserves.insert(usdc.address.clone(), "2000000000".to_string()); // 2000 USDC
+
+    vec![PoolConfig {
+        id: "ETH-USDC".to_string(),
+        tokens: vec![eth, usdc],
+        reserves,
+        fee_rate: Some(300), // 3% fee
+        pool_type: PoolType::ConstantProduct.to_string(),
+        amplification: None,
+        tags: vec!["blue-chip".to_string()],
+    }]
+}
+
+/// Builds every valid pool, recording the rest in the report instead of
+/// failing startup.
+pub fn load_pools(configs: Vec<PoolConfig>) -> (Vec<Pool>, InitReport) {
+    let mut report = InitReport::default();
+    let mut seen_ids = HashSet::new();
+    let mut pools = Vec::new();
+
+    for config in configs {
+        let id = config.id.clone();
+        let result = if seen_ids.contains(&id) {
+            Err("duplicate pool id".to_string())
+        } else {
+            build_pool(config)
+        };
+
+        match result {
+            Ok(pool) => {
+                seen_ids.insert(id.clone());
+                report.loaded.push(id);
+                pools.push(pool);
+            }
+            Err(reason) => report.rejected.push((id, reason)),
+        }
+    }
+
+    (pools, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_valid_pools_load() {
+        let valid = sample_pool_configs().remove(0);
+
+        let mut invalid = valid.clone();
+        invalid.id = "BROKEN".to_string();
+        invalid.fee_rate = Some(250);
+        let mut zero_reserve = valid.clone();
+        zero_reserve.id = "EMPTY".to_string();
+        let first = zero_reserve.tokens[0].address.clone();
+        zero_reserve.reserves.insert(first, "0".to_string());
+
+        let mut bad_tag = valid.clone();
+        bad_tag.id = "TAGGED".to_string();
+        bad_tag.tags = vec!["Blue Chip".to_string()];
+
+        let (pools, report) = load_pools(vec![valid, invalid, zero_reserve, bad_tag]);
+
+        assert_eq!(pools.len(), 1);
+        assert_eq!(pools[0].id, "ETH-USDC");
+        assert_eq!(pools[0].tags, vec!["blue-chip"]);
+        assert_eq!(report.loaded, vec!["ETH-USDC"]);
+        let rejected: Vec<&str> = report.rejected.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(rejected, vec!["BROKEN", "EMPTY", "TAGGED"]);
+        assert!(report.rejected[0].1.contains("fee rate 250"));
+        assert!(report.rejected[1].1.contains("zero"));
+        assert!(report.rejected[2].1.contains("Blue Chip"));
+    }
+
+    #[test]
+    fn test_pools_file_from_arguments() {
+        let args = |args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            pools_file(args.into_iter())
+        };
+        assert_eq!(
+            args(&["dex-api", "--pools", "pools.json"]),
+            Some(PathBuf::from("pools.json"))
+        );
+        assert_eq!(
+            args(&["dex-api", "--pools=/etc/dex/pools.json"]),
+            Some(PathBuf::from("/etc/dex/pools.json"))
+        );
+    }
+
+    #[test]
+    fn test_pool_file_loads_its_valid_pools() {
+        let valid = sample_pool_configs().remove(0);
+        let mut invalid = valid.clone();
+        invalid.id = "BROKEN".to_string();
+        invalid.fee_rate = Some(250);
+        let path =
+            std::env::temp_dir().join(format!("dex-startup-pools-{}.json", std::process::id()));
+        std::fs::write(&path, serde_json::to_string(&[valid, invalid]).unwrap()).unwrap();
+
+        // The invalid pool is reported and the rest of the file still loads
+        let (pools, report) = load_pools(startup_pool_configs(Some(&path)).unwrap());
+        assert_eq!(pools.len(), 1);
+        assert_eq!(report.loaded, vec!["ETH-USDC"]);
+        assert_eq!(report.rejected[0].0, "BROKEN");
+
+        // A file that does not parse fails startup instead
+        std::fs::write(&path, "[{").unwrap();
+        assert!(matches!(
+            startup_pool_configs(Some(&path)),
+            Err(ConfigError::Parse(_))
+        ));
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(startup_pool_configs(None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_token_decimals_overrides() {
+        let registry = TokenDecimals::with_overrides(r#"{"usdc": 18, "ARB": 18}"#);
+        assert_eq!(registry.get("USDC"), Some(18));
+        assert_eq!(registry.get("arb"), Some(18));
+        assert_eq!(registry.get("WBTC"), Some(8));
+        assert_eq!(
+            TokenDecimals::with_overrides("not json").get("USDC"),
+            Some(6)
+        );
+    }
+
+    #[test]
+    fn test_fee_rate_defaults_by_pool_type() {
+        let mut volatile = sample_pool_configs().remove(0);
+        volatile.fee_rate = None;
+        let mut stable = volatile.clone();
+        stable.id = "STABLE".to_string();
+        stable.pool_type = PoolType::StableSwap.to_string();
+        // StableSwap pools need tokens of one precision
+        for token in &mut stable.tokens {
+            token.decimals = 6;
+        }
+
+        let (pools, _) = load_pools(vec![volatile, stable]);
+
+        assert_eq!(pools[0].fee_rate, 30);
+        assert_eq!(pools[1].fee_rate, 4);
+    }
+}
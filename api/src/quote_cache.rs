@@ -0,0 +1,111 @@
+use dex_protocol_core::routing::{find_best_route_with, Route, RoutingConfig};
+use dex_protocol_core::{Pool, Token};
+use num_bigint::BigUint;
+use std::collections::HashMap;
+
+// Whole-token input sizes quoted ahead of requests unless configured otherwise
+pub const DEFAULT_WARM_SIZES: [u64; 4] = [1, 10, 100, 1000];
+
+// Seconds between warming passes unless configured otherwise
+pub const DEFAULT_WARM_INTERVAL_SECS: u64 = 10;
+
+/// A quote for `input_amount` of one token in another, over any pools.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct QuoteKey {
+    pub input_token: String,
+    pub output_token: String,
+    pub input_amount: BigUint,
+}
+
+impl QuoteKey {
+    pub fn new(input_token: &str, output_token: &str, input_amount: &BigUint) -> Self {
+        QuoteKey {
+            input_token: input_token.to_string(),
+            output_token: output_token.to_string(),
+            input_amount: input_amount.clone(),
+        }
+    }
+}
+
+/// Best routes found by the router in one warming pass. A route may cross
+/// any pool, so the routes are only served while every pool is still at the
+/// version it was warmed at and no pool has been added since.
+#[derive(Debug, Clone, Default)]
+pub struct WarmQuotes {
+    pool_versions: HashMap<String, u64>,
+    pub routes: HashMap<QuoteKey, Route>,
+}
+
+impl WarmQuotes {
+    pub fn get(&self, pools: &HashMap<String, Pool>, key: &QuoteKey) -> Option<&Route> {
+        let current = pools.len() == self.pool_versions.len()
+            && pools
+                .values()
+                .all(|pool| self.pool_versions.get(&pool.id) == Some(&pool.version));
+        if current {
+            self.routes.get(key)
+        } else {
+            None
+        }
+    }
+}
+
+/// Routes every ordered pair of pooled tokens at each of `sizes` whole
+/// input tokens, through at most `max_hops` of the pools `config` allows.
+/// Pairs the router finds no route for at a size are left out.
+pub fn warm_quotes(
+    pools: &HashMap<String, Pool>,
+    sizes: &[u64],
+    max_hops: usize,
+    config: &RoutingConfig,
+) -> WarmQuotes {
+    // Sorted so ties between routes go the same way on every pass
+    let mut candidates: Vec<&Pool> = pools.values().collect();
+    candidates.sort_by(|a, b| a.id.cmp(&b.id));
+    let mut tokens: Vec<&Token> = Vec::new();
+    for token in candidates.iter().flat_map(|pool| &pool.tokens) {
+        if !tokens.iter().any(|t| t.address == token.address) {
+            tokens.push(token);
+        }
+    }
+
+    let mut routes = HashMap::new();
+    for input in &tokens {
+        let unit = BigUint::from(10u64).pow(input.decimals.into());
+        for output in &tokens {
+            if input.address == output.address {
+                continue;
+            }
+            for &size in sizes {
+                let amount = &unit * size;
+                let best = find_best_route_with(
+                    &candidates,
+                    &input.address,
+                    &output.address,
+                    &amount,
+                    max_hops,
+                    config,
+                );
+                if let Some(route) = best {
+                    let key = QuoteKey::new(&input.address, &output.address, &amount);
+                    routes.insert(key, route);
+                }
+            }
+        }
+    }
+
+    WarmQuotes {
+        pool_versions: pools
+            .values()
+            .map(|pool| (pool.id.clone(), pool.version))
+            .collect(),
+        routes,
+    }
+}
+
+// "1,10,100"; entries that are not whole numbers are skipped
+pub fn parse_warm_sizes(list: &str) -> Vec<u64> {
+    list.split(',')
+        .filter_map(|size| size.trim().parse().ok())
+        .collect()
+}
@@ -1,5 +1,13 @@
+mod token_cache;
+
+pub use token_cache::{TokenMetadata, TokenMetadataCache};
+
+use ethers::middleware::signer::SignerMiddlewareError;
 use ethers::prelude::*;
-use std::sync::Arc;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 // Contract ABI definitions
 abigen!(
@@ -22,10 +30,60 @@ abigen!(
     ]"#
 );
 
+abigen!(
+    ERC20,
+    r#"[
+        function symbol() external view returns (string)
+        function decimals() external view returns (uint8)
+    ]"#
+);
+
+// Token metadata is cached for an hour unless configured otherwise
+const DEFAULT_TOKEN_CACHE_TTL: Duration = Duration::from_secs(3600);
+const DEFAULT_TOKEN_CACHE_CAPACITY: usize = 1024;
+
+type SignerClient = SignerMiddleware<Arc<Provider<Http>>, LocalWallet>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ContractError {
+    #[error("RPC request timed out")]
+    Timeout,
+}
+
 pub struct DEXProtocol {
     pub router: DEXRouter<Provider<Http>>,
     pub factory: DEXFactory<Provider<Http>>,
     pub provider: Arc<Provider<Http>>,
+    pub timeout: Duration,
+    token_cache: Mutex<TokenMetadataCache>,
+    swap_submissions: Mutex<HashMap<SwapKey, SwapSubmission>>,
+}
+
+// Broadcasts of one swap before `swap_tokens_reliable` gives up
+const SWAP_BROADCAST_ATTEMPTS: usize = 3;
+
+// Identifies a swap across retries: the same sender asking for the same trade
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct SwapKey {
+    from: Address,
+    token_in: Address,
+    token_out: Address,
+    amount_in: U256,
+    amount_out_min: U256,
+    deadline: U256,
+}
+
+// A broadcast swap. Retries rebroadcast exactly this transaction, so the
+// nonce is never spent twice.
+#[derive(Debug, Clone)]
+struct SwapSubmission {
+    tx: TypedTransaction,
+    tx_hash: H256,
+}
+
+enum SubmitOutcome {
+    Landed(Box<TransactionReceipt>),
+    Broadcast(H256),
 }
 
 impl DEXProtocol {
@@ -33,8 +91,12 @@ impl DEXProtocol {
         provider_url: &str,
         router_address: Address,
         factory_address: Address,
+        timeout: Duration,
     ) -> Result<Self, Box<dyn std::error::Error>> {
-        let provider = Provider::<Http>::try_from(provider_url)?;
+        // Every RPC request gives up after `timeout` instead of hanging on a stuck node
+        let client = reqwest::Client::builder().timeout(timeout).build()?;
+        let url = reqwest::Url::parse(provider_url)?;
+        let provider = Provider::new(Http::new_with_client(url, client));
         let provider = Arc::new(provider);
         
         let router = DEXRouter::new(router_address, provider.clone());
@@ -44,9 +106,47 @@ impl DEXProtocol {
             router,
             factory,
             provider,
+            timeout,
+            token_cache: Mutex::new(TokenMetadataCache::new(
+                DEFAULT_TOKEN_CACHE_TTL,
+                DEFAULT_TOKEN_CACHE_CAPACITY,
+            )),
+            swap_submissions: Mutex::new(HashMap::new()),
         })
     }
 
+    /// Replaces the token metadata cache with one of the given TTL and capacity.
+    pub fn with_token_cache(mut self, ttl: Duration, capacity: usize) -> Self {
+        self.token_cache = Mutex::new(TokenMetadataCache::new(ttl, capacity));
+        self
+    }
+
+    pub async fn fetch_token_metadata(
+        &self,
+        token: Address,
+    ) -> Result<TokenMetadata, Box<dyn std::error::Error>> {
+        if let Some(metadata) = self.token_cache.lock().unwrap().get(&token) {
+            return Ok(metadata);
+        }
+        
+        let erc20 = ERC20::new(token, self.provider.clone());
+        let symbol = erc20.symbol().call().await.map_err(map_rpc_error)?;
+        let decimals = erc20.decimals().call().await.map_err(map_rpc_error)?;
+        
+        let metadata = TokenMetadata {
+            address: token,
+            symbol,
+            decimals,
+        };
+        self.token_cache.lock().unwrap().insert(metadata.clone());
+        Ok(metadata)
+    }
+
+    /// Forgets cached metadata for `token`, e.g. after a proxy upgrade.
+    pub fn invalidate_token_metadata(&self, token: Address) {
+        self.token_cache.lock().unwrap().invalidate(&token);
+    }
+
     pub async fn swap_tokens(
         &self,
         wallet: &LocalWallet,
@@ -62,19 +162,81 @@ impl DEXProtocol {
         let path = vec![token_in, token_out];
         let to = wallet.address();
         
-        let tx = router
+        let call = router.swap_exact_tokens_for_tokens(
+            amount_in,
+            amount_out_min,
+            path,
+            to,
+            deadline,
+        );
+        let tx = call.send().await.map_err(map_rpc_error)?;
+            
+        let receipt = tx.await.map_err(map_rpc_error)?;
+        Ok(receipt.unwrap())
+    }
+
+    /// Like `swap_tokens`, but safe to retry. The signed transaction is
+    /// remembered per sender and parameters until its receipt is in; a retry
+    /// first checks whether it landed and otherwise rebroadcasts it unchanged,
+    /// with the same nonce, rather than submitting a second swap. Within one
+    /// call, a transaction dropped from the mempool is broadcast up to
+    /// `SWAP_BROADCAST_ATTEMPTS` times.
+    pub async fn swap_tokens_reliable(
+        &self,
+        wallet: &LocalWallet,
+        token_in: Address,
+        token_out: Address,
+        amount_in: U256,
+        amount_out_min: U256,
+        deadline: U256,
+    ) -> Result<TransactionReceipt, Box<dyn std::error::Error>> {
+        let client = SignerMiddleware::new(self.provider.clone(), wallet.clone());
+        let key = SwapKey {
+            from: wallet.address(),
+            token_in,
+            token_out,
+            amount_in,
+            amount_out_min,
+            deadline,
+        };
+        let tx = self
+            .router
             .swap_exact_tokens_for_tokens(
                 amount_in,
                 amount_out_min,
-                path,
-                to,
+                vec![token_in, token_out],
+                key.from,
                 deadline,
             )
-            .send()
-            .await?;
-            
-        let receipt = tx.await?;
-        Ok(receipt.unwrap())
+            .tx;
+        
+        reliable_swap(&client, &self.swap_submissions, key, tx).await
+    }
+
+    /// Replaces a stuck transaction by sending a 0-value self-transfer with
+    /// the same `nonce`, priced `gas_bump_percent` above the stuck
+    /// transaction's gas price or the current one, whichever is higher.
+    /// The stuck transaction is looked up in the node's `txpool`.
+    pub async fn cancel_transaction(
+        &self,
+        wallet: &LocalWallet,
+        nonce: U256,
+        gas_bump_percent: u64,
+    ) -> Result<TransactionReceipt, Box<dyn std::error::Error>> {
+        let tx = cancellation_request(
+            self.provider.as_ref(),
+            wallet.address(),
+            nonce,
+            gas_bump_percent,
+        )
+        .await
+        .map_err(map_rpc_error)?;
+        
+        let client = SignerMiddleware::new(self.provider.clone(), wallet.clone());
+        let pending = client.send_transaction(tx, None).await.map_err(map_rpc_error)?;
+        
+        let receipt = pending.await.map_err(map_rpc_error)?;
+        receipt.ok_or_else(|| "cancellation transaction was dropped from the mempool".into())
     }
 
     pub async fn get_amounts_out(
@@ -82,7 +244,12 @@ impl DEXProtocol {
         amount_in: U256,
         path: Vec<Address>,
     ) -> Result<Vec<U256>, Box<dyn std::error::Error>> {
-        let amounts = self.router.get_amounts_out(amount_in, path).call().await?;
+        let amounts = self
+            .router
+            .get_amounts_out(amount_in, path)
+            .call()
+            .await
+            .map_err(map_rpc_error)?;
         Ok(amounts)
     }
 
@@ -95,11 +262,342 @@ impl DEXProtocol {
         let client = SignerMiddleware::new(self.provider.clone(), wallet.clone());
         let factory = DEXFactory::new(self.factory.address(), Arc::new(client));
         
-        let tx = factory.create_pair(token_a, token_b).send().await?;
-        let receipt = tx.await?.unwrap();
+        let call = factory.create_pair(token_a, token_b);
+        let tx = call.send().await.map_err(map_rpc_error)?;
+        tx.await.map_err(map_rpc_error)?;
         
         // Extract pair address from logs
-        let pair_address = self.factory.get_pair(token_a, token_b).call().await?;
+        let pair_address = self
+            .factory
+            .get_pair(token_a, token_b)
+            .call()
+            .await
+            .map_err(map_rpc_error)?;
         Ok(pair_address)
     }
-}
\ No newline at end of file
+}
+
+// Gas limit of a plain ETH transfer
+const TRANSFER_GAS: u64 = 21_000;
+
+// Builds the self-transfer that replaces the transaction at `nonce`. Nodes only
+// accept a replacement that pays strictly more than the one it replaces, so the
+// bump starts from the stuck transaction's price when that is above the network's.
+async fn cancellation_request<M: Middleware>(
+    client: &M,
+    from: Address,
+    nonce: U256,
+    gas_bump_percent: u64,
+) -> Result<TransactionRequest, M::Error> {
+    let stuck_price = pending_gas_price(client, from, nonce).await?;
+    let gas_price = client
+        .get_gas_price()
+        .await?
+        .max(stuck_price.unwrap_or_default());
+    let bumped = gas_price * (100 + gas_bump_percent) / 100;
+    
+    Ok(TransactionRequest::new()
+        .from(from)
+        .to(from)
+        .value(U256::zero())
+        .nonce(nonce)
+        .gas(TRANSFER_GAS)
+        .gas_price(bumped.max(gas_price + 1)))
+}
+
+// Gas price of `from`'s transaction at `nonce` if the node still holds it,
+// pending or queued. EIP-1559 transactions are priced by their max fee.
+async fn pending_gas_price<M: Middleware>(
+    client: &M,
+    from: Address,
+    nonce: U256,
+) -> Result<Option<U256>, M::Error> {
+    let mut content = client.txpool_content().await?;
+    let nonce = nonce.to_string();
+    let tx = [&mut content.pending, &mut content.queued]
+        .into_iter()
+        .find_map(|pool| pool.get_mut(&from)?.remove(&nonce));
+    Ok(tx.and_then(|tx| tx.gas_price.or(tx.max_fee_per_gas)))
+}
+
+// The broadcast loop of `swap_tokens_reliable`. A submission is kept until its
+// receipt is in, so only retries of a swap still in flight find it.
+async fn reliable_swap<M>(
+    client: &M,
+    submissions: &Mutex<HashMap<SwapKey, SwapSubmission>>,
+    key: SwapKey,
+    tx: TypedTransaction,
+) -> Result<TransactionReceipt, Box<dyn std::error::Error>>
+where
+    M: Middleware,
+    M::Error: 'static,
+{
+    for _ in 0..SWAP_BROADCAST_ATTEMPTS {
+        let previous = submissions.lock().unwrap().get(&key).cloned();
+        let (outcome, submission) = submit_swap(client, previous, tx.clone())
+            .await
+            .map_err(map_rpc_error)?;
+        submissions.lock().unwrap().insert(key.clone(), submission);
+        
+        let receipt = match outcome {
+            SubmitOutcome::Landed(receipt) => Some(*receipt),
+            // Resolves to None if the node drops the transaction
+            SubmitOutcome::Broadcast(tx_hash) => {
+                PendingTransaction::new(tx_hash, client.provider())
+                    .await
+                    .map_err(map_rpc_error)?
+            }
+        };
+        if let Some(receipt) = receipt {
+            submissions.lock().unwrap().remove(&key);
+            return Ok(receipt);
+        }
+    }
+    
+    Err("swap transaction was dropped on every attempt".into())
+}
+
+// One attempt at a swap: returns the receipt if `previous` already landed,
+// otherwise (re)broadcasts. A fresh swap is filled (nonce, gas) first; a
+// retry reuses `previous` as is, so it can only ever replace itself.
+async fn submit_swap<M: Middleware>(
+    client: &M,
+    previous: Option<SwapSubmission>,
+    mut tx: TypedTransaction,
+) -> Result<(SubmitOutcome, SwapSubmission), M::Error> {
+    match previous {
+        Some(previous) => {
+            if let Some(receipt) = client.get_transaction_receipt(previous.tx_hash).await? {
+                return Ok((SubmitOutcome::Landed(Box::new(receipt)), previous));
+            }
+            tx = previous.tx;
+        }
+        None => client.fill_transaction(&mut tx, None).await?,
+    }
+    
+    let tx_hash = client.send_transaction(tx.clone(), None).await?.tx_hash();
+    Ok((SubmitOutcome::Broadcast(tx_hash), SwapSubmission { tx, tx_hash }))
+}
+
+// Surfaces HTTP timeouts as `ContractError::Timeout`; everything else passes through
+fn map_rpc_error<E: std::error::Error + 'static>(err: E) -> Box<dyn std::error::Error> {
+    if is_timeout(&err) {
+        Box::new(ContractError::Timeout)
+    } else {
+        Box::new(err)
+    }
+}
+
+fn is_timeout(err: &(dyn std::error::Error + 'static)) -> bool {
+    if let Some(err) = err.downcast_ref::<ProviderError>() {
+        return provider_timed_out(err);
+    }
+    if let Some(err) = err.downcast_ref::<ethers::contract::ContractError<Provider<Http>>>() {
+        return err
+            .as_middleware_error()
+            .or_else(|| err.as_provider_error())
+            .is_some_and(provider_timed_out);
+    }
+    if let Some(err) = err.downcast_ref::<SignerMiddlewareError<Arc<Provider<Http>>, LocalWallet>>() {
+        return matches!(err, SignerMiddlewareError::MiddlewareError(inner) if provider_timed_out(inner));
+    }
+    if let Some(err) = err.downcast_ref::<ethers::contract::ContractError<SignerClient>>() {
+        return match err.as_middleware_error() {
+            Some(SignerMiddlewareError::MiddlewareError(inner)) => provider_timed_out(inner),
+            _ => err.as_provider_error().is_some_and(provider_timed_out),
+        };
+    }
+    false
+}
+
+fn provider_timed_out(err: &ProviderError) -> bool {
+    matches!(err, ProviderError::HTTPError(e) if e.is_timeout())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_rpc_timeout() {
+        // RPC node that accepts the request but never answers in time
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = socket.read(&mut buf).await;
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        });
+
+        let dex = DEXProtocol::new(
+            &format!("http://{}", addr),
+            Address::zero(),
+            Address::zero(),
+            Duration::from_millis(200),
+        )
+        .await
+        .unwrap();
+
+        let result = dex
+            .get_amounts_out(U256::from(100u64), vec![Address::zero(), Address::zero()])
+            .await;
+
+        let err = result.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ContractError>(),
+            Some(ContractError::Timeout)
+        ));
+    }
+
+    // Node txpool holding `from`'s transaction at `nonce`, priced `gas_price`
+    fn txpool_with(from: Address, nonce: U256, gas_price: U256) -> TxpoolContent {
+        let tx = Transaction {
+            from,
+            nonce,
+            gas_price: Some(gas_price),
+            ..Default::default()
+        };
+        let mut content = TxpoolContent::default();
+        content
+            .pending
+            .entry(from)
+            .or_default()
+            .insert(nonce.to_string(), tx);
+        content
+    }
+
+    #[tokio::test]
+    async fn test_cancellation_reuses_nonce_with_higher_gas() {
+        let (provider, mock) = Provider::mocked();
+        let network_price = U256::from(20_000_000_000u64); // 20 gwei
+        let stuck_price = U256::from(30_000_000_000u64);
+        let from = Address::repeat_byte(0x11);
+        let nonce = U256::from(42u64);
+
+        // Responses are served last pushed first: the txpool, then the gas price
+        mock.push(network_price).unwrap();
+        mock.push(txpool_with(from, nonce, stuck_price)).unwrap();
+        let tx = cancellation_request(&provider, from, nonce, 10).await.unwrap();
+
+        assert_eq!(tx.nonce, Some(nonce));
+        assert_eq!(tx.to, Some(from.into()));
+        assert_eq!(tx.value, Some(U256::zero()));
+        // The bump is on the stuck transaction's price, which is the higher
+        assert_eq!(tx.gas_price, Some(U256::from(33_000_000_000u64)));
+
+        // Even a zero bump must outbid the stuck transaction
+        mock.push(network_price).unwrap();
+        mock.push(txpool_with(from, nonce, stuck_price)).unwrap();
+        let tx = cancellation_request(&provider, from, nonce, 0).await.unwrap();
+        assert!(tx.gas_price.unwrap() > stuck_price);
+
+        // Another nonce's transaction is not the one being replaced, so the
+        // network price applies
+        mock.push(network_price).unwrap();
+        mock.push(txpool_with(from, nonce + 1, stuck_price)).unwrap();
+        let tx = cancellation_request(&provider, from, nonce, 10).await.unwrap();
+        assert_eq!(tx.gas_price, Some(U256::from(22_000_000_000u64)));
+    }
+
+    // A wallet and a swap transaction it signs, with the raw bytes and hash
+    // the node sees. Fully specified, so filling it needs no RPC calls.
+    fn signed_swap() -> (LocalWallet, TypedTransaction, Bytes, H256) {
+        let wallet = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318"
+            .parse::<LocalWallet>()
+            .unwrap()
+            .with_chain_id(1u64);
+        let tx: TypedTransaction = TransactionRequest::new()
+            .from(wallet.address())
+            .to(Address::repeat_byte(0x22))
+            .data(vec![0x38, 0xed, 0x17, 0x39])
+            .nonce(7u64)
+            .gas(200_000u64)
+            .gas_price(20_000_000_000u64)
+            .chain_id(1u64)
+            .into();
+        let signature = wallet.sign_transaction_sync(&tx).unwrap();
+        let raw = tx.rlp_signed(&signature);
+        let tx_hash = tx.hash(&signature);
+        (wallet, tx, raw, tx_hash)
+    }
+
+    #[tokio::test]
+    async fn test_dropped_swap_is_rebroadcast_not_resubmitted() {
+        let (provider, mock) = Provider::mocked();
+        let (wallet, tx, raw, tx_hash) = signed_swap();
+        let client = SignerMiddleware::new(provider, wallet);
+        let receipt = TransactionReceipt {
+            transaction_hash: tx_hash,
+            ..Default::default()
+        };
+
+        // First attempt broadcasts
+        mock.push(tx_hash).unwrap();
+        let (outcome, submission) = submit_swap(&client, None, tx.clone()).await.unwrap();
+        assert!(matches!(outcome, SubmitOutcome::Broadcast(hash) if hash == tx_hash));
+
+        // It was dropped: the retry finds no receipt and rebroadcasts the same transaction
+        mock.push(tx_hash).unwrap();
+        mock.push(Option::<TransactionReceipt>::None).unwrap();
+        let (outcome, submission) = submit_swap(&client, Some(submission), tx.clone()).await.unwrap();
+        assert!(matches!(outcome, SubmitOutcome::Broadcast(hash) if hash == tx_hash));
+
+        // Then it landed: the next retry returns its receipt without sending anything
+        mock.push(receipt.clone()).unwrap();
+        let (outcome, _) = submit_swap(&client, Some(submission), tx).await.unwrap();
+        assert!(matches!(outcome, SubmitOutcome::Landed(r) if r.transaction_hash == tx_hash));
+
+        mock.assert_request("eth_sendRawTransaction", [&raw]).unwrap();
+        mock.assert_request("eth_getTransactionReceipt", [tx_hash]).unwrap();
+        mock.assert_request("eth_sendRawTransaction", [&raw]).unwrap();
+        mock.assert_request("eth_getTransactionReceipt", [tx_hash]).unwrap();
+        assert!(mock.assert_request("eth_sendRawTransaction", [&raw]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_swap_landing_after_a_drop_is_not_resubmitted() {
+        let (provider, mock) = Provider::mocked();
+        let provider = provider.interval(Duration::from_millis(1));
+        let (wallet, tx, raw, tx_hash) = signed_swap();
+        let client = SignerMiddleware::new(provider, wallet.clone());
+        let key = SwapKey {
+            from: wallet.address(),
+            token_in: Address::repeat_byte(0x33),
+            token_out: Address::repeat_byte(0x44),
+            amount_in: U256::from(1000u64),
+            amount_out_min: U256::from(990u64),
+            deadline: U256::from(1_700_000_000u64),
+        };
+        let submissions = Mutex::new(HashMap::new());
+        let receipt = TransactionReceipt {
+            transaction_hash: tx_hash,
+            ..Default::default()
+        };
+
+        // Served last pushed first. The broadcast looks dropped: the node
+        // stops knowing the transaction. Yet it landed, so the second
+        // attempt finds its receipt.
+        mock.push(receipt).unwrap();
+        for _ in 0..4 {
+            mock.push(Option::<Transaction>::None).unwrap();
+        }
+        mock.push(tx_hash).unwrap();
+
+        let landed = reliable_swap(&client, &submissions, key, tx).await.unwrap();
+        assert_eq!(landed.transaction_hash, tx_hash);
+
+        // Broadcast once, never again after the drop
+        mock.assert_request("eth_sendRawTransaction", [&raw]).unwrap();
+        for _ in 0..4 {
+            mock.assert_request("eth_getTransactionByHash", [tx_hash]).unwrap();
+        }
+        mock.assert_request("eth_getTransactionReceipt", [tx_hash]).unwrap();
+        assert!(mock.assert_request("eth_sendRawTransaction", [&raw]).is_err());
+
+        // Confirmed, so nothing is left to rebroadcast
+        assert!(submissions.lock().unwrap().is_empty());
+    }
+}
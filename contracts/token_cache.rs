@@ -0,0 +1,154 @@
+use ethers::types::Address;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// On-chain ERC20 metadata. Rarely changes, but can after a proxy upgrade.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenMetadata {
+    pub address: Address,
+    pub symbol: String,
+    pub decimals: u8,
+}
+
+struct CacheEntry {
+    metadata: TokenMetadata,
+    fetched_at: Instant,
+    last_used: u64,
+}
+
+/// LRU cache of token metadata whose entries expire after `ttl`.
+pub struct TokenMetadataCache {
+    ttl: Duration,
+    capacity: usize,
+    entries: HashMap<Address, CacheEntry>,
+    clock: u64, // bumped on every access to order entries by recency
+}
+
+impl TokenMetadataCache {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity,
+            entries: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    /// Returns the cached metadata unless it is missing or older than the TTL.
+    pub fn get(&mut self, token: &Address) -> Option<TokenMetadata> {
+        self.clock += 1;
+        let ttl = self.ttl;
+        match self.entries.get_mut(token) {
+            Some(entry) if entry.fetched_at.elapsed() < ttl => {
+                entry.last_used = self.clock;
+                Some(entry.metadata.clone())
+            }
+            Some(_) => {
+                self.entries.remove(token);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Stores freshly fetched metadata, evicting the least recently used
+    /// entry when the cache is full.
+    pub fn insert(&mut self, metadata: TokenMetadata) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.clock += 1;
+        if !self.entries.contains_key(&metadata.address) && self.entries.len() >= self.capacity {
+            let lru = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(address, _)| *address);
+            if let Some(address) = lru {
+                self.entries.remove(&address);
+            }
+        }
+        self.entries.insert(
+            metadata.address,
+            CacheEntry {
+                metadata,
+                fetched_at: Instant::now(),
+                last_used: self.clock,
+            },
+        );
+    }
+
+    /// Drops a token so its next lookup goes back to the chain.
+    pub fn invalidate(&mut self, token: &Address) {
+        self.entries.remove(token);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(byte: u8, symbol: &str) -> TokenMetadata {
+        TokenMetadata {
+            address: Address::repeat_byte(byte),
+            symbol: symbol.to_string(),
+            decimals: 18,
+        }
+    }
+
+    // Mirrors how `fetch_token_metadata` consults the cache before the chain
+    fn fetch(
+        cache: &mut TokenMetadataCache,
+        token: TokenMetadata,
+        fetches: &mut u32,
+    ) -> TokenMetadata {
+        if let Some(cached) = cache.get(&token.address) {
+            return cached;
+        }
+        *fetches += 1;
+        cache.insert(token.clone());
+        token
+    }
+
+    #[test]
+    fn test_cache_hit_within_ttl_and_refetch_after_expiry() {
+        let mut cache = TokenMetadataCache::new(Duration::from_millis(50), 8);
+        let mut fetches = 0;
+
+        fetch(&mut cache, metadata(1, "WETH"), &mut fetches);
+        fetch(&mut cache, metadata(1, "WETH"), &mut fetches);
+        assert_eq!(fetches, 1);
+
+        std::thread::sleep(Duration::from_millis(60));
+        fetch(&mut cache, metadata(1, "WETH"), &mut fetches);
+        assert_eq!(fetches, 2);
+
+        cache.invalidate(&Address::repeat_byte(1));
+        fetch(&mut cache, metadata(1, "WETH"), &mut fetches);
+        assert_eq!(fetches, 3);
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let mut cache = TokenMetadataCache::new(Duration::from_secs(60), 2);
+        cache.insert(metadata(1, "WETH"));
+        cache.insert(metadata(2, "USDC"));
+
+        // Touching WETH leaves USDC as the eviction candidate
+        assert!(cache.get(&Address::repeat_byte(1)).is_some());
+        cache.insert(metadata(3, "DAI"));
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&Address::repeat_byte(1)).is_some());
+        assert!(cache.get(&Address::repeat_byte(2)).is_none());
+        assert!(cache.get(&Address::repeat_byte(3)).is_some());
+    }
+}
@@ -0,0 +1,83 @@
+//! Counts heap allocations per quote on the swap hot path.
+//!
+//! Run with `cargo bench -p dex-protocol-core --bench quote_allocations`.
+
+use dex_protocol_core::{Pool, PoolType, Token};
+use num_bigint::BigUint;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const QUOTES: usize = 10_000;
+
+fn pool(pool_type: PoolType, symbols: &[&str]) -> Pool {
+    let tokens: Vec<Token> = symbols
+        .iter()
+        .map(|symbol| Token {
+            address: symbol.to_string(),
+            symbol: symbol.to_string(),
+            decimals: 18,
+        })
+        .collect();
+    let reserves: HashMap<String, BigUint> = tokens
+        .iter()
+        .map(|t| (t.address.clone(), BigUint::from(10u64).pow(24)))
+        .collect();
+    Pool::new("BENCH".to_string(), tokens, reserves, 30, pool_type)
+}
+
+fn measure(name: &str, pool: &Pool, input: &str, output: &str) {
+    let amount = BigUint::from(10u64).pow(21);
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..QUOTES {
+        black_box(
+            pool.calculate_swap_output(input, output, black_box(&amount))
+                .unwrap(),
+        );
+    }
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+
+    println!(
+        "{:<16} {:>8.1} allocations/quote {:>8.2} us/quote",
+        name,
+        allocations as f64 / QUOTES as f64,
+        elapsed.as_secs_f64() * 1e6 / QUOTES as f64
+    );
+}
+
+fn main() {
+    measure(
+        "constant_product",
+        &pool(PoolType::ConstantProduct, &["ETH", "USDC"]),
+        "ETH",
+        "USDC",
+    );
+    measure(
+        "stable_swap",
+        &pool(PoolType::StableSwap, &["USDC", "USDT", "DAI"]),
+        "USDC",
+        "DAI",
+    );
+}
@@ -0,0 +1,86 @@
+use crate::Pool;
+use num_bigint::BigUint;
+
+/// Largest total fee, in basis points, at which buying `token_b` with
+/// `amount` of `token_a` in `pool_a` and selling it back in `pool_b` still
+/// breaks even. The round trip is priced on both curves without fees and
+/// the fee taken as if charged once on its proceeds. `None` if the trip
+/// loses money even for free.
+pub fn breakeven_fee_bps(
+    pool_a: &Pool,
+    pool_b: &Pool,
+    token_a: &str,
+    token_b: &str,
+    amount: &BigUint,
+) -> Option<u64> {
+    let bought = fee_free(pool_a)
+        .calculate_swap_output(token_a, token_b, amount)
+        .ok()?;
+    let returned = fee_free(pool_b)
+        .calculate_swap_output(token_b, token_a, &bought)
+        .ok()?;
+    if returned <= *amount {
+        return None;
+    }
+
+    // returned * (1 - fee) = amount
+    let fee_bps = (&returned - amount) * 10000u64 / &returned;
+    u64::try_from(fee_bps).ok()
+}
+
+fn fee_free(pool: &Pool) -> Pool {
+    let mut pool = pool.clone();
+    pool.fee_rate = 0;
+    pool.directional_fee_rates = None;
+    pool.fee_strategy = None;
+    pool
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PoolType, Token};
+    use std::collections::HashMap;
+
+    fn create_pool(id: &str, eth: u64, usdc: u64) -> Pool {
+        let tokens = ["ETH", "USDC"]
+            .iter()
+            .map(|symbol| Token {
+                address: symbol.to_string(),
+                symbol: symbol.to_string(),
+                decimals: 18,
+            })
+            .collect();
+        let mut reserves = HashMap::new();
+        reserves.insert("ETH".to_string(), BigUint::from(eth));
+        reserves.insert("USDC".to_string(), BigUint::from(usdc));
+
+        Pool::new(
+            id.to_string(),
+            tokens,
+            reserves,
+            30,
+            PoolType::ConstantProduct,
+        )
+    }
+
+    #[test]
+    fn test_breakeven_fee_of_mispriced_pair() {
+        // ETH at 2000 USDC in one pool and 2200 in the other
+        let cheap = create_pool("CHEAP", 1_000_000, 2_000_000_000);
+        let dear = create_pool("DEAR", 1_000_000, 2_200_000_000);
+        let amount = BigUint::from(10_000_000u64);
+
+        // 10_000_000 USDC buys 4975 ETH, which sell for 10_890_818 USDC:
+        // 890_818 / 10_890_818 = 8.17%
+        assert_eq!(
+            breakeven_fee_bps(&cheap, &dear, "USDC", "ETH", &amount),
+            Some(817)
+        );
+        // The other way round loses money whatever the fee
+        assert_eq!(
+            breakeven_fee_bps(&dear, &cheap, "USDC", "ETH", &amount),
+            None
+        );
+    }
+}
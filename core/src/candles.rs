@@ -0,0 +1,237 @@
+use crate::{ratio_to_decimal, unix_timestamp, Pool, SwapError};
+use num_bigint::BigUint;
+use num_traits::Zero;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Reserves of a pool at a point in time, the raw input for price charts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReserveSnapshot {
+    pub timestamp: u64, // unix seconds
+    pub reserves: HashMap<String, BigUint>,
+}
+
+impl ReserveSnapshot {
+    /// Price of `base` in `quote` implied by the snapshot, in raw units.
+    pub fn spot_price(&self, base: &str, quote: &str) -> Option<Decimal> {
+        let base_reserve = self.reserves.get(base)?;
+        let quote_reserve = self.reserves.get(quote)?;
+        if base_reserve.is_zero() {
+            return None;
+        }
+        ratio_to_decimal(quote_reserve, base_reserve)
+    }
+}
+
+impl Pool {
+    /// Captures the current reserves.
+    pub fn snapshot(&self) -> ReserveSnapshot {
+        ReserveSnapshot {
+            timestamp: unix_timestamp(),
+            reserves: self.reserves.clone(),
+        }
+    }
+
+    /// Quotes a swap as if the pool still held the snapshot's reserves,
+    /// keeping its current fee and curve.
+    pub fn simulate_swap_at(
+        &self,
+        snapshot: &ReserveSnapshot,
+        input_token: &str,
+        output_token: &str,
+        input_amount: &BigUint,
+    ) -> Result<BigUint, SwapError> {
+        let mut past = self.clone();
+        past.reserves = snapshot.reserves.clone();
+        past.bump_version();
+        past.calculate_swap_output(input_token, output_token, input_amount)
+    }
+}
+
+/// OHLC spot prices over one `interval`-second bucket starting at `open_time`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Candle {
+    pub open_time: u64,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+}
+
+/// Buckets snapshots into candles of the `base`/`quote` spot price, returning
+/// up to `count` candles ending with the bucket of the latest snapshot.
+/// Buckets without snapshots carry the previous close forward.
+pub fn build_candles(
+    snapshots: &[ReserveSnapshot],
+    base: &str,
+    quote: &str,
+    interval: u64,
+    count: usize,
+) -> Vec<Candle> {
+    if interval == 0 || count == 0 {
+        return Vec::new();
+    }
+
+    let mut prices: Vec<(u64, Decimal)> = snapshots
+        .iter()
+        .filter_map(|s| s.spot_price(base, quote).map(|p| (s.timestamp, p)))
+        .collect();
+    prices.sort_by_key(|(timestamp, _)| *timestamp);
+
+    let (first, last) = match (prices.first(), prices.last()) {
+        (Some(first), Some(last)) => (first.0, last.0),
+        _ => return Vec::new(),
+    };
+    let first_bucket = first / interval * interval;
+    let last_bucket = last / interval * interval;
+    let span = (count as u64 - 1).saturating_mul(interval);
+    let start = first_bucket.max(last_bucket.saturating_sub(span));
+
+    // Prices before the first returned bucket only matter for carrying forward
+    let mut next = prices.partition_point(|(timestamp, _)| *timestamp < start);
+    let mut last_close = next.checked_sub(1).map(|i| prices[i].1);
+
+    let mut candles = Vec::with_capacity(count);
+    let mut open_time = start;
+    while open_time <= last_bucket {
+        let bucket_end = open_time.saturating_add(interval);
+        let mut candle: Option<Candle> = None;
+        while next < prices.len() && prices[next].0 < bucket_end {
+            let price = prices[next].1;
+            candle = Some(match candle {
+                None => Candle {
+                    open_time,
+                    open: price,
+                    high: price,
+                    low: price,
+                    close: price,
+                },
+                Some(c) => Candle {
+                    high: c.high.max(price),
+                    low: c.low.min(price),
+                    close: price,
+                    ..c
+                },
+            });
+            next += 1;
+        }
+
+        let candle = candle.or_else(|| {
+            last_close.map(|close| Candle {
+                open_time,
+                open: close,
+                high: close,
+                low: close,
+                close,
+            })
+        });
+        if let Some(candle) = candle {
+            last_close = Some(candle.close);
+            candles.push(candle);
+        }
+
+        open_time = bucket_end;
+    }
+
+    candles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(timestamp: u64, eth: u64, usdc: u64) -> ReserveSnapshot {
+        let mut reserves = HashMap::new();
+        reserves.insert("ETH".to_string(), BigUint::from(eth));
+        reserves.insert("USDC".to_string(), BigUint::from(usdc));
+        ReserveSnapshot {
+            timestamp,
+            reserves,
+        }
+    }
+
+    fn candle(open_time: u64, open: i64, high: i64, low: i64, close: i64) -> Candle {
+        Candle {
+            open_time,
+            open: Decimal::from(open),
+            high: Decimal::from(high),
+            low: Decimal::from(low),
+            close: Decimal::from(close),
+        }
+    }
+
+    #[test]
+    fn test_candles_bucket_prices_and_fill_gaps() {
+        let snapshots = vec![
+            snapshot(100, 100, 200), // 2
+            snapshot(110, 100, 500), // 5
+            snapshot(150, 100, 100), // 1
+            snapshot(170, 100, 300), // 3
+            snapshot(300, 100, 400), // 4, after an empty bucket
+        ];
+
+        let candles = build_candles(&snapshots, "ETH", "USDC", 120, 10);
+        assert_eq!(
+            candles,
+            vec![
+                candle(0, 2, 5, 2, 5),
+                candle(120, 1, 3, 1, 3),
+                candle(240, 4, 4, 4, 4),
+            ]
+        );
+
+        let candles = build_candles(&snapshots, "ETH", "USDC", 60, 4);
+        assert_eq!(
+            candles,
+            vec![
+                candle(120, 1, 3, 1, 3),
+                candle(180, 3, 3, 3, 3), // carried forward
+                candle(240, 3, 3, 3, 3), // carried forward
+                candle(300, 4, 4, 4, 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_simulate_swap_at_uses_snapshot_reserves() {
+        let past = snapshot(100, 1_000_000, 2_000_000);
+        let mut pool = Pool::new(
+            "ETH-USDC".to_string(),
+            ["ETH", "USDC"]
+                .iter()
+                .map(|symbol| crate::Token {
+                    address: symbol.to_string(),
+                    symbol: symbol.to_string(),
+                    decimals: 18,
+                })
+                .collect(),
+            past.reserves.clone(),
+            30,
+            crate::PoolType::ConstantProduct,
+        );
+        let input = BigUint::from(10_000u64);
+        let then = pool.calculate_swap_output("ETH", "USDC", &input).unwrap();
+
+        pool.execute_swap("ETH", "USDC", &BigUint::from(500_000u64))
+            .unwrap();
+        let now = pool.calculate_swap_output("ETH", "USDC", &input).unwrap();
+        assert!(now < then);
+
+        assert_eq!(
+            pool.simulate_swap_at(&past, "ETH", "USDC", &input).unwrap(),
+            then
+        );
+        assert_eq!(
+            pool.calculate_swap_output("ETH", "USDC", &input).unwrap(),
+            now
+        );
+    }
+
+    #[test]
+    fn test_candles_without_prices_are_empty() {
+        assert!(build_candles(&[], "ETH", "USDC", 60, 5).is_empty());
+        assert!(build_candles(&[snapshot(0, 1, 1)], "ETH", "DAI", 60, 5).is_empty());
+        assert!(build_candles(&[snapshot(0, 1, 1)], "ETH", "USDC", 0, 5).is_empty());
+    }
+}
@@ -0,0 +1,849 @@
+use crate::{ceil_div, sqrt, Pool, PoolType, SwapError, SwapFee, Q96_BITS};
+use num_bigint::{BigInt, BigUint};
+use num_traits::{One, Zero};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+
+// Range of ticks representable by Uniswap V3 style pools
+pub const MIN_TICK: i32 = -887_272;
+pub const MAX_TICK: i32 = 887_272;
+
+// Each tick moves the price by one basis point
+const TICK_BASE: f64 = 1.0001;
+
+// Fractional bits kept while raising sqrt(1.0001) to a tick
+const Q128_BITS: usize = 128;
+
+/// Liquidity provided over the half-open tick range `[tick_lower, tick_upper)`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Position {
+    pub owner: String,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    #[serde(with = "crate::serde_biguint")]
+    pub liquidity: BigUint,
+    #[serde(default, with = "crate::serde_biguint::map")]
+    pub fees_owed: HashMap<String, BigUint>, // swap fees earned while in range
+}
+
+impl Position {
+    pub fn is_active_at(&self, tick: i32) -> bool {
+        self.tick_lower <= tick && tick < self.tick_upper
+    }
+}
+
+/// Change in active liquidity when the price crosses a tick upwards.
+pub type LiquidityNet = BigInt;
+
+/// Initialized ticks of a concentrated pool. A position adds its liquidity
+/// at `tick_lower` and takes it away again at `tick_upper`, so the active
+/// liquidity at a tick is the sum of the nets at and below it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TickMap {
+    ticks: BTreeMap<i32, LiquidityNet>,
+}
+
+impl TickMap {
+    pub fn is_empty(&self) -> bool {
+        self.ticks.is_empty()
+    }
+
+    pub fn liquidity_net(&self, tick: i32) -> LiquidityNet {
+        self.ticks.get(&tick).cloned().unwrap_or_default()
+    }
+
+    pub fn add_position(&mut self, tick_lower: i32, tick_upper: i32, liquidity: &BigUint) {
+        let liquidity = BigInt::from(liquidity.clone());
+        self.update(tick_lower, &liquidity);
+        self.update(tick_upper, &-liquidity);
+    }
+
+    pub fn remove_position(&mut self, tick_lower: i32, tick_upper: i32, liquidity: &BigUint) {
+        let liquidity = BigInt::from(liquidity.clone());
+        self.update(tick_lower, &-&liquidity);
+        self.update(tick_upper, &liquidity);
+    }
+
+    /// Liquidity in range while the price sits in `tick`.
+    pub fn active_liquidity(&self, tick: i32) -> BigUint {
+        let active: BigInt = self.ticks.range(..=tick).map(|(_, net)| net).sum();
+        active.to_biguint().unwrap_or_default()
+    }
+
+    // Ticks whose nets cancel out are uninitialized again
+    fn update(&mut self, tick: i32, delta: &BigInt) {
+        let net = self.ticks.entry(tick).or_default();
+        *net += delta;
+        if net.is_zero() {
+            self.ticks.remove(&tick);
+        }
+    }
+
+    fn next_at_or_below(&self, tick: i32) -> Option<i32> {
+        self.ticks.range(..=tick).next_back().map(|(tick, _)| *tick)
+    }
+
+    fn next_above(&self, tick: i32) -> Option<i32> {
+        self.ticks
+            .range((Bound::Excluded(tick), Bound::Unbounded))
+            .next()
+            .map(|(tick, _)| *tick)
+    }
+
+    // Liquidity after the price crosses `tick`, in either direction
+    fn cross(&self, tick: i32, liquidity: &BigUint, upward: bool) -> BigUint {
+        let net = self.liquidity_net(tick);
+        let liquidity = BigInt::from(liquidity.clone());
+        let crossed = if upward {
+            liquidity + net
+        } else {
+            liquidity - net
+        };
+        crossed.to_biguint().unwrap_or_default()
+    }
+}
+
+// Positions need a nonempty range within MIN_TICK..=MAX_TICK
+fn check_tick_range(tick_lower: i32, tick_upper: i32) -> Result<(), SwapError> {
+    if tick_lower >= tick_upper || tick_lower < MIN_TICK || tick_upper > MAX_TICK {
+        return Err(SwapError::PriceOutOfRange);
+    }
+    Ok(())
+}
+
+/// Q64.96 square root of the price at `tick`, `sqrt(1.0001^tick) * 2^96`,
+/// in integer math so every node agrees on it.
+pub fn sqrt_price_at_tick(tick: i32) -> Result<BigUint, SwapError> {
+    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+        return Err(SwapError::PriceOutOfRange);
+    }
+
+    let mut base = sqrt(&((BigUint::from(10001u32) << (2 * Q128_BITS)) / 10000u32));
+    let mut ratio = BigUint::one() << Q128_BITS;
+    let mut exponent = tick.unsigned_abs();
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            ratio = (ratio * &base) >> Q128_BITS;
+        }
+        base = (&base * &base) >> Q128_BITS;
+        exponent >>= 1;
+    }
+    if tick < 0 {
+        ratio = (BigUint::one() << (2 * Q128_BITS)) / ratio;
+    }
+    Ok(ratio >> (Q128_BITS - Q96_BITS))
+}
+
+/// Tick containing the Q64.96 `sqrt_price`: the largest `t` with
+/// `sqrt_price_at_tick(t) <= sqrt_price`.
+pub fn tick_at_sqrt_price(sqrt_price: &BigUint) -> Result<i32, SwapError> {
+    if *sqrt_price < sqrt_price_at_tick(MIN_TICK)? || *sqrt_price >= sqrt_price_at_tick(MAX_TICK)? {
+        return Err(SwapError::PriceOutOfRange);
+    }
+
+    let (mut low, mut high) = (MIN_TICK, MAX_TICK);
+    while high - low > 1 {
+        let middle = low + (high - low) / 2;
+        if sqrt_price_at_tick(middle)? <= *sqrt_price {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    Ok(low)
+}
+
+/// Tick whose price range contains `price`: the largest `t` with
+/// `1.0001^t <= price`.
+pub fn price_to_tick(price: f64) -> Result<i32, SwapError> {
+    if !price.is_finite() || price <= 0.0 {
+        return Err(SwapError::PriceOutOfRange);
+    }
+
+    let tick = (price.ln() / TICK_BASE.ln()).floor();
+    if tick < MIN_TICK as f64 || tick > MAX_TICK as f64 {
+        return Err(SwapError::PriceOutOfRange);
+    }
+    Ok(tick as i32)
+}
+
+impl Pool {
+    /// Tick of the current price of the pool's first token in its second.
+    pub fn current_tick(&self) -> Result<i32, SwapError> {
+        if self.pool_type != PoolType::ConcentratedLiquidity {
+            return Err(SwapError::UnsupportedPoolType);
+        }
+        let (token0, token1) = match self.tokens.as_slice() {
+            [token0, token1, ..] => (&token0.address, &token1.address),
+            _ => return Err(SwapError::TokenNotFound),
+        };
+
+        tick_at_sqrt_price(&self.sqrt_price(token0, token1)?)
+    }
+
+    /// Total liquidity of the positions whose range covers `tick`.
+    pub fn liquidity_at_tick(&self, tick: i32) -> BigUint {
+        self.ticks.active_liquidity(tick)
+    }
+
+    /// Liquidity in range at the current price.
+    pub fn calculate_active_liquidity(&self) -> Result<BigUint, SwapError> {
+        Ok(self.liquidity_at_tick(self.current_tick()?))
+    }
+
+    /// Adds a ranged position to the pool and its tick map. Reserves are
+    /// left alone: funding the position is up to the caller.
+    pub fn add_position(&mut self, position: Position) -> Result<(), SwapError> {
+        check_tick_range(position.tick_lower, position.tick_upper)?;
+
+        self.ticks.add_position(
+            position.tick_lower,
+            position.tick_upper,
+            &position.liquidity,
+        );
+        self.positions.push(position);
+        self.bump_version();
+        Ok(())
+    }
+
+    /// Takes `owner`'s position over the given range out of the pool.
+    pub fn remove_position(
+        &mut self,
+        owner: &str,
+        tick_lower: i32,
+        tick_upper: i32,
+    ) -> Option<Position> {
+        let index = self.positions.iter().position(|position| {
+            position.owner == owner
+                && position.tick_lower == tick_lower
+                && position.tick_upper == tick_upper
+        })?;
+
+        let position = self.positions.remove(index);
+        self.ticks
+            .remove_position(tick_lower, tick_upper, &position.liquidity);
+        self.bump_version();
+        Some(position)
+    }
+
+    /// Opens a position of `liquidity` over `[tick_lower, tick_upper)` and
+    /// returns the token amounts it takes, rounded up, now in the reserves.
+    /// Below the range only token0 is needed, above it only token1.
+    pub fn mint_position(
+        &mut self,
+        owner: &str,
+        tick_lower: i32,
+        tick_upper: i32,
+        liquidity: &BigUint,
+    ) -> Result<HashMap<String, BigUint>, SwapError> {
+        if self.pool_type != PoolType::ConcentratedLiquidity {
+            return Err(SwapError::UnsupportedPoolType);
+        }
+        if liquidity.is_zero() {
+            return Err(SwapError::InsufficientLiquidity);
+        }
+        check_tick_range(tick_lower, tick_upper)?;
+        let amounts = self.position_amounts(tick_lower, tick_upper, liquidity, true)?;
+
+        // From here on reserves no longer imply the price, so pin it
+        let (token0, token1) = self.position_tokens()?;
+        self.sqrt_price_x96 = Some(self.sqrt_price(&token0, &token1)?);
+        for (token, amount) in &amounts {
+            *self.reserves.entry(token.clone()).or_default() += amount;
+        }
+        self.add_position(Position {
+            owner: owner.to_string(),
+            tick_lower,
+            tick_upper,
+            liquidity: liquidity.clone(),
+            fees_owed: HashMap::new(),
+        })?;
+        Ok(amounts)
+    }
+
+    /// Closes `owner`'s position over the range, paying out its share of
+    /// the reserves at the current price, rounded down, plus its fees.
+    pub fn burn_position(
+        &mut self,
+        owner: &str,
+        tick_lower: i32,
+        tick_upper: i32,
+    ) -> Result<HashMap<String, BigUint>, SwapError> {
+        let position = self
+            .positions
+            .iter()
+            .find(|position| {
+                position.owner == owner
+                    && position.tick_lower == tick_lower
+                    && position.tick_upper == tick_upper
+            })
+            .ok_or(SwapError::PositionNotFound)?;
+
+        let mut payouts =
+            self.position_amounts(tick_lower, tick_upper, &position.liquidity, false)?;
+        for (token, fee) in &position.fees_owed {
+            *payouts.entry(token.clone()).or_default() += fee;
+        }
+        for (token, payout) in &payouts {
+            if self
+                .reserves
+                .get(token)
+                .is_none_or(|reserve| reserve < payout)
+            {
+                return Err(SwapError::InsufficientLiquidity);
+            }
+        }
+
+        self.remove_position(owner, tick_lower, tick_upper);
+        for (token, payout) in &payouts {
+            if let Some(reserve) = self.reserves.get_mut(token) {
+                *reserve -= payout;
+            }
+        }
+        Ok(payouts)
+    }
+
+    /// Credits a swap `fee` in `token` to the positions in range at the
+    /// current price, in proportion to their liquidity. Rounding dust
+    /// stays with the pool.
+    pub fn accrue_position_fees(&mut self, token: &str, fee: &BigUint) -> Result<(), SwapError> {
+        let tick = self.current_tick()?;
+        let active = self.liquidity_at_tick(tick);
+        if active.is_zero() {
+            return Ok(());
+        }
+
+        for position in &mut self.positions {
+            if position.is_active_at(tick) {
+                *position.fees_owed.entry(token.to_string()).or_default() +=
+                    fee * &position.liquidity / &active;
+            }
+        }
+        self.bump_version();
+        Ok(())
+    }
+
+    fn position_tokens(&self) -> Result<(String, String), SwapError> {
+        match self.tokens.as_slice() {
+            [token0, token1] => Ok((token0.address.clone(), token1.address.clone())),
+            _ => Err(SwapError::UnsupportedPoolType),
+        }
+    }
+
+    // Token amounts backing `liquidity` over the range at the current price:
+    // token0 for the part of the range above it, token1 for the part below
+    fn position_amounts(
+        &self,
+        tick_lower: i32,
+        tick_upper: i32,
+        liquidity: &BigUint,
+        round_up: bool,
+    ) -> Result<HashMap<String, BigUint>, SwapError> {
+        let (token0, token1) = self.position_tokens()?;
+        let sqrt_price = self.sqrt_price(&token0, &token1)?;
+        let lower = sqrt_price_at_tick(tick_lower)?;
+        let upper = sqrt_price_at_tick(tick_upper)?;
+        let divide = |numerator: BigUint, denominator: &BigUint| {
+            if round_up {
+                ceil_div(&numerator, denominator)
+            } else {
+                numerator / denominator
+            }
+        };
+        // amount0 = L * (1/sqrt(P_low) - 1/sqrt(P_high)), amount1 = L * (sqrt(P_high) - sqrt(P_low))
+        let amount0 = |low: &BigUint, high: &BigUint| {
+            divide((liquidity * (high - low)) << Q96_BITS, &(high * low))
+        };
+        let amount1 = |low: &BigUint, high: &BigUint| {
+            divide(liquidity * (high - low), &(BigUint::one() << Q96_BITS))
+        };
+
+        let (amount0, amount1) = if sqrt_price <= lower {
+            (amount0(&lower, &upper), BigUint::zero())
+        } else if sqrt_price >= upper {
+            (BigUint::zero(), amount1(&lower, &upper))
+        } else {
+            (amount0(&sqrt_price, &upper), amount1(&lower, &sqrt_price))
+        };
+        Ok(HashMap::from([(token0, amount0), (token1, amount1)]))
+    }
+
+    // Q64.96 square root of the price of token0 in token1: the pinned
+    // `sqrt_price_x96` once positions are minted, else implied by reserves
+    fn sqrt_price(&self, base: &str, quote: &str) -> Result<BigUint, SwapError> {
+        if let Some(sqrt_price) = &self.sqrt_price_x96 {
+            return Ok(sqrt_price.clone());
+        }
+        let base_reserve = self.reserves.get(base).ok_or(SwapError::TokenNotFound)?;
+        let quote_reserve = self.reserves.get(quote).ok_or(SwapError::TokenNotFound)?;
+        if base_reserve.is_zero() || quote_reserve.is_zero() {
+            return Err(SwapError::InsufficientLiquidity);
+        }
+        Ok(sqrt(&((quote_reserve << (2 * Q96_BITS)) / base_reserve)))
+    }
+
+    // Output of selling `input_amount` of `input_token` for `output_token`,
+    // the fee it takes from the input and the Q64.96 sqrt price of token0
+    // in token1 it leaves
+    pub(crate) fn concentrated_swap(
+        &self,
+        input_token: &str,
+        output_token: &str,
+        input_amount: &BigUint,
+    ) -> Result<(BigUint, SwapFee, BigUint), SwapError> {
+        if input_token == output_token || !self.tokens.iter().any(|t| t.address == output_token) {
+            return Err(SwapError::TokenNotFound);
+        }
+        let (sqrt_price, output) = self.concentrated_swap_sqrt_price(input_token, input_amount)?;
+        let fee = SwapFee {
+            token: input_token.to_string(),
+            amount: self.swap_fee(input_token, input_amount)?,
+        };
+        Ok((output, fee, sqrt_price))
+    }
+
+    // New Q64.96 sqrt price of token0 in token1 and output of selling
+    // `input_amount` of `input_token` for the pool's other token, on the
+    // tick map when there are positions, else on the price range
+    pub(crate) fn concentrated_swap_sqrt_price(
+        &self,
+        input_token: &str,
+        input_amount: &BigUint,
+    ) -> Result<(BigUint, BigUint), SwapError> {
+        if self.pool_type != PoolType::ConcentratedLiquidity {
+            return Err(SwapError::UnsupportedPoolType);
+        }
+        let (token0, token1) = match self.tokens.as_slice() {
+            [token0, token1] => (&token0.address, &token1.address),
+            _ => return Err(SwapError::UnsupportedPoolType),
+        };
+        let output_token = if input_token == token0 {
+            token1
+        } else if input_token == token1 {
+            token0
+        } else {
+            return Err(SwapError::TokenNotFound);
+        };
+
+        if !self.ticks.is_empty() {
+            return self.tick_swap_sqrt_price(input_token, output_token, input_amount);
+        }
+        let price_range = self.price_range.unwrap_or((0.0, f64::INFINITY));
+        let (sqrt_price, output) =
+            self.range_swap_sqrt_price(input_token, output_token, input_amount, price_range)?;
+        // The range curve prices the input in the output
+        if input_token == token0 {
+            Ok((sqrt_price, output))
+        } else {
+            Ok(((BigUint::one() << (2 * Q96_BITS)) / sqrt_price, output))
+        }
+    }
+
+    pub(crate) fn tick_swap(
+        &self,
+        input_token: &str,
+        output_token: &str,
+        input_amount: &BigUint,
+    ) -> Result<BigUint, SwapError> {
+        self.tick_swap_sqrt_price(input_token, output_token, input_amount)
+            .map(|(_, output)| output)
+    }
+
+    // Swaps against the tick map, one step per range of constant liquidity:
+    // each step either fills the rest of the input or runs the price to
+    // the next initialized tick, whose net then changes the liquidity.
+    // Returns the Q64.96 sqrt price it ends at and the output.
+    fn tick_swap_sqrt_price(
+        &self,
+        input_token: &str,
+        output_token: &str,
+        input_amount: &BigUint,
+    ) -> Result<(BigUint, BigUint), SwapError> {
+        let (token0, token1) = match self.tokens.as_slice() {
+            [token0, token1] => (&token0.address, &token1.address),
+            _ => return Err(SwapError::UnsupportedPoolType),
+        };
+        // Selling token0 lowers the price of token0 in token1
+        let zero_for_one = if input_token == token0 && output_token == token1 {
+            true
+        } else if input_token == token1 && output_token == token0 {
+            false
+        } else {
+            return Err(SwapError::TokenNotFound);
+        };
+        let output_reserve = self
+            .reserves
+            .get(output_token)
+            .ok_or(SwapError::TokenNotFound)?;
+
+        let q96 = BigUint::one() << Q96_BITS;
+        let mut sqrt_price = self.sqrt_price(token0, token1)?;
+        let mut tick = tick_at_sqrt_price(&sqrt_price)?;
+        let mut liquidity = self.ticks.active_liquidity(tick);
+        let mut remaining = input_amount - self.swap_fee(input_token, input_amount)?;
+        let mut output = BigUint::zero();
+
+        while !remaining.is_zero() {
+            // Past the last initialized tick there is no liquidity left
+            let next_tick = if zero_for_one {
+                self.ticks.next_at_or_below(tick)
+            } else {
+                self.ticks.next_above(tick)
+            }
+            .ok_or(SwapError::InsufficientLiquidity)?;
+            let target = sqrt_price_at_tick(next_tick)?;
+
+            if !liquidity.is_zero() {
+                // Input that takes the price to the tick, rounded up, and
+                // what it pays out, rounded down:
+                // dx = L * (1/sqrt(P_low) - 1/sqrt(P_high)), dy = L * (sqrt(P_high) - sqrt(P_low))
+                let (low, high) = if zero_for_one {
+                    (&target, &sqrt_price)
+                } else {
+                    (&sqrt_price, &target)
+                };
+                let dy_numerator = &liquidity * (high - low);
+                let dx_numerator = &dy_numerator << Q96_BITS;
+                let dx_denominator = high * low;
+                let (step_in, step_out) = if zero_for_one {
+                    (
+                        ceil_div(&dx_numerator, &dx_denominator),
+                        dy_numerator >> Q96_BITS,
+                    )
+                } else {
+                    (ceil_div(&dy_numerator, &q96), dx_numerator / dx_denominator)
+                };
+
+                if remaining < step_in {
+                    // Rounding the new price towards the start keeps the
+                    // output rounded down, in the pool's favour
+                    let new_sqrt_price = if zero_for_one {
+                        let numerator = (&liquidity * &sqrt_price) << Q96_BITS;
+                        let denominator = (&liquidity << Q96_BITS) + &remaining * &sqrt_price;
+                        ceil_div(&numerator, &denominator)
+                    } else {
+                        &sqrt_price + (&remaining << Q96_BITS) / &liquidity
+                    };
+                    let (low, high) = if zero_for_one {
+                        (&new_sqrt_price, &sqrt_price)
+                    } else {
+                        (&sqrt_price, &new_sqrt_price)
+                    };
+                    output += if zero_for_one {
+                        (&liquidity * (high - low)) >> Q96_BITS
+                    } else {
+                        ((&liquidity * (high - low)) << Q96_BITS) / (high * low)
+                    };
+                    sqrt_price = new_sqrt_price;
+                    break;
+                }
+
+                remaining -= step_in;
+                output += step_out;
+            }
+
+            liquidity = self.ticks.cross(next_tick, &liquidity, !zero_for_one);
+            sqrt_price = target;
+            tick = if zero_for_one {
+                next_tick - 1
+            } else {
+                next_tick
+            };
+        }
+
+        if output >= *output_reserve {
+            return Err(SwapError::InsufficientLiquidity);
+        }
+        Ok((sqrt_price, output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Token;
+    use std::collections::HashMap;
+
+    fn create_concentrated_pool(eth: u64, usdc: u64) -> Pool {
+        let tokens = ["ETH", "USDC"]
+            .iter()
+            .map(|symbol| Token {
+                address: symbol.to_string(),
+                symbol: symbol.to_string(),
+                decimals: 18,
+            })
+            .collect();
+
+        let mut reserves = HashMap::new();
+        reserves.insert("ETH".to_string(), BigUint::from(eth));
+        reserves.insert("USDC".to_string(), BigUint::from(usdc));
+
+        Pool::new(
+            "ETH-USDC".to_string(),
+            tokens,
+            reserves,
+            30,
+            PoolType::ConcentratedLiquidity,
+        )
+    }
+
+    fn position(owner: &str, tick_lower: i32, tick_upper: i32, liquidity: u64) -> Position {
+        Position {
+            owner: owner.to_string(),
+            tick_lower,
+            tick_upper,
+            liquidity: BigUint::from(liquidity),
+            fees_owed: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_current_tick_follows_price() {
+        assert_eq!(
+            create_concentrated_pool(1000, 1000).current_tick().unwrap(),
+            0
+        );
+        // ln(2) / ln(1.0001) = 6931.8
+        assert_eq!(
+            create_concentrated_pool(1000, 2000).current_tick().unwrap(),
+            6931
+        );
+        assert_eq!(
+            create_concentrated_pool(2000, 1000).current_tick().unwrap(),
+            -6932
+        );
+
+        let mut pool = create_concentrated_pool(1000, 1000);
+        pool.pool_type = PoolType::ConstantProduct;
+        assert!(matches!(
+            pool.current_tick(),
+            Err(SwapError::UnsupportedPoolType)
+        ));
+    }
+
+    #[test]
+    fn test_liquidity_at_tick_sums_covering_positions() {
+        let mut pool = create_concentrated_pool(1000, 2000);
+        for position in [
+            position("alice", 0, 10_000, 500),
+            position("bob", 6_000, 7_000, 300),
+            position("carol", -5_000, 0, 200),
+        ] {
+            pool.add_position(position).unwrap();
+        }
+
+        let tick = pool.current_tick().unwrap();
+        assert_eq!(pool.liquidity_at_tick(tick), BigUint::from(800u64));
+        // Ranges are half-open: carol's ends where alice's starts
+        assert_eq!(pool.liquidity_at_tick(0), BigUint::from(500u64));
+        assert_eq!(pool.liquidity_at_tick(-1), BigUint::from(200u64));
+        assert_eq!(pool.liquidity_at_tick(10_000), BigUint::from(0u64));
+        assert_eq!(
+            pool.calculate_active_liquidity().unwrap(),
+            BigUint::from(800u64)
+        );
+
+        // Removing bob's position clears both of its ticks
+        let removed = pool.remove_position("bob", 6_000, 7_000).unwrap();
+        assert_eq!(removed.liquidity, BigUint::from(300u64));
+        assert_eq!(pool.ticks.liquidity_net(6_000), BigInt::zero());
+        assert_eq!(pool.ticks.liquidity_net(7_000), BigInt::zero());
+        assert_eq!(pool.liquidity_at_tick(tick), BigUint::from(500u64));
+        assert!(pool.remove_position("bob", 6_000, 7_000).is_none());
+
+        assert!(matches!(
+            pool.add_position(position("dave", 100, 100, 1)),
+            Err(SwapError::PriceOutOfRange)
+        ));
+    }
+
+    #[test]
+    fn test_mint_then_burn_returns_the_deposit() {
+        let unit = 10u64.pow(12);
+        let mut pool = create_concentrated_pool(1000 * unit, 2000 * unit);
+        let reserves = pool.reserves.clone();
+        let tick = pool.current_tick().unwrap();
+        let liquidity = BigUint::from(1_000 * unit);
+
+        // A range around the current price takes both tokens
+        let deposited = pool
+            .mint_position("alice", tick - 1_000, tick + 1_000, &liquidity)
+            .unwrap();
+        assert!(deposited.values().all(|amount| !amount.is_zero()));
+        for (token, amount) in &deposited {
+            assert_eq!(pool.reserves[token], &reserves[token] + amount);
+        }
+        assert_eq!(pool.calculate_active_liquidity().unwrap(), liquidity);
+        // Minting moves reserves but not the price
+        assert_eq!(pool.current_tick().unwrap(), tick);
+
+        let returned = pool
+            .burn_position("alice", tick - 1_000, tick + 1_000)
+            .unwrap();
+        for (token, amount) in &returned {
+            assert!(amount <= &deposited[token]);
+            assert!(&deposited[token] - amount <= BigUint::one());
+        }
+        assert!(pool.positions.is_empty() && pool.ticks.is_empty());
+        assert!(matches!(
+            pool.burn_position("alice", tick - 1_000, tick + 1_000),
+            Err(SwapError::PositionNotFound)
+        ));
+
+        // Entirely below the price, a range holds only token1
+        let below = pool
+            .mint_position("bob", tick - 2_000, tick - 1_000, &liquidity)
+            .unwrap();
+        assert!(below["ETH"].is_zero() && !below["USDC"].is_zero());
+        assert!(matches!(
+            pool.mint_position("bob", tick, tick, &liquidity),
+            Err(SwapError::PriceOutOfRange)
+        ));
+    }
+
+    #[test]
+    fn test_burn_pays_out_accrued_fees() {
+        let unit = 10u64.pow(12);
+        let mut pool = create_concentrated_pool(1000 * unit, 2000 * unit);
+        let tick = pool.current_tick().unwrap();
+        pool.mint_position("alice", tick - 100, tick + 100, &BigUint::from(unit))
+            .unwrap();
+        let bob = pool
+            .mint_position("bob", tick - 200, tick + 200, &BigUint::from(3 * unit))
+            .unwrap();
+        // Out of range, so it earns nothing
+        pool.mint_position("carol", tick + 500, tick + 600, &BigUint::from(unit))
+            .unwrap();
+
+        pool.accrue_position_fees("ETH", &BigUint::from(1000u64))
+            .unwrap();
+        let fees: Vec<_> = pool
+            .positions
+            .iter()
+            .map(|position| position.fees_owed.get("ETH").cloned().unwrap_or_default())
+            .collect();
+        assert_eq!(fees, [250u64, 750, 0].map(BigUint::from).to_vec());
+
+        let returned = pool.burn_position("bob", tick - 200, tick + 200).unwrap();
+        assert!(returned["ETH"] >= &bob["ETH"] + 749u64);
+        assert!(returned["ETH"] <= &bob["ETH"] + 750u64);
+    }
+
+    #[test]
+    fn test_burn_pays_out_fees_of_executed_swaps() {
+        let unit = 10u64.pow(12);
+        let mut pool = create_concentrated_pool(1000 * unit, 2000 * unit);
+        pool.fee_rate = 100;
+        let tick = pool.current_tick().unwrap();
+        let (lower, upper) = (tick - 1_000, tick + 1_000);
+        let deposit = pool
+            .mint_position("alice", lower, upper, &BigUint::from(1_000 * unit))
+            .unwrap();
+
+        // A round trip leaves the price about where it started, and a fee
+        // in each token with the position
+        let amount = BigUint::from(10 * unit);
+        let received = pool.execute_swap("ETH", "USDC", &amount).unwrap();
+        pool.execute_swap("USDC", "ETH", &received).unwrap();
+        let fees = &pool.positions[0].fees_owed;
+        assert_eq!(fees.len(), 2);
+        assert!(fees.values().all(|fee| !fee.is_zero()));
+
+        // Valued in USDC at the final price, the payout beats the deposit
+        let price = pool.sqrt_price_x96.clone().unwrap().pow(2);
+        let value = |amounts: &HashMap<String, BigUint>| {
+            ((&amounts["ETH"] * &price) >> (2 * Q96_BITS)) + &amounts["USDC"]
+        };
+        let payout = pool.burn_position("alice", lower, upper).unwrap();
+        assert!(value(&payout) > value(&deposit));
+    }
+
+    #[test]
+    fn test_sqrt_price_at_tick() {
+        let q96 = BigUint::one() << Q96_BITS;
+        assert_eq!(sqrt_price_at_tick(0).unwrap(), q96);
+        // 1.0001^(6931 / 2) is just below sqrt(2)
+        let sqrt_two = sqrt(&(BigUint::from(2u32) << (2 * Q96_BITS)));
+        assert!(sqrt_price_at_tick(6931).unwrap() <= sqrt_two);
+        assert!(sqrt_price_at_tick(6932).unwrap() > sqrt_two);
+        assert_eq!(tick_at_sqrt_price(&sqrt_two).unwrap(), 6931);
+
+        for tick in [MIN_TICK, -1, 1, 60, MAX_TICK - 1] {
+            let sqrt_price = sqrt_price_at_tick(tick).unwrap();
+            assert_eq!(tick_at_sqrt_price(&sqrt_price).unwrap(), tick);
+        }
+        assert!(sqrt_price_at_tick(MAX_TICK + 1).is_err());
+    }
+
+    #[test]
+    fn test_swap_crosses_tick_boundaries() {
+        let unit = 10u64.pow(12);
+        // Price 1, so the current tick is 0
+        let create = |positions: &[(i32, i32)]| {
+            let mut pool = create_concentrated_pool(1_000_000 * unit, 1_000_000 * unit);
+            for &(tick_lower, tick_upper) in positions {
+                pool.add_position(position("lp", tick_lower, tick_upper, 10_000 * unit))
+                    .unwrap();
+            }
+            pool
+        };
+        let quote = |pool: &Pool, input: &str, output: &str, amount: u64| {
+            pool.calculate_multi_asset_swap(input, output, &BigUint::from(amount))
+        };
+
+        let stacked = create(&[(-600, 600), (-60, 60)]);
+        let wide = create(&[(-600, 600), (-600, 600)]);
+        let base = create(&[(-600, 600)]);
+        assert_eq!(
+            stacked.calculate_active_liquidity().unwrap(),
+            BigUint::from(20_000 * unit)
+        );
+
+        // Inside [-60, 60) both positions trade, as if they were one
+        for (input, output) in [("ETH", "USDC"), ("USDC", "ETH")] {
+            let small = quote(&stacked, input, output, unit).unwrap();
+            assert_eq!(small, quote(&wide, input, output, unit).unwrap());
+        }
+
+        // Past tick -60 (or 60) only the wide position is left, so the rest
+        // of the trade fills at the shallower liquidity
+        for (input, output) in [("ETH", "USDC"), ("USDC", "ETH")] {
+            let amount = 100 * unit;
+            let crossing = quote(&stacked, input, output, amount).unwrap();
+            assert!(crossing < quote(&wide, input, output, amount).unwrap());
+            assert!(crossing > quote(&base, input, output, amount).unwrap());
+        }
+
+        // Beyond tick -600 nothing is left to trade against
+        assert!(matches!(
+            quote(&stacked, "ETH", "USDC", 1_000 * unit),
+            Err(SwapError::InsufficientLiquidity)
+        ));
+    }
+
+    #[test]
+    fn test_executed_swap_crosses_ticks_and_moves_the_price() {
+        let unit = 10u64.pow(12);
+        let mut pool = create_concentrated_pool(1_000_000 * unit, 1_000_000 * unit);
+        for (tick_lower, tick_upper) in [(-600, 600), (-60, 60)] {
+            pool.add_position(position("lp", tick_lower, tick_upper, 10_000 * unit))
+                .unwrap();
+        }
+        let amount = BigUint::from(100 * unit);
+        let quoted = pool.calculate_swap_output("ETH", "USDC", &amount).unwrap();
+
+        let output = pool.execute_swap("ETH", "USDC", &amount).unwrap();
+        assert_eq!(output, quoted);
+
+        // The price ran past tick -60, leaving only the wide position in range
+        let tick = pool.current_tick().unwrap();
+        assert!((-600..-60).contains(&tick));
+        assert_eq!(
+            pool.calculate_active_liquidity().unwrap(),
+            BigUint::from(10_000 * unit)
+        );
+        // So the same trade again fills at the shallower liquidity only
+        assert!(pool.calculate_swap_output("ETH", "USDC", &amount).unwrap() < output);
+    }
+}
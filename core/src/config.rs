@@ -0,0 +1,212 @@
+//! Pool definitions as operators write them, and loading a set of them
+//! from a file.
+
+use crate::{normalize_address, Pool, PoolType, Token};
+use num_bigint::BigUint;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Cannot read pool file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid pool file: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("Pool {id}: {reason}")]
+    InvalidPool { id: String, reason: String },
+}
+
+// Fee tiers (in basis points) a pool may be configured with
+const FEE_TIERS: [u64; 6] = [1, 4, 5, 30, 100, 300];
+
+/// A pool as described in the startup config. Reserves are decimal strings
+/// keyed by token address.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoolConfig {
+    pub id: String,
+    pub tokens: Vec<Token>,
+    pub reserves: HashMap<String, String>,
+    #[serde(default)]
+    pub fee_rate: Option<u64>, // bps; the pool type's default when unset
+    pub pool_type: String,
+    #[serde(default)]
+    pub amplification: Option<u64>, // StableSwap A, the core default when unset
+    #[serde(default)]
+    pub tags: Vec<String>, // see `Pool::with_tags`
+}
+
+/// Validates `config` and builds its pool: a known pool type, a fee on
+/// one of the tiers, at least two distinct tokens (of equal decimals for
+/// StableSwap) and exactly one nonzero reserve for each.
+pub fn build_pool(config: PoolConfig) -> Result<Pool, String> {
+    let pool_type = config
+        .pool_type
+        .parse::<PoolType>()
+        .map_err(|e| e.to_string())?;
+
+    let fee_rate = config
+        .fee_rate
+        .unwrap_or_else(|| pool_type.default_fee_bps());
+    if !FEE_TIERS.contains(&fee_rate) {
+        return Err(format!(
+            "fee rate {} is not one of the tiers {:?}",
+            fee_rate, FEE_TIERS
+        ));
+    }
+
+    if config.tokens.len() < 2 {
+        return Err("a pool needs at least two tokens".to_string());
+    }
+    let mut addresses = HashSet::new();
+    for token in &config.tokens {
+        if !addresses.insert(normalize_address(&token.address)) {
+            return Err(format!("token {} is listed twice", token.address));
+        }
+    }
+    // The StableSwap invariant runs on raw balances, which only compare
+    // like for like when every token has the same precision
+    let decimals = config.tokens[0].decimals;
+    if pool_type == PoolType::StableSwap && config.tokens.iter().any(|t| t.decimals != decimals) {
+        return Err("stable_swap tokens must all have the same decimals".to_string());
+    }
+
+    // Exactly one nonzero reserve per token
+    if config.reserves.len() != config.tokens.len() {
+        return Err("reserves must list each token exactly once".to_string());
+    }
+    let mut reserves = HashMap::new();
+    for token in &config.tokens {
+        let amount = config
+            .reserves
+            .get(&token.address)
+            .ok_or_else(|| format!("no reserve for token {}", token.address))?;
+        let amount = amount
+            .parse::<BigUint>()
+            .map_err(|_| format!("invalid reserve for {}: {}", token.address, amount))?;
+        if amount == BigUint::from(0u64) {
+            return Err(format!("reserve for {} is zero", token.address));
+        }
+        reserves.insert(token.address.clone(), amount);
+    }
+
+    let pool = Pool::try_new(config.id, config.tokens, reserves, fee_rate, pool_type)
+        .and_then(|pool| pool.with_tags(config.tags))
+        .map_err(|e| e.to_string())?;
+    match config.amplification {
+        Some(amplification) => pool
+            .with_amplification(amplification)
+            .map_err(|e| e.to_string()),
+        None => Ok(pool),
+    }
+}
+
+/// Reads a JSON array of `PoolConfig`s from `path`. The pools in it are
+/// not validated yet; see `build_pool`.
+pub fn read_pool_configs(path: &Path) -> Result<Vec<PoolConfig>, ConfigError> {
+    Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+}
+
+/// Reads a JSON array of `PoolConfig`s from `path` and builds every pool.
+/// All or nothing: one invalid or repeated pool fails the whole file.
+pub fn load_pools_from_file(path: &Path) -> Result<Vec<Pool>, ConfigError> {
+    let configs = read_pool_configs(path)?;
+
+    let mut seen_ids = HashSet::new();
+    let mut pools = Vec::with_capacity(configs.len());
+    for config in configs {
+        let id = config.id.clone();
+        if !seen_ids.insert(id.clone()) {
+            return Err(ConfigError::InvalidPool {
+                id,
+                reason: "duplicate pool id".to_string(),
+            });
+        }
+        let pool = build_pool(config).map_err(|reason| ConfigError::InvalidPool { id, reason })?;
+        pools.push(pool);
+    }
+    Ok(pools)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join(name)
+    }
+
+    #[test]
+    fn test_load_pools_from_fixture() {
+        let pools = load_pools_from_file(&fixture("pools.json")).unwrap();
+
+        let ids: Vec<&str> = pools.iter().map(|pool| pool.id.as_str()).collect();
+        assert_eq!(ids, vec!["ETH-USDC", "USDC-USDT", "WETH-WBTC"]);
+        let eth_usdc = &pools[0];
+        assert_eq!(eth_usdc.fee_rate, 30);
+        assert_eq!(
+            eth_usdc.reserves["0xA0b86a33E6441B8C5c4EA1E18AA41bE2d5E27ad2"],
+            BigUint::from(2_000_000_000_000u64)
+        );
+        assert_eq!(eth_usdc.tags, vec!["blue-chip"]);
+        // Unset fees fall back to the pool type's default
+        assert_eq!(pools[1].pool_type, PoolType::StableSwap);
+        assert_eq!(pools[1].fee_rate, 4);
+        assert_eq!(pools[1].amplification, 200);
+        assert_eq!(
+            pools[2].pool_type,
+            PoolType::Weighted {
+                weights: vec![80, 20]
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_pool_files_are_refused() {
+        let dir = std::env::temp_dir().join(format!("dex-pools-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let load = |name: &str, contents: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, contents).unwrap();
+            load_pools_from_file(&path)
+        };
+        let valid = std::fs::read_to_string(fixture("pools.json")).unwrap();
+
+        let bad_fee = valid.replacen("\"fee_rate\": 30", "\"fee_rate\": 250", 1);
+        assert!(matches!(
+            load("bad_fee.json", &bad_fee),
+            Err(ConfigError::InvalidPool { id, reason }) if id == "ETH-USDC" && reason.contains("250")
+        ));
+        let missing_reserve = valid.replacen("\"2000000000000\"", "\"0\"", 1);
+        assert!(matches!(
+            load("zero_reserve.json", &missing_reserve),
+            Err(ConfigError::InvalidPool { reason, .. }) if reason.contains("zero")
+        ));
+        let duplicated = valid.replacen("\"USDC-USDT\"", "\"ETH-USDC\"", 1);
+        assert!(matches!(
+            load("duplicate.json", &duplicated),
+            Err(ConfigError::InvalidPool { reason, .. }) if reason == "duplicate pool id"
+        ));
+        let mixed_decimals = valid.replacen(
+            "\"symbol\": \"USDT\", \"decimals\": 6",
+            "\"symbol\": \"USDT\", \"decimals\": 18",
+            1,
+        );
+        assert!(matches!(
+            load("mixed_decimals.json", &mixed_decimals),
+            Err(ConfigError::InvalidPool { id, reason }) if id == "USDC-USDT" && reason.contains("decimals")
+        ));
+        assert!(matches!(
+            load("truncated.json", &valid[..40]),
+            Err(ConfigError::Parse(_))
+        ));
+        assert!(matches!(
+            load_pools_from_file(&dir.join("missing.json")),
+            Err(ConfigError::Io(_))
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,159 @@
+use crate::SwapError;
+use num_bigint::BigUint;
+use std::fmt;
+
+const BPS: u64 = 10_000;
+
+// Ceiling of `DynamicFee`, 10%
+const MAX_DYNAMIC_FEE_BPS: u64 = 1000;
+
+// 24h volume above which `DynamicFee` charges its volume surcharge
+const HIGH_VOLUME: u64 = 1_000_000;
+const HIGH_VOLUME_SURCHARGE_BPS: u64 = 50;
+
+/// Which way a swap goes through a pool: `Forward` sells the pool's first
+/// token, `Backward` any other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+/// How a swap's input splits between the LP fee and the part that trades.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeeResult {
+    pub fee: BigUint,
+    pub amount_after_fee: BigUint,
+}
+
+/// Fee model of a pool. Fees are charged in basis points of the input; the
+/// default `apply` rounds the traded amount down, so the fee rounds up, and
+/// refuses a fee of 100% or more rather than swallowing the whole input.
+pub trait FeeStrategy: fmt::Debug + Send + Sync {
+    fn fee_bps(&self, direction: Direction) -> u64;
+
+    fn apply(&self, input: &BigUint, direction: Direction) -> Result<FeeResult, SwapError> {
+        let fee_bps = self.fee_bps(direction);
+        if fee_bps >= BPS {
+            return Err(SwapError::InvalidFeeRate(fee_bps));
+        }
+        let amount_after_fee = input * (BPS - fee_bps) / BPS;
+        Ok(FeeResult {
+            fee: input - &amount_after_fee,
+            amount_after_fee,
+        })
+    }
+}
+
+/// The same fee in both directions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlatFee {
+    pub fee_bps: u64,
+}
+
+impl FlatFee {
+    pub fn new(fee_bps: u64) -> Self {
+        FlatFee { fee_bps }
+    }
+}
+
+impl FeeStrategy for FlatFee {
+    fn fee_bps(&self, _direction: Direction) -> u64 {
+        self.fee_bps
+    }
+}
+
+/// A separate fee for each direction, e.g. to discourage selling one side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AsymmetricFee {
+    pub forward_bps: u64,
+    pub backward_bps: u64,
+}
+
+impl FeeStrategy for AsymmetricFee {
+    fn fee_bps(&self, direction: Direction) -> u64 {
+        match direction {
+            Direction::Forward => self.forward_bps,
+            Direction::Backward => self.backward_bps,
+        }
+    }
+}
+
+/// A base fee raised by recent volume and volatility, capped at 10%.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DynamicFee {
+    pub base_bps: u64,
+    pub volume_24h: BigUint,
+    pub volatility: f64, // e.g. 0.5 adds 50 bps
+}
+
+impl DynamicFee {
+    pub fn new(base_bps: u64, volume_24h: BigUint, volatility: f64) -> Self {
+        DynamicFee {
+            base_bps,
+            volume_24h,
+            volatility,
+        }
+    }
+}
+
+impl FeeStrategy for DynamicFee {
+    fn fee_bps(&self, _direction: Direction) -> u64 {
+        let volume_factor = if self.volume_24h > BigUint::from(HIGH_VOLUME) {
+            HIGH_VOLUME_SURCHARGE_BPS
+        } else {
+            0
+        };
+        let volatility_factor = (self.volatility * 100.0) as u64;
+
+        self.base_bps
+            .saturating_add(volume_factor)
+            .saturating_add(volatility_factor)
+            .min(MAX_DYNAMIC_FEE_BPS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flat_fee_rounds_the_fee_up() {
+        let fee = FlatFee::new(30)
+            .apply(&BigUint::from(1001u64), Direction::Backward)
+            .unwrap();
+        // 1001 * 0.997 = 997.997
+        assert_eq!(fee.amount_after_fee, BigUint::from(997u64));
+        assert_eq!(fee.fee, BigUint::from(4u64));
+    }
+
+    #[test]
+    fn test_asymmetric_fee_depends_on_direction() {
+        let strategy = AsymmetricFee {
+            forward_bps: 100,
+            backward_bps: 5,
+        };
+        let input = BigUint::from(10_000u64);
+
+        assert_eq!(
+            strategy.apply(&input, Direction::Forward).unwrap().fee,
+            BigUint::from(100u64)
+        );
+        assert_eq!(
+            strategy.apply(&input, Direction::Backward).unwrap().fee,
+            BigUint::from(5u64)
+        );
+    }
+
+    #[test]
+    fn test_dynamic_fee_rises_with_volume_and_volatility() {
+        let quiet = DynamicFee::new(30, BigUint::from(1000u64), 0.0);
+        assert_eq!(quiet.fee_bps(Direction::Forward), 30);
+
+        let busy = DynamicFee::new(30, BigUint::from(2_000_000u64), 0.25);
+        assert_eq!(busy.fee_bps(Direction::Forward), 30 + 50 + 25);
+
+        let frantic = DynamicFee::new(300, BigUint::from(2_000_000u64), 50.0);
+        assert_eq!(frantic.fee_bps(Direction::Backward), MAX_DYNAMIC_FEE_BPS);
+    }
+}
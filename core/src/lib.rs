@@ -1,23 +1,184 @@
+pub mod arbitrage;
+pub mod candles;
+pub mod concentrated;
+pub mod config;
+pub mod fees;
+pub mod order_book;
+pub mod rewards;
+pub mod routing;
+pub mod serde_biguint;
+pub mod simulation;
+#[cfg(feature = "u256")]
+pub mod u256;
+pub mod valuation;
+
+use fees::{Direction, FeeResult, FeeStrategy, FlatFee};
 use num_bigint::BigUint;
-use num_traits::{One, Zero};
+use num_traits::{One, ToPrimitive, Zero};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Pool {
     pub id: String,
     pub tokens: Vec<Token>,
+    #[serde(with = "serde_biguint::map")]
     pub reserves: HashMap<String, BigUint>,
+    #[serde(with = "serde_biguint")]
     pub total_supply: BigUint,
     pub fee_rate: u64, // basis points (100 = 1%)
     pub pool_type: PoolType,
+    #[serde(default, with = "serde_biguint::map")]
+    pub cumulative_fees: HashMap<String, BigUint>, // LP fee revenue per input token
+    #[serde(default)]
+    pub fees_since: u64, // unix seconds when cumulative_fees started accruing
+    #[serde(default, with = "serde_biguint::map")]
+    pub lp_positions: HashMap<String, BigUint>, // LP tokens held per owner
+    #[serde(default)]
+    pub price_range: Option<(f64, f64)>, // active range of concentrated liquidity
+    #[serde(default, with = "serde_biguint::option_map")]
+    pub min_reserves: Option<HashMap<String, BigUint>>, // opt-in floors per token
+    #[serde(default)]
+    pub directional_fee_rates: Option<HashMap<String, u64>>, // fee_rate overrides by input token
+    #[serde(default)]
+    pub positions: Vec<concentrated::Position>, // ranged liquidity of concentrated pools
+    #[serde(default)]
+    pub ticks: concentrated::TickMap, // liquidity nets of `positions` by tick
+    #[serde(default, with = "serde_biguint::option")]
+    pub sqrt_price_x96: Option<BigUint>, // Q64.96 price pinned once positions are minted
+    #[serde(default, with = "serde_biguint::map")]
+    pub max_reserves: HashMap<String, BigUint>, // highest each reserve has been, see `utilization`
+    #[serde(default)]
+    pub tags: Vec<String>, // operator labels such as "stablecoins", see `with_tags`
+    #[serde(default, with = "serde_biguint")]
+    pub last_price_cumulative: BigUint, // sum of Q112 spot price times seconds, see `accumulate_price`
+    #[serde(default)]
+    pub last_update_timestamp: u64, // unix seconds of the last accumulation, 0 before the first
+    #[serde(default)]
+    pub version: u64, // bumped by every state change, for optimistic concurrency
+    #[serde(default = "default_amplification")]
+    pub amplification: u64, // StableSwap A, see `with_amplification`
+    #[serde(skip)]
+    pub fee_strategy: Option<Arc<dyn FeeStrategy>>, // replaces `fee_rate_for` when set
+    #[serde(skip)]
+    normalized_cache: OnceLock<HashMap<String, BigUint>>, // cleared by `bump_version`
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PoolType {
-    ConstantProduct,       // x * y = k
-    StableSwap,            // For stablecoins
-    ConcentratedLiquidity, // Uniswap V3 style
+    ConstantProduct,                // x * y = k
+    StableSwap,                     // For stablecoins
+    ConcentratedLiquidity,          // Uniswap V3 style
+    Weighted { weights: Vec<u64> }, // Balancer style, out of WEIGHT_DENOMINATOR per token
+}
+
+/// Weights of a `PoolType::Weighted` pool sum to this, so 80/20 is `[80, 20]`.
+pub const WEIGHT_DENOMINATOR: u64 = 100;
+
+/// Longest pool tag accepted by `Pool::with_tags`.
+pub const MAX_TAG_LEN: usize = 32;
+
+impl PoolType {
+    /// Fee in basis points for pools configured without one: stable pairs
+    /// barely move, so they get a small fraction of the volatile default.
+    pub fn default_fee_bps(&self) -> u64 {
+        match self {
+            PoolType::ConstantProduct
+            | PoolType::ConcentratedLiquidity
+            | PoolType::Weighted { .. } => 30,
+            PoolType::StableSwap => 4,
+        }
+    }
+}
+
+// Weighted pools need one positive weight per token, summing to WEIGHT_DENOMINATOR
+fn valid_weights(weights: &[u64], token_count: usize) -> bool {
+    weights.len() == token_count
+        && weights.iter().all(|&weight| weight > 0)
+        && weights.iter().sum::<u64>() == WEIGHT_DENOMINATOR
+}
+
+impl fmt::Display for PoolType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PoolType::ConstantProduct => "constant_product",
+            PoolType::StableSwap => "stable_swap",
+            PoolType::ConcentratedLiquidity => "concentrated_liquidity",
+            PoolType::Weighted { weights } => {
+                let weights: Vec<String> = weights.iter().map(u64::to_string).collect();
+                return write!(f, "weighted:{}", weights.join(","));
+            }
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for PoolType {
+    type Err = ParsePoolTypeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "constant_product" => Ok(PoolType::ConstantProduct),
+            "stable_swap" => Ok(PoolType::StableSwap),
+            "concentrated_liquidity" => Ok(PoolType::ConcentratedLiquidity),
+            // `weighted:80,20`, weights in token order
+            other => other
+                .strip_prefix("weighted:")
+                .and_then(|weights| weights.split(',').map(|w| w.trim().parse().ok()).collect())
+                .map(|weights| PoolType::Weighted { weights })
+                .ok_or_else(|| ParsePoolTypeError(other.to_string())),
+        }
+    }
+}
+
+/// Record of an executed swap, for reconciling client and pool state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SwapReceipt {
+    pub id: Uuid,
+    pub pool_id: String,
+    pub input_token: String,
+    pub output_token: String,
+    pub input_amount: BigUint,
+    pub output_amount: BigUint,
+    pub fee: BigUint,
+    pub fee_token: String, // the token `fee` was taken in
+    pub timestamp: u64,    // unix seconds
+    pub resulting_reserves: HashMap<String, BigUint>,
+}
+
+/// The fee a swap pays, in the token the curve takes it from: the input
+/// for most curves, the output for StableSwap.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SwapFee {
+    pub token: String,
+    #[serde(with = "serde_biguint")]
+    pub amount: BigUint,
+}
+
+/// Outcome of `Pool::reconcile`: how far `total_supply` has drifted from
+/// the supply implied by the current reserves.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReconcileReport {
+    pub total_supply: BigUint,
+    pub implied_supply: BigUint,
+    pub surplus: BigUint, // reserves backing no LP tokens: fees, donations, rounding
+    pub deficit: BigUint, // LP tokens not backed by reserves
+    pub dust_absorbed: BigUint,
+}
+
+impl ReconcileReport {
+    /// True when no LP tokens are left unbacked after reconciling.
+    pub fn is_consistent(&self) -> bool {
+        self.deficit <= self.dust_absorbed
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,27 +196,164 @@ impl Pool {
         fee_rate: u64,
         pool_type: PoolType,
     ) -> Self {
-        let total_supply = match pool_type {
-            PoolType::ConstantProduct => {
-                // Calculate initial LP tokens using geometric mean
-                let mut product = BigUint::one();
-                for reserve in initial_reserves.values() {
-                    product *= reserve;
-                }
-                // Simplified: use square root for 2-token pools
-                sqrt(&product)
-            }
-            _ => BigUint::zero(), // Implement for other pool types
-        };
-
-        Pool {
+        let mut pool = Pool {
             id,
             tokens,
-            reserves: initial_reserves,
-            total_supply,
+            reserves: initial_reserves.clone(),
+            total_supply: BigUint::zero(),
             fee_rate,
             pool_type,
+            cumulative_fees: HashMap::new(),
+            fees_since: unix_timestamp(),
+            lp_positions: HashMap::new(),
+            price_range: None,
+            min_reserves: None,
+            directional_fee_rates: None,
+            positions: Vec::new(),
+            ticks: concentrated::TickMap::default(),
+            sqrt_price_x96: None,
+            max_reserves: initial_reserves,
+            tags: Vec::new(),
+            last_price_cumulative: BigUint::zero(),
+            last_update_timestamp: 0,
+            version: 0,
+            amplification: STABLE_AMPLIFICATION,
+            fee_strategy: None,
+            normalized_cache: OnceLock::new(),
+        };
+        pool.total_supply = pool.supply_implied_by(&pool.pool_type);
+        pool
+    }
+
+    /// `new` for untrusted configuration: refuses a `fee_rate` of 100% or
+    /// more, which no swap could pay, and reserves that are not keyed by
+    /// exactly the tokens' addresses (the swap math would treat a missing
+    /// reserve as empty).
+    pub fn try_new(
+        id: String,
+        tokens: Vec<Token>,
+        initial_reserves: HashMap<String, BigUint>,
+        fee_rate: u64,
+        pool_type: PoolType,
+    ) -> Result<Self, PoolError> {
+        if fee_rate >= 10000 {
+            return Err(PoolError::InvalidFeeRate(fee_rate));
+        }
+        if let PoolType::Weighted { weights } = &pool_type {
+            if !valid_weights(weights, tokens.len()) {
+                return Err(PoolError::InvalidWeights(weights.clone()));
+            }
         }
+
+        let mut missing: Vec<String> = tokens
+            .iter()
+            .filter(|token| !initial_reserves.contains_key(&token.address))
+            .map(|token| token.address.clone())
+            .collect();
+        let mut extra: Vec<String> = initial_reserves
+            .keys()
+            .filter(|key| !tokens.iter().any(|token| &token.address == *key))
+            .cloned()
+            .collect();
+        if !missing.is_empty() || !extra.is_empty() {
+            missing.sort();
+            extra.sort();
+            return Err(PoolError::ReserveTokenMismatch { missing, extra });
+        }
+        Ok(Pool::new(id, tokens, initial_reserves, fee_rate, pool_type))
+    }
+
+    /// Sets the StableSwap amplification coefficient A, which must lie in
+    /// `MIN_AMPLIFICATION..=MAX_AMPLIFICATION`. Higher A keeps prices near
+    /// 1:1 over a wider range of balances; A = 0 would reduce the curve to
+    /// constant product and past the maximum `calculate_d` stops converging
+    /// in reasonable time. Meant for construction: a stable pool's supply is
+    /// re-derived as D under the new A.
+    pub fn with_amplification(mut self, amplification: u64) -> Result<Self, PoolError> {
+        if !(MIN_AMPLIFICATION..=MAX_AMPLIFICATION).contains(&amplification) {
+            return Err(PoolError::InvalidAmplification(amplification));
+        }
+        self.amplification = amplification;
+        if self.pool_type == PoolType::StableSwap {
+            self.total_supply = self.supply_implied_by(&self.pool_type);
+        }
+        Ok(self)
+    }
+
+    /// Labels the pool for filtering. Each tag is 1 to `MAX_TAG_LEN`
+    /// characters of lowercase ASCII letters, digits and `-`; repeats are
+    /// dropped.
+    pub fn with_tags(mut self, tags: Vec<String>) -> Result<Self, PoolError> {
+        let mut unique = Vec::with_capacity(tags.len());
+        for tag in tags {
+            let valid = (1..=MAX_TAG_LEN).contains(&tag.len())
+                && tag
+                    .bytes()
+                    .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-');
+            if !valid {
+                return Err(PoolError::InvalidTag(tag));
+            }
+            if !unique.contains(&tag) {
+                unique.push(tag);
+            }
+        }
+        self.tags = unique;
+        Ok(self)
+    }
+
+    /// Charges swaps through `strategy` instead of `fee_rate` and
+    /// `directional_fee_rates`.
+    pub fn with_fee_strategy(mut self, strategy: impl FeeStrategy + 'static) -> Self {
+        self.fee_strategy = Some(Arc::new(strategy));
+        self
+    }
+
+    /// Looks up one of the pool's tokens by address, ignoring case.
+    pub fn find_token(&self, token: &str) -> Option<&Token> {
+        let token = normalize_address(token);
+        self.tokens
+            .iter()
+            .find(|t| normalize_address(&t.address) == token)
+    }
+
+    /// True if `token` is one of the pool's tokens, ignoring address case.
+    pub fn supports_token(&self, token: &str) -> bool {
+        self.find_token(token).is_some()
+    }
+
+    /// True if both tokens trade in this pool.
+    pub fn supports_pair(&self, token_a: &str, token_b: &str) -> bool {
+        self.supports_token(token_a) && self.supports_token(token_b)
+    }
+
+    /// One-line summary for logs, with reserves in whole tokens:
+    /// `ETH-USDC [ConstantProduct, fee=30bps, reserves: ETH=1, USDC=2000, supply=1414]`.
+    pub fn describe(&self) -> String {
+        let reserves: Vec<String> = self
+            .tokens
+            .iter()
+            .map(|token| {
+                let reserve = self
+                    .reserves
+                    .get(&token.address)
+                    .cloned()
+                    .unwrap_or_default();
+                format!(
+                    "{}={}",
+                    token.symbol,
+                    format_units(&reserve, token.decimals)
+                )
+            })
+            .collect();
+
+        format!(
+            "{} [{:?}, fee={}bps, reserves: {}, supply={}]",
+            self.id,
+            self.pool_type,
+            self.fee_rate,
+            reserves.join(", "),
+            self.total_supply
+        )
     }
 
     pub fn calculate_swap_output(
@@ -64,20 +362,384 @@ impl Pool {
         output_token: &str,
         input_amount: &BigUint,
     ) -> Result<BigUint, SwapError> {
+        self.quote_with_fee(input_token, output_token, input_amount)
+            .map(|(output_amount, _)| output_amount)
+    }
+
+    /// Same as `calculate_swap_output`, also returning the fee exactly as
+    /// the curve takes it, which `execute_swap` records in `cumulative_fees`.
+    pub fn quote_with_fee(
+        &self,
+        input_token: &str,
+        output_token: &str,
+        input_amount: &BigUint,
+    ) -> Result<(BigUint, SwapFee), SwapError> {
         match self.pool_type {
             PoolType::ConstantProduct => {
                 self.constant_product_swap(input_token, output_token, input_amount)
             }
-            _ => Err(SwapError::UnsupportedPoolType),
+            PoolType::StableSwap => self.stable_swap(input_token, output_token, input_amount),
+            PoolType::Weighted { .. } => {
+                self.weighted_swap(input_token, output_token, input_amount)
+            }
+            PoolType::ConcentratedLiquidity => self
+                .concentrated_swap(input_token, output_token, input_amount)
+                .map(|(output_amount, fee, _)| (output_amount, fee)),
         }
     }
 
-    fn constant_product_swap(
+    /// The swap output as if the pool charged no fee, so the difference to
+    /// `calculate_swap_output` is what the fee costs in output terms.
+    pub fn gross_output(
+        &self,
+        input_token: &str,
+        output_token: &str,
+        input_amount: &BigUint,
+    ) -> Result<BigUint, SwapError> {
+        if self.pool_type == PoolType::ConstantProduct {
+            let input_reserve = self
+                .reserves
+                .get(input_token)
+                .ok_or(SwapError::TokenNotFound)?;
+            let output_reserve = self
+                .reserves
+                .get(output_token)
+                .ok_or(SwapError::TokenNotFound)?;
+            return Self::constant_product_curve(
+                input_reserve,
+                output_reserve,
+                input_amount.clone(),
+            );
+        }
+
+        // The other curves take their fee part way through, so they are
+        // quoted on a copy with every fee source cleared
+        let mut fee_free = self.clone();
+        fee_free.fee_rate = 0;
+        fee_free.directional_fee_rates = None;
+        fee_free.fee_strategy = None;
+        fee_free.calculate_swap_output(input_token, output_token, input_amount)
+    }
+
+    /// Executes a swap, moving the input into and the output out of the
+    /// reserves. The fee stays in the pool and is tracked in `cumulative_fees`.
+    pub fn execute_swap(
+        &mut self,
+        input_token: &str,
+        output_token: &str,
+        input_amount: &BigUint,
+    ) -> Result<BigUint, SwapError> {
+        self.apply_swap(input_token, output_token, input_amount)
+            .map(|(output_amount, _)| output_amount)
+    }
+
+    /// Same as `execute_swap`, but refuses to trade for less than
+    /// `min_output`, leaving the reserves untouched. See
+    /// `min_output_for_slippage` for deriving it from a tolerance.
+    pub fn swap_with_min_output(
+        &mut self,
+        input_token: &str,
+        output_token: &str,
+        input_amount: &BigUint,
+        min_output: &BigUint,
+    ) -> Result<BigUint, SwapError> {
+        let output_amount = self.calculate_swap_output(input_token, output_token, input_amount)?;
+        if output_amount < *min_output {
+            return Err(SwapError::SlippageExceeded {
+                expected: min_output.clone(),
+                actual: output_amount,
+            });
+        }
+        self.execute_swap(input_token, output_token, input_amount)
+    }
+
+    /// Same as `execute_swap`, also returning the resulting spot price of
+    /// `input_token` in `output_token` so callers need no second query.
+    pub fn execute_swap_detailed(
+        &mut self,
+        input_token: &str,
+        output_token: &str,
+        input_amount: &BigUint,
+    ) -> Result<(BigUint, Decimal), SwapError> {
+        let (output_amount, _) = self.apply_swap(input_token, output_token, input_amount)?;
+        let price = self.spot_price(input_token, output_token)?;
+        Ok((output_amount, price))
+    }
+
+    /// Same as `execute_swap`, returning a full receipt with a unique id.
+    pub fn execute_swap_with_receipt(
+        &mut self,
+        input_token: &str,
+        output_token: &str,
+        input_amount: &BigUint,
+    ) -> Result<SwapReceipt, SwapError> {
+        let (output_amount, fee) = self.apply_swap(input_token, output_token, input_amount)?;
+
+        Ok(SwapReceipt {
+            id: Uuid::new_v4(),
+            pool_id: self.id.clone(),
+            input_token: input_token.to_string(),
+            output_token: output_token.to_string(),
+            input_amount: input_amount.clone(),
+            output_amount,
+            fee: fee.amount,
+            fee_token: fee.token,
+            timestamp: unix_timestamp(),
+            resulting_reserves: self.reserves.clone(),
+        })
+    }
+
+    // Moves the swap through the reserves, returning the output and its fee
+    fn apply_swap(
+        &mut self,
+        input_token: &str,
+        output_token: &str,
+        input_amount: &BigUint,
+    ) -> Result<(BigUint, SwapFee), SwapError> {
+        // A concentrated swap also moves the pool's price
+        let (output_amount, fee, sqrt_price) = match self.pool_type {
+            PoolType::ConcentratedLiquidity => {
+                let (output_amount, fee, sqrt_price) =
+                    self.concentrated_swap(input_token, output_token, input_amount)?;
+                (output_amount, fee, Some(sqrt_price))
+            }
+            _ => {
+                let (output_amount, fee) =
+                    self.quote_with_fee(input_token, output_token, input_amount)?;
+                (output_amount, fee, None)
+            }
+        };
+
+        // Only the output side shrinks, so it is the only reserve to check
+        let output_reserve = self
+            .reserves
+            .get(output_token)
+            .ok_or(SwapError::TokenNotFound)?;
+        if output_amount > *output_reserve {
+            return Err(SwapError::InsufficientLiquidity);
+        }
+        let remaining = output_reserve - &output_amount;
+        // An empty reserve breaks the pricing of every later swap
+        if remaining.is_zero() {
+            return Err(SwapError::WouldEmptyReserve);
+        }
+        if !self.respects_floor(output_token, &remaining) {
+            return Err(SwapError::ReserveFloorBreached);
+        }
+
+        // Fees stay in the pool, so x * y may only grow
+        if self.pool_type == PoolType::ConstantProduct {
+            let input_reserve = self
+                .reserves
+                .get(input_token)
+                .ok_or(SwapError::TokenNotFound)?;
+            let k_before = input_reserve * output_reserve;
+            if (input_reserve + input_amount) * &remaining < k_before {
+                return Err(SwapError::InvariantViolated);
+            }
+        }
+
+        // On the tick map the price is pinned rather than implied by the
+        // reserves, so the swap moves it explicitly. The fee goes to the
+        // positions in range where the swap starts.
+        if let Some(sqrt_price) = sqrt_price.filter(|_| !self.ticks.is_empty()) {
+            self.accrue_position_fees(&fee.token, &fee.amount)?;
+            self.sqrt_price_x96 = Some(sqrt_price);
+        }
+        *self
+            .reserves
+            .get_mut(input_token)
+            .ok_or(SwapError::TokenNotFound)? += input_amount;
+        *self
+            .reserves
+            .get_mut(output_token)
+            .ok_or(SwapError::TokenNotFound)? -= &output_amount;
+        *self.cumulative_fees.entry(fee.token.clone()).or_default() += &fee.amount;
+        self.bump_version();
+
+        Ok((output_amount, fee))
+    }
+
+    // Whether `reserve` would keep `token` at or above its configured floor
+    fn respects_floor(&self, token: &str, reserve: &BigUint) -> bool {
+        self.min_reserves
+            .as_ref()
+            .and_then(|floors| floors.get(token))
+            .is_none_or(|floor| reserve >= floor)
+    }
+
+    /// Fee in basis points charged on swaps from `input_token`: the fee
+    /// strategy's if there is one, else its entry in `directional_fee_rates`
+    /// if it has one, else `fee_rate`.
+    pub fn fee_rate_for(&self, input_token: &str) -> u64 {
+        if let Some(strategy) = &self.fee_strategy {
+            return strategy.fee_bps(self.direction_from(input_token));
+        }
+        self.directional_fee_rates
+            .as_ref()
+            .and_then(|rates| rates.get(input_token))
+            .copied()
+            .unwrap_or(self.fee_rate)
+    }
+
+    /// Portion of `input_amount` the pool's fee schedule keeps as LP fee, in
+    /// raw units of the input token. StableSwap pools charge their fee on
+    /// the output instead; `quote_with_fee` has the fee any curve takes.
+    pub fn swap_fee(
+        &self,
+        input_token: &str,
+        input_amount: &BigUint,
+    ) -> Result<BigUint, SwapError> {
+        Ok(self.apply_fee(input_token, input_amount)?.fee)
+    }
+
+    fn apply_fee(&self, input_token: &str, input_amount: &BigUint) -> Result<FeeResult, SwapError> {
+        let direction = self.direction_from(input_token);
+        match &self.fee_strategy {
+            Some(strategy) => strategy.apply(input_amount, direction),
+            None => FlatFee::new(self.fee_rate_for(input_token)).apply(input_amount, direction),
+        }
+    }
+
+    fn direction_from(&self, input_token: &str) -> Direction {
+        match self.tokens.first() {
+            Some(first) if first.address == input_token => Direction::Forward,
+            _ => Direction::Backward,
+        }
+    }
+
+    /// Marginal price of `base` in units of `quote` implied by the reserves,
+    /// in raw token units and before fees. Weighted pools scale each reserve
+    /// by its weight: `(R_quote / w_quote) / (R_base / w_base)`.
+    pub fn spot_price(&self, base: &str, quote: &str) -> Result<Decimal, SwapError> {
+        let base_reserve = self.reserves.get(base).ok_or(SwapError::TokenNotFound)?;
+        let quote_reserve = self.reserves.get(quote).ok_or(SwapError::TokenNotFound)?;
+
+        if base_reserve.is_zero() || quote_reserve.is_zero() {
+            return Err(SwapError::InsufficientLiquidity);
+        }
+
+        if let PoolType::Weighted { weights } = &self.pool_type {
+            if valid_weights(weights, self.tokens.len()) {
+                let base_weight = weights[self.find_token_index(base)?];
+                let quote_weight = weights[self.find_token_index(quote)?];
+                return ratio_to_decimal(
+                    &(quote_reserve * base_weight),
+                    &(base_reserve * quote_weight),
+                )
+                .ok_or(SwapError::InsufficientLiquidity);
+            }
+        }
+
+        ratio_to_decimal(quote_reserve, base_reserve).ok_or(SwapError::InsufficientLiquidity)
+    }
+
+    /// Spot prices for every ordered pair of pool tokens, keyed by
+    /// `(base, quote)` address. Pairs with an empty reserve are left out.
+    pub fn price_matrix(&self) -> HashMap<(String, String), Decimal> {
+        let mut prices = HashMap::new();
+        for base in &self.tokens {
+            for quote in &self.tokens {
+                if base.address == quote.address {
+                    continue;
+                }
+                if let Ok(price) = self.spot_price(&base.address, &quote.address) {
+                    prices.insert((base.address.clone(), quote.address.clone()), price);
+                }
+            }
+        }
+        prices
+    }
+
+    /// Amount of `base` that can be sold before its spot price in `quote`
+    /// falls to `price`, ignoring fees; zero when it is already at or below.
+    /// Only constant-product pools have a closed form for this.
+    pub fn depth_at_price(
+        &self,
+        base: &str,
+        quote: &str,
+        price: Decimal,
+    ) -> Result<BigUint, SwapError> {
+        if self.pool_type != PoolType::ConstantProduct {
+            return Err(SwapError::UnsupportedPoolType);
+        }
+        let base_reserve = self.reserves.get(base).ok_or(SwapError::TokenNotFound)?;
+        let quote_reserve = self.reserves.get(quote).ok_or(SwapError::TokenNotFound)?;
+        if base_reserve.is_zero() || quote_reserve.is_zero() {
+            return Err(SwapError::InsufficientLiquidity);
+        }
+        if price <= Decimal::ZERO {
+            return Err(SwapError::PriceOutOfRange);
+        }
+
+        // price = k / x'^2 once x' of base is in the pool
+        let mantissa = BigUint::from(price.mantissa().unsigned_abs());
+        let scale = BigUint::from(10u64).pow(price.scale());
+        let target = sqrt(&(base_reserve * quote_reserve * scale / mantissa));
+        Ok(if target > *base_reserve {
+            target - base_reserve
+        } else {
+            BigUint::zero()
+        })
+    }
+
+    /// Effective execution price for a trade: output received per unit of
+    /// input, in raw token units (fees and price impact included).
+    pub fn effective_price(
+        &self,
+        input_token: &str,
+        output_token: &str,
+        input_amount: &BigUint,
+    ) -> Result<Decimal, SwapError> {
+        if input_amount.is_zero() {
+            return Err(SwapError::InsufficientLiquidity);
+        }
+        let output_amount = self.calculate_swap_output(input_token, output_token, input_amount)?;
+        ratio_to_decimal(&output_amount, input_amount).ok_or(SwapError::InsufficientLiquidity)
+    }
+
+    /// Swap output minus the caller's gas estimate, already expressed in the
+    /// output token. Never negative: a trade not worth its gas nets zero.
+    pub fn net_output_after_gas(
         &self,
         input_token: &str,
         output_token: &str,
         input_amount: &BigUint,
+        gas_in_output_token: &BigUint,
     ) -> Result<BigUint, SwapError> {
+        let output_amount = self.calculate_swap_output(input_token, output_token, input_amount)?;
+        if output_amount > *gas_in_output_token {
+            Ok(output_amount - gas_in_output_token)
+        } else {
+            Ok(BigUint::zero())
+        }
+    }
+
+    /// Returns the effective price as a function of input size, evaluated
+    /// against the reserves at the time of the call. Inputs that cannot be
+    /// filled map to a price of zero.
+    pub fn price_curve(
+        &self,
+        input_token: &str,
+        output_token: &str,
+    ) -> impl Fn(&BigUint) -> Decimal {
+        let snapshot = self.clone();
+        let input_token = input_token.to_string();
+        let output_token = output_token.to_string();
+
+        move |input_amount| {
+            snapshot
+                .effective_price(&input_token, &output_token, input_amount)
+                .unwrap_or(Decimal::ZERO)
+        }
+    }
+
+    fn constant_product_swap(
+        &self,
+        input_token: &str,
+        output_token: &str,
+        input_amount: &BigUint,
+    ) -> Result<(BigUint, SwapFee), SwapError> {
         let input_reserve = self
             .reserves
             .get(input_token)
@@ -87,17 +749,41 @@ impl Pool {
             .get(output_token)
             .ok_or(SwapError::TokenNotFound)?;
 
+        let fee = self.apply_fee(input_token, input_amount)?;
+        let output_amount =
+            Self::constant_product_curve(input_reserve, output_reserve, fee.amount_after_fee)?;
+        Ok((
+            output_amount,
+            SwapFee {
+                token: input_token.to_string(),
+                amount: fee.fee,
+            },
+        ))
+    }
+
+    // x * y = k output for the given reserves and flat fee, used by `PoolSim`
+    fn constant_product_output(
+        input_reserve: &BigUint,
+        output_reserve: &BigUint,
+        input_amount: &BigUint,
+        fee_rate: u64,
+    ) -> Result<BigUint, SwapError> {
+        let fee = FlatFee::new(fee_rate).apply(input_amount, Direction::Forward)?;
+        Self::constant_product_curve(input_reserve, output_reserve, fee.amount_after_fee)
+    }
+
+    fn constant_product_curve(
+        input_reserve: &BigUint,
+        output_reserve: &BigUint,
+        input_amount_with_fee: BigUint,
+    ) -> Result<BigUint, SwapError> {
         if input_reserve.is_zero() || output_reserve.is_zero() {
             return Err(SwapError::InsufficientLiquidity);
         }
 
-        // Apply fee: input_amount_with_fee = input_amount * (10000 - fee_rate) / 10000
-        let fee_multiplier = BigUint::from(10000u64 - self.fee_rate);
-        let input_amount_with_fee = (input_amount * &fee_multiplier) / BigUint::from(10000u64);
-
         // Calculate output: output = (input_with_fee * output_reserve) / (input_reserve + input_with_fee)
         let numerator = &input_amount_with_fee * output_reserve;
-        let denominator = input_reserve + &input_amount_with_fee;
+        let denominator = input_amount_with_fee + input_reserve;
 
         if denominator.is_zero() {
             return Err(SwapError::InsufficientLiquidity);
@@ -112,6 +798,48 @@ impl Pool {
         Ok(output_amount)
     }
 
+    /// Smallest input that yields at least `output_amount`, the exact-output
+    /// counterpart of `calculate_swap_output`. Both divisions round up.
+    pub fn calculate_input_for_output(
+        &self,
+        input_token: &str,
+        output_token: &str,
+        output_amount: &BigUint,
+    ) -> Result<BigUint, SwapError> {
+        if self.pool_type != PoolType::ConstantProduct {
+            return Err(SwapError::UnsupportedPoolType);
+        }
+
+        let input_reserve = self
+            .reserves
+            .get(input_token)
+            .ok_or(SwapError::TokenNotFound)?;
+        let output_reserve = self
+            .reserves
+            .get(output_token)
+            .ok_or(SwapError::TokenNotFound)?;
+
+        if input_reserve.is_zero() || output_amount >= output_reserve {
+            return Err(SwapError::InsufficientLiquidity);
+        }
+
+        // input_with_fee = input_reserve * output / (output_reserve - output)
+        let input_with_fee = ceil_div(
+            &(input_reserve * output_amount),
+            &(output_reserve - output_amount),
+        );
+        // input = input_with_fee * 10000 / (10000 - fee_rate)
+        // A fee of 100% or more lets no input through at all
+        let fee_multiplier = 10000u64
+            .checked_sub(self.fee_rate_for(input_token))
+            .filter(|multiplier| *multiplier > 0)
+            .ok_or(SwapError::InsufficientLiquidity)?;
+        Ok(ceil_div(
+            &(input_with_fee * BigUint::from(10000u64)),
+            &BigUint::from(fee_multiplier),
+        ))
+    }
+
     pub fn add_liquidity(
         &mut self,
         token_amounts: HashMap<String, BigUint>,
@@ -130,20 +858,139 @@ impl Pool {
 
         // Update total supply
         self.total_supply += &lp_tokens;
+        self.bump_version();
 
         Ok(lp_tokens)
     }
 
-    fn calculate_lp_tokens_to_mint(
-        &self,
-        token_amounts: &HashMap<String, BigUint>,
+    /// Adds liquidity and credits the minted LP tokens to `owner`'s position.
+    pub fn add_liquidity_for(
+        &mut self,
+        owner: &str,
+        token_amounts: HashMap<String, BigUint>,
     ) -> Result<BigUint, LiquidityError> {
-        if self.total_supply.is_zero() {
-            // Initial liquidity
-            let mut product = BigUint::one();
-            for amount in token_amounts.values() {
-                product *= amount;
-            }
+        let lp_tokens = self.add_liquidity(token_amounts)?;
+        *self.lp_positions.entry(owner.to_string()).or_default() += &lp_tokens;
+        Ok(lp_tokens)
+    }
+
+    /// Token amounts `lp_tokens` are redeemable for at current reserves,
+    /// `reserve_i * lp_tokens / total_supply` rounded down. Does not touch
+    /// the pool.
+    pub fn underlying_amounts(
+        &self,
+        lp_tokens: &BigUint,
+    ) -> Result<HashMap<String, BigUint>, LiquidityError> {
+        if self.total_supply.is_zero() || *lp_tokens > self.total_supply {
+            return Err(LiquidityError::InsufficientLiquidity);
+        }
+
+        Ok(self
+            .reserves
+            .iter()
+            .map(|(token, reserve)| (token.clone(), reserve * lp_tokens / &self.total_supply))
+            .collect())
+    }
+
+    /// Burns `lp_tokens` and pays out their share of every reserve, as
+    /// computed by `underlying_amounts`. Rounding down keeps the payouts
+    /// within the reserves, so the pool cannot be overdrawn. Refused,
+    /// leaving the pool untouched, if any reserve would drop below its
+    /// `min_reserves` floor. Only supply outside `lp_positions` can be burned
+    /// this way; owned tokens go through `remove_liquidity_for`.
+    pub fn remove_liquidity(
+        &mut self,
+        lp_tokens: &BigUint,
+    ) -> Result<HashMap<String, BigUint>, LiquidityError> {
+        let owned: BigUint = self.lp_positions.values().sum();
+        if owned + lp_tokens > self.total_supply {
+            return Err(LiquidityError::InsufficientLPTokens);
+        }
+        self.burn_lp_tokens(lp_tokens, true)
+    }
+
+    /// Removes liquidity, burning the LP tokens from `owner`'s position.
+    pub fn remove_liquidity_for(
+        &mut self,
+        owner: &str,
+        lp_tokens: &BigUint,
+    ) -> Result<HashMap<String, BigUint>, LiquidityError> {
+        self.burn_owned_lp_tokens(owner, lp_tokens, true)
+    }
+
+    // `burn_lp_tokens` for tokens held by `owner`, debiting their position
+    fn burn_owned_lp_tokens(
+        &mut self,
+        owner: &str,
+        lp_tokens: &BigUint,
+        enforce_floors: bool,
+    ) -> Result<HashMap<String, BigUint>, LiquidityError> {
+        let held = self.lp_positions.get(owner).cloned().unwrap_or_default();
+        if *lp_tokens > held {
+            return Err(LiquidityError::InsufficientLPTokens);
+        }
+
+        let payouts = self.burn_lp_tokens(lp_tokens, enforce_floors)?;
+        self.lp_positions
+            .insert(owner.to_string(), held - lp_tokens);
+        Ok(payouts)
+    }
+
+    // Pays out `lp_tokens`' share of every reserve; only the emergency exit
+    // may take reserves below their floors
+    fn burn_lp_tokens(
+        &mut self,
+        lp_tokens: &BigUint,
+        enforce_floors: bool,
+    ) -> Result<HashMap<String, BigUint>, LiquidityError> {
+        if *lp_tokens > self.total_supply {
+            return Err(LiquidityError::InsufficientLPTokens);
+        }
+        let payouts = self.underlying_amounts(lp_tokens)?;
+        // Every payout is checked before any reserve moves
+        let breaches_floor = payouts
+            .iter()
+            .any(|(token, amount)| !self.respects_floor(token, &(&self.reserves[token] - amount)));
+        if enforce_floors && breaches_floor {
+            return Err(LiquidityError::ReserveFloorBreached);
+        }
+
+        for (token, amount) in &payouts {
+            if let Some(reserve) = self.reserves.get_mut(token) {
+                *reserve -= amount;
+            }
+        }
+        self.total_supply -= lp_tokens;
+        self.bump_version();
+
+        Ok(payouts)
+    }
+
+    /// Incident-only exit for `owner`: burns `lp_tokens` from their
+    /// position for the strictly proportional share of every reserve, with
+    /// no fee or imbalance penalty, even below the reserve floors. Refused
+    /// unless `set_emergency_mode(true)`.
+    pub fn emergency_remove_liquidity(
+        &mut self,
+        lp_tokens: &BigUint,
+        owner: &str,
+    ) -> Result<HashMap<String, BigUint>, LiquidityError> {
+        if !emergency_mode() {
+            return Err(LiquidityError::EmergencyModeDisabled);
+        }
+        self.burn_owned_lp_tokens(owner, lp_tokens, false)
+    }
+
+    fn calculate_lp_tokens_to_mint(
+        &self,
+        token_amounts: &HashMap<String, BigUint>,
+    ) -> Result<BigUint, LiquidityError> {
+        if self.total_supply.is_zero() {
+            // Initial liquidity
+            let mut product = BigUint::one();
+            for amount in token_amounts.values() {
+                product *= amount;
+            }
             return Ok(sqrt(&product));
         }
 
@@ -170,26 +1017,373 @@ impl Pool {
 
         min_ratio.ok_or(LiquidityError::InsufficientLiquidity)
     }
+
+    /// Overwrites the reserves, e.g. to seed a test deployment, and resets
+    /// `total_supply` to what they imply. `reserves` must name every pool
+    /// token exactly once (addresses compare case-insensitively), each with
+    /// a nonzero amount.
+    pub fn set_reserves(
+        &mut self,
+        reserves: HashMap<String, BigUint>,
+    ) -> Result<(), LiquidityError> {
+        if reserves.len() != self.tokens.len() {
+            return Err(LiquidityError::TokenNotFound);
+        }
+        let mut canonical = HashMap::new();
+        for (token, amount) in reserves {
+            let address = self
+                .find_token(&token)
+                .ok_or(LiquidityError::TokenNotFound)?
+                .address
+                .clone();
+            // Curves divide by every reserve, e.g. StableSwap's D
+            if amount.is_zero() {
+                return Err(LiquidityError::InsufficientLiquidity);
+            }
+            if canonical.insert(address, amount).is_some() {
+                return Err(LiquidityError::TokenNotFound);
+            }
+        }
+
+        self.reserves = canonical;
+        self.total_supply = self.supply_implied_by(&self.pool_type);
+        self.bump_version();
+        Ok(())
+    }
+
+    /// Converts the pool to another curve in place, keeping its tokens and
+    /// reserves. `total_supply` is recomputed for the new curve (D for
+    /// StableSwap) and LP positions are rescaled so every owner keeps the
+    /// same share of the pool.
+    pub fn migrate_to(&mut self, new_type: PoolType) -> Result<(), PoolError> {
+        let incompatible = |reason: &str| PoolError::IncompatibleMigration {
+            from: self.pool_type.clone(),
+            to: new_type.clone(),
+            reason: reason.to_string(),
+        };
+        if new_type == self.pool_type {
+            return Err(incompatible("pool already uses this curve"));
+        }
+
+        // Reserves must cover exactly the pool's tokens, all nonzero
+        let balances: Vec<&BigUint> = self
+            .tokens
+            .iter()
+            .filter_map(|token| self.reserves.get(&token.address))
+            .collect();
+        if balances.len() < 2
+            || balances.len() != self.tokens.len()
+            || self.reserves.len() != self.tokens.len()
+        {
+            return Err(incompatible("reserves do not match the token set"));
+        }
+        if balances.iter().any(|balance| balance.is_zero()) {
+            return Err(incompatible("every reserve must be nonzero"));
+        }
+        if let PoolType::Weighted { weights } = &new_type {
+            if !valid_weights(weights, self.tokens.len()) {
+                return Err(PoolError::InvalidWeights(weights.clone()));
+            }
+        }
+
+        // Zero would wipe every position, e.g. when D does not converge
+        let new_supply = self.supply_implied_by(&new_type);
+        if new_supply.is_zero() {
+            return Err(incompatible(
+                "reserves imply no LP supply under the new curve",
+            ));
+        }
+
+        if !self.total_supply.is_zero() {
+            for position in self.lp_positions.values_mut() {
+                *position = &*position * &new_supply / &self.total_supply;
+            }
+        }
+        self.total_supply = new_supply;
+        self.pool_type = new_type;
+        self.bump_version();
+        Ok(())
+    }
+
+    // LP supply backed by the current reserves under `pool_type`
+    fn supply_implied_by(&self, pool_type: &PoolType) -> BigUint {
+        match pool_type {
+            PoolType::ConstantProduct | PoolType::ConcentratedLiquidity => {
+                // Calculate initial LP tokens using geometric mean
+                let mut product = BigUint::one();
+                for reserve in self.reserves.values() {
+                    product *= reserve;
+                }
+                // Simplified: use square root for 2-token pools
+                sqrt(&product)
+            }
+            PoolType::StableSwap => {
+                // The invariant D over balances in token order, as swaps see them
+                let zero = BigUint::zero();
+                let balances: Vec<&BigUint> = self
+                    .tokens
+                    .iter()
+                    .map(|token| self.reserves.get(&token.address).unwrap_or(&zero))
+                    .collect();
+                if balances.iter().any(|balance| balance.is_zero()) {
+                    return BigUint::zero();
+                }
+                Self::calculate_d(&balances, &BigUint::from(self.amplification)).unwrap_or_default()
+            }
+            PoolType::Weighted { weights } => {
+                // Balancer's invariant, the weighted geometric mean of the reserves
+                if !valid_weights(weights, self.tokens.len()) {
+                    return BigUint::zero();
+                }
+                let mut product = BigUint::one();
+                for (token, weight) in self.tokens.iter().zip(weights) {
+                    match self.reserves.get(&token.address) {
+                        Some(reserve) => product *= reserve.pow(*weight as u32),
+                        None => return BigUint::zero(),
+                    }
+                }
+                product.nth_root(WEIGHT_DENOMINATOR as u32)
+            }
+        }
+    }
+
+    /// Compares `total_supply` with the supply the reserves imply. Rounding
+    /// in favour of the pool only ever leaves a surplus, so a deficit of up to
+    /// `RECONCILE_DUST` is integer-root dust and is burned from the supply;
+    /// anything larger is reported and left for an operator to look at.
+    pub fn reconcile(&mut self) -> ReconcileReport {
+        let implied = self.supply_implied_by(&self.pool_type);
+        let total_supply = self.total_supply.clone();
+
+        let (surplus, deficit) = if implied >= total_supply {
+            (&implied - &total_supply, BigUint::zero())
+        } else {
+            (BigUint::zero(), &total_supply - &implied)
+        };
+
+        let dust_absorbed = if !deficit.is_zero() && deficit <= BigUint::from(RECONCILE_DUST) {
+            self.total_supply = implied.clone();
+            self.bump_version();
+            deficit.clone()
+        } else {
+            BigUint::zero()
+        };
+
+        ReconcileReport {
+            total_supply,
+            implied_supply: implied,
+            surplus,
+            deficit,
+            dust_absorbed,
+        }
+    }
+
+    /// Records a state change; anything derived from the reserves is
+    /// recomputed on next use.
+    pub(crate) fn bump_version(&mut self) {
+        self.version += 1;
+        self.normalized_cache = OnceLock::new();
+        for (token, reserve) in &self.reserves {
+            match self.max_reserves.get_mut(token) {
+                Some(max) if reserve > max => *max = reserve.clone(),
+                Some(_) => {}
+                None => {
+                    self.max_reserves.insert(token.clone(), reserve.clone());
+                }
+            }
+        }
+    }
+
+    /// How far `token`'s reserve has fallen from the highest it has been,
+    /// from 0 (at its peak) to 1 (drained). A rising value flags a pool
+    /// being drained; zero for tokens the pool does not hold.
+    pub fn utilization(&self, token: &str) -> Decimal {
+        let (Some(reserve), Some(max)) = (self.reserves.get(token), self.max_reserves.get(token))
+        else {
+            return Decimal::ZERO;
+        };
+        if reserve >= max {
+            return Decimal::ZERO;
+        }
+        ratio_to_decimal(&(max - reserve), max).unwrap_or(Decimal::ZERO)
+    }
+
+    /// Adds to `last_price_cumulative` the current spot price of the first
+    /// token in the second, as Q112 fixed point, times the seconds since
+    /// the last call, as the Uniswap V2 oracle does. Call it
+    /// before every change to the reserves so each interval is priced at
+    /// the reserves it ran at. The first call only starts the clock.
+    pub fn accumulate_price(&mut self, now: u64) {
+        if now <= self.last_update_timestamp {
+            return;
+        }
+        if self.last_update_timestamp != 0 {
+            if let Some(price) = self.oracle_price() {
+                self.last_price_cumulative += price * (now - self.last_update_timestamp);
+            }
+        }
+        self.last_update_timestamp = now;
+    }
+
+    /// Time-weighted average spot price of the first token in the second
+    /// between `snapshot`, an earlier `(last_price_cumulative,
+    /// last_update_timestamp)`, and `now`. The interval since the last
+    /// accumulation counts at the current price. Zero for an empty interval.
+    pub fn consult_twap(&self, snapshot: (BigUint, u64), now: u64) -> f64 {
+        let (snapshot_cumulative, snapshot_timestamp) = snapshot;
+        if now <= snapshot_timestamp {
+            return 0.0;
+        }
+        let mut cumulative = self.last_price_cumulative.clone();
+        if self.last_update_timestamp != 0 && now > self.last_update_timestamp {
+            if let Some(price) = self.oracle_price() {
+                cumulative += price * (now - self.last_update_timestamp);
+            }
+        }
+        if cumulative < snapshot_cumulative {
+            return 0.0;
+        }
+        let elapsed = BigUint::from(now - snapshot_timestamp) << ORACLE_PRICE_BITS;
+        biguint_ratio_to_f64(&(cumulative - snapshot_cumulative), &elapsed)
+    }
+
+    // Spot price of the first token in the second as Q112, `None` while
+    // either reserve is empty
+    fn oracle_price(&self) -> Option<BigUint> {
+        let [base, quote, ..] = self.tokens.as_slice() else {
+            return None;
+        };
+        let base_reserve = self.reserves.get(&base.address)?;
+        let quote_reserve = self.reserves.get(&quote.address)?;
+        if base_reserve.is_zero() || quote_reserve.is_zero() {
+            return None;
+        }
+        Some((quote_reserve << ORACLE_PRICE_BITS) / base_reserve)
+    }
+
+    /// Reserves scaled to 18 decimals, keyed like `reserves`. Computed once
+    /// per version, so code that edits `reserves` directly must go through
+    /// a method that bumps it.
+    pub fn normalized_reserves(&self) -> &HashMap<String, BigUint> {
+        self.normalized_cache.get_or_init(|| {
+            self.tokens
+                .iter()
+                .filter_map(|token| {
+                    self.reserves.get(&token.address).map(|reserve| {
+                        (
+                            token.address.clone(),
+                            normalize_amount(reserve, token.decimals),
+                        )
+                    })
+                })
+                .collect()
+        })
+    }
+
+    /// `reserves_checksum` of the pool's reserves.
+    pub fn reserves_checksum(&self) -> u64 {
+        reserves_checksum(&self.reserves)
+    }
+
+    /// Sum of all reserves scaled to 18 decimals. For pools of ~$1 stablecoins
+    /// this is a cheap TVL approximation that needs no price feed.
+    pub fn reserves_sum_normalized(&self) -> BigUint {
+        self.normalized_reserves().values().sum()
+    }
+
+    /// Largest over smallest reserve of a stable pool once scaled to 18
+    /// decimals; 1 means perfectly balanced. A rising ratio flags a depeg.
+    pub fn imbalance_ratio(&self) -> Result<Decimal, SwapError> {
+        if self.pool_type != PoolType::StableSwap {
+            return Err(SwapError::UnsupportedPoolType);
+        }
+
+        let normalized_reserves = self.normalized_reserves();
+        let normalized = self
+            .tokens
+            .iter()
+            .map(|token| {
+                normalized_reserves
+                    .get(&token.address)
+                    .ok_or(SwapError::TokenNotFound)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let max = normalized
+            .iter()
+            .max()
+            .ok_or(SwapError::InsufficientLiquidity)?;
+        let min = normalized
+            .iter()
+            .min()
+            .ok_or(SwapError::InsufficientLiquidity)?;
+        if min.is_zero() {
+            return Err(SwapError::InsufficientLiquidity);
+        }
+
+        ratio_to_decimal(max, min).ok_or(SwapError::InsufficientLiquidity)
+    }
+
+    /// True when the stable pool's imbalance ratio is at most `threshold`.
+    pub fn is_stable_balanced(&self, threshold: Decimal) -> bool {
+        self.imbalance_ratio()
+            .map(|ratio| ratio <= threshold)
+            .unwrap_or(false)
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
-pub enum SwapError {
+pub enum LiquidityError {
     #[error("Token not found in pool")]
     TokenNotFound,
     #[error("Insufficient liquidity")]
     InsufficientLiquidity,
-    #[error("Unsupported pool type")]
-    UnsupportedPoolType,
+    #[error("More LP tokens than the pool's total supply")]
+    InsufficientLPTokens,
+    #[error("Emergency withdrawals are disabled")]
+    EmergencyModeDisabled,
+    #[error("Reserve would fall below its configured minimum")]
+    ReserveFloorBreached,
 }
 
 #[derive(Debug, thiserror::Error)]
-pub enum LiquidityError {
-    #[error("Token not found in pool")]
-    TokenNotFound,
-    #[error("Insufficient liquidity")]
-    InsufficientLiquidity,
+pub enum PoolError {
+    #[error("Cannot migrate {from} pool to {to}: {reason}")]
+    IncompatibleMigration {
+        from: PoolType,
+        to: PoolType,
+        reason: String,
+    },
+    #[error("Amplification {0} is outside {MIN_AMPLIFICATION}..={MAX_AMPLIFICATION}")]
+    InvalidAmplification(u64),
+    #[error("Fee rate {0} bps must be below 10000")]
+    InvalidFeeRate(u64),
+    #[error("Reserves do not match tokens: missing {missing:?}, unexpected {extra:?}")]
+    ReserveTokenMismatch {
+        missing: Vec<String>,
+        extra: Vec<String>,
+    },
+    #[error("Weights {0:?} must be positive, one per token, and sum to {WEIGHT_DENOMINATOR}")]
+    InvalidWeights(Vec<u64>),
+    #[error("Tag '{0}' must be 1 to {MAX_TAG_LEN} lowercase letters, digits or '-'")]
+    InvalidTag(String),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "Unknown pool type '{0}' (expected constant_product, stable_swap, concentrated_liquidity or weighted:<weights>)"
+)]
+pub struct ParsePoolTypeError(pub String);
+
+#[derive(Debug, thiserror::Error)]
+#[error("Value of {bits} bits exceeds the exact integer range of f64 (2^53)")]
+pub struct PrecisionError {
+    pub bits: u64,
 }
 
+// Largest supply/reserve mismatch attributed to rounding rather than a bug
+const RECONCILE_DUST: u64 = 1;
+
 // Helper function for square root calculation
 fn sqrt(n: &BigUint) -> BigUint {
     if n.is_zero() {
@@ -207,19 +1401,203 @@ fn sqrt(n: &BigUint) -> BigUint {
     x
 }
 
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+// Fractional bits of the fixed-point power in weighted swaps
+const WEIGHTED_PRECISION_BITS: usize = 128;
+
+// Process-wide switch for `Pool::emergency_remove_liquidity`
+static EMERGENCY_MODE: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables emergency withdrawals for every pool.
+pub fn set_emergency_mode(enabled: bool) {
+    EMERGENCY_MODE.store(enabled, Ordering::SeqCst);
+}
+
+pub fn emergency_mode() -> bool {
+    EMERGENCY_MODE.load(Ordering::SeqCst)
+}
+
+// Amplification parameter of stable pools unless configured otherwise
+const STABLE_AMPLIFICATION: u64 = 100;
+
+// Accepted range of the amplification parameter
+pub const MIN_AMPLIFICATION: u64 = 1;
+pub const MAX_AMPLIFICATION: u64 = 1_000_000;
+
+fn default_amplification() -> u64 {
+    STABLE_AMPLIFICATION
+}
+
+// Integer division rounding towards +infinity
+fn ceil_div(numerator: &BigUint, denominator: &BigUint) -> BigUint {
+    (numerator + denominator - BigUint::one()) / denominator
+}
+
+// Current unix time in seconds
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Canonical form of a token address for comparisons, so differently-cased
+/// spellings of the same address match.
+pub fn normalize_address(address: &str) -> String {
+    address.trim().to_lowercase()
+}
+
+// Integers above 2^53 can no longer be represented exactly by an f64
+const F64_EXACT_INT_BITS: usize = 53;
+
+/// Converts to f64, refusing values beyond the exact-integer range of f64
+/// instead of silently rounding them. Callers should normalize amounts
+/// (e.g. scale to a ratio or strip decimals) before converting.
+pub fn checked_biguint_to_f64(n: &BigUint) -> Result<f64, PrecisionError> {
+    if *n > BigUint::one() << F64_EXACT_INT_BITS {
+        return Err(PrecisionError { bits: n.bits() });
+    }
+
+    n.to_f64().ok_or(PrecisionError { bits: n.bits() })
+}
+
+/// Nearest f64 to `n`, for math that is float-based anyway. Values
+/// beyond the f64 range saturate at `f64::MAX` rather than becoming
+/// infinite.
+pub fn biguint_to_f64(n: &BigUint) -> f64 {
+    n.to_f64()
+        .filter(|value| value.is_finite())
+        .unwrap_or(f64::MAX)
+}
+
+// Leaves headroom below f64::MAX_EXP when shifting operands down
+const F64_RATIO_BITS: u64 = 1000;
+
+// `numerator / denominator` as f64. Bits below the precision of the larger
+// operand are dropped from both first, so huge values keep their ratio
+// instead of dividing infinity by infinity.
+fn biguint_ratio_to_f64(numerator: &BigUint, denominator: &BigUint) -> f64 {
+    let excess = numerator
+        .bits()
+        .max(denominator.bits())
+        .saturating_sub(F64_RATIO_BITS);
+    biguint_to_f64(&(numerator >> excess)) / biguint_to_f64(&(denominator >> excess))
+}
+
+// Fractional bits of the prices summed by `Pool::accumulate_price`, as in
+// Uniswap V2's UQ112x112
+const ORACLE_PRICE_BITS: usize = 112;
+
+// Units D may lose to integer rounding across a stable swap
+const STABLE_D_TOLERANCE: u32 = 1;
+
+// Newton steps allowed for D and y before giving up
+const NEWTON_ITERATIONS: usize = 255;
+
+// Common precision that token amounts are scaled to before comparing them
+const NORMALIZED_DECIMALS: u8 = 18;
+
+// Helper for scaling a raw token amount to NORMALIZED_DECIMALS
+fn normalize_amount(amount: &BigUint, decimals: u8) -> BigUint {
+    if decimals <= NORMALIZED_DECIMALS {
+        amount * BigUint::from(10u64).pow((NORMALIZED_DECIMALS - decimals) as u32)
+    } else {
+        amount / BigUint::from(10u64).pow((decimals - NORMALIZED_DECIMALS) as u32)
+    }
+}
+
+// FNV-1a, which unlike std's hashers is stable across builds and platforms
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Fingerprint of a reserve map that is the same on every deployment for
+/// equal reserves, whatever the map's iteration order. Cheap to compare
+/// before diffing reserve by reserve.
+pub fn reserves_checksum(reserves: &HashMap<String, BigUint>) -> u64 {
+    let mut entries: Vec<(&String, &BigUint)> = reserves.iter().collect();
+    entries.sort();
+
+    let mut hash = FNV_OFFSET_BASIS;
+    for (token, amount) in entries {
+        // The separators keep ("ab", 1) and ("a", b1) apart
+        let bytes = token
+            .bytes()
+            .chain([b'='])
+            .chain(amount.to_bytes_le())
+            .chain([b';']);
+        for byte in bytes {
+            hash = (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME);
+        }
+    }
+    hash
+}
+
+/// Formats a raw token amount as a decimal string in whole tokens, e.g.
+/// 1500000 with 6 decimals is "1.5". Exact, unlike converting via f64.
+pub fn format_units(amount: &BigUint, decimals: u8) -> String {
+    let unit = BigUint::from(10u64).pow(decimals as u32);
+    let whole = amount / &unit;
+    let fraction = amount % &unit;
+    if fraction.is_zero() {
+        return whole.to_string();
+    }
+
+    let fraction = format!(
+        "{:0>width$}",
+        fraction.to_string(),
+        width = decimals as usize
+    );
+    format!("{}.{}", whole, fraction.trim_end_matches('0'))
+}
+
+// Number of fractional digits kept when converting BigUint ratios to Decimal
+const DECIMAL_SCALE: u32 = 18;
+
+// Helper for converting an integer ratio to Decimal, dropping fractional
+// digits when the integer part is too large to keep the full scale.
+pub(crate) fn ratio_to_decimal(numerator: &BigUint, denominator: &BigUint) -> Option<Decimal> {
+    if denominator.is_zero() {
+        return None;
+    }
+
+    for scale in (0..=DECIMAL_SCALE).rev() {
+        let scaled = numerator * BigUint::from(10u64).pow(scale) / denominator;
+        if let Some(mantissa) = scaled.to_i128() {
+            if let Ok(value) = Decimal::try_from_i128_with_scale(mantissa, scale) {
+                return Some(value.normalize());
+            }
+        }
+    }
+
+    None
+}
+
+/// Least output a trade quoted at `expected` may return when the caller
+/// accepts `slippage_percent` less, rounded down. Exact for any tolerance
+/// `Decimal` can hold; `None` outside 0..=100.
+pub fn min_output_for_slippage(expected: &BigUint, slippage_percent: Decimal) -> Option<BigUint> {
+    if slippage_percent < Decimal::ZERO || slippage_percent > Decimal::ONE_HUNDRED {
+        return None;
+    }
+
+    // expected * (100 - tolerance) / 100, with tolerance = mantissa / 10^scale
+    let denominator = BigUint::from(100u32) * BigUint::from(10u64).pow(slippage_percent.scale());
+    let tolerance = BigUint::from(slippage_percent.mantissa().unsigned_abs());
+    Some(expected * (&denominator - tolerance) / denominator)
+}
+
 impl Pool {
     pub fn update_dynamic_fee(&mut self, volume_24h: &BigUint, volatility: f64) {
-        // Dynamic fee based on volume and volatility
-        let base_fee = 300u64; // 3% base fee
-        let volume_factor = if *volume_24h > BigUint::from(1000000u64) {
-            50
-        } else {
-            0
-        };
-        let volatility_factor = (volatility * 100.0) as u64;
-
-        self.fee_rate = base_fee + volume_factor + volatility_factor;
-        self.fee_rate = self.fee_rate.min(1000); // Cap at 10%
+        // Dynamic fee based on volume and volatility, from a 3% base fee
+        let fee = fees::DynamicFee::new(300, volume_24h.clone(), volatility);
+        self.fee_rate = fee.fee_bps(Direction::Forward);
+        self.bump_version();
     }
 
     pub fn calculate_concentrated_liquidity_swap(
@@ -229,24 +1607,12 @@ impl Pool {
         input_amount: &BigUint,
         price_range: (f64, f64),
     ) -> Result<BigUint, SwapError> {
-        // Uniswap V3 style concentrated liquidity logic
-        // This is a simplified version - real implementation would be more complex
-
-        let current_price = self.get_current_price(input_token, output_token)?;
-
-        if current_price < price_range.0 || current_price > price_range.1 {
-            return Err(SwapError::PriceOutOfRange);
-        }
-
-        // Calculate output based on concentrated liquidity curve
-        let liquidity_in_range = self.calculate_active_liquidity(price_range)?;
-        let output_amount =
-            self.calculate_output_from_liquidity(input_amount, &liquidity_in_range, current_price)?;
-
-        Ok(output_amount)
+        self.concentrated_liquidity_swap(input_token, output_token, input_amount, price_range)
     }
 
-    fn get_current_price(&self, token_a: &str, token_b: &str) -> Result<f64, SwapError> {
+    /// Price of `token_a` in `token_b` in raw units, ignoring decimals.
+    /// See `get_price_normalized` for display.
+    pub fn get_current_price(&self, token_a: &str, token_b: &str) -> Result<f64, SwapError> {
         let reserve_a = self.reserves.get(token_a).ok_or(SwapError::TokenNotFound)?;
         let reserve_b = self.reserves.get(token_b).ok_or(SwapError::TokenNotFound)?;
 
@@ -254,32 +1620,31 @@ impl Pool {
             return Err(SwapError::InsufficientLiquidity);
         }
 
-        let price = reserve_b.clone() as f64 / reserve_a.clone() as f64;
-        Ok(price)
-    }
-
-    fn calculate_active_liquidity(&self, price_range: (f64, f64)) -> Result<BigUint, SwapError> {
-        // Simplified - in reality this would track liquidity positions
-        let total_liquidity = &self.total_supply;
-        let range_factor = 1.0 / (price_range.1 - price_range.0);
-        let active_liquidity = total_liquidity * BigUint::from(range_factor as u64);
-
-        Ok(active_liquidity)
+        Ok(biguint_ratio_to_f64(reserve_b, reserve_a))
     }
 
-    fn calculate_output_from_liquidity(
-        &self,
-        input_amount: &BigUint,
-        liquidity: &BigUint,
-        current_price: f64,
-    ) -> Result<BigUint, SwapError> {
-        // Simplified concentrated liquidity calculation
-        let price_impact = input_amount.clone() / liquidity;
-        let new_price =
-            current_price * (1.0 + price_impact.to_string().parse::<f64>().unwrap_or(0.0));
-        let output_amount = input_amount * BigUint::from(new_price as u64);
+    /// Price of one whole `base` token in whole `quote` tokens, i.e. with
+    /// each reserve divided by `10^decimals` of its token.
+    pub fn get_price_normalized(&self, base: &str, quote: &str) -> Result<f64, SwapError> {
+        let base_token = self.find_token(base).ok_or(SwapError::TokenNotFound)?;
+        let quote_token = self.find_token(quote).ok_or(SwapError::TokenNotFound)?;
+        let base_reserve = self
+            .reserves
+            .get(&base_token.address)
+            .ok_or(SwapError::TokenNotFound)?;
+        let quote_reserve = self
+            .reserves
+            .get(&quote_token.address)
+            .ok_or(SwapError::TokenNotFound)?;
+        if base_reserve.is_zero() || quote_reserve.is_zero() {
+            return Err(SwapError::InsufficientLiquidity);
+        }
 
-        Ok(output_amount)
+        // (quote / 10^dq) / (base / 10^db), kept in integers until the end
+        let ten = BigUint::from(10u64);
+        let numerator = quote_reserve * ten.pow(base_token.decimals as u32);
+        let denominator = base_reserve * ten.pow(quote_token.decimals as u32);
+        Ok(biguint_ratio_to_f64(&numerator, &denominator))
     }
 }
 
@@ -294,6 +1659,22 @@ pub enum SwapError {
     UnsupportedPoolType,
     #[error("Price out of range")]
     PriceOutOfRange,
+    #[error("Reserve would fall below its configured minimum")]
+    ReserveFloorBreached,
+    #[error("Swap would empty a reserve")]
+    WouldEmptyReserve,
+    #[error("Output {actual} is below the minimum of {expected}")]
+    SlippageExceeded { expected: BigUint, actual: BigUint },
+    #[error("Swap would decrease the pool invariant")]
+    InvariantViolated,
+    #[error("Amount does not fit in 256 bits")]
+    AmountOverflow,
+    #[error("StableSwap math did not converge")]
+    ConvergenceFailed,
+    #[error("Position not found")]
+    PositionNotFound,
+    #[error("Fee of {0} bps would take the whole swap")]
+    InvalidFeeRate(u64),
 }
 
 // Extended Pool implementation for multi-asset pools
@@ -308,138 +1689,269 @@ impl Pool {
             PoolType::ConstantProduct => {
                 // Standard 2-token AMM
                 self.constant_product_swap(input_token, output_token, input_amount)
+                    .map(|(output_amount, _)| output_amount)
             }
             PoolType::StableSwap => {
                 // Curve-style stable swap for correlated assets
                 self.stable_swap(input_token, output_token, input_amount)
+                    .map(|(output_amount, _)| output_amount)
             }
             PoolType::ConcentratedLiquidity => {
-                // Uniswap V3 style with price ranges
-                self.concentrated_liquidity_swap(input_token, output_token, input_amount)
+                // Uniswap V3 style: positions on the tick map when there are
+                // any, otherwise a single price range, by default the full one
+                if !self.ticks.is_empty() {
+                    return self.tick_swap(input_token, output_token, input_amount);
+                }
+                let price_range = self.price_range.unwrap_or((0.0, f64::INFINITY));
+                self.concentrated_liquidity_swap(
+                    input_token,
+                    output_token,
+                    input_amount,
+                    price_range,
+                )
+            }
+            PoolType::Weighted { .. } => {
+                // Balancer-style constant product with unequal weights
+                self.weighted_swap(input_token, output_token, input_amount)
+                    .map(|(output_amount, _)| output_amount)
             }
         }
     }
 
-    fn stable_swap(
+    fn weighted_swap(
         &self,
         input_token: &str,
         output_token: &str,
         input_amount: &BigUint,
+    ) -> Result<(BigUint, SwapFee), SwapError> {
+        let weights = match &self.pool_type {
+            PoolType::Weighted { weights } if valid_weights(weights, self.tokens.len()) => weights,
+            _ => return Err(SwapError::UnsupportedPoolType),
+        };
+        let input_weight = weights[self.find_token_index(input_token)?];
+        let output_weight = weights[self.find_token_index(output_token)?];
+        let input_reserve = self
+            .reserves
+            .get(input_token)
+            .ok_or(SwapError::TokenNotFound)?;
+        let output_reserve = self
+            .reserves
+            .get(output_token)
+            .ok_or(SwapError::TokenNotFound)?;
+        let fee = self.apply_fee(input_token, input_amount)?;
+        let output_amount = Self::weighted_output(
+            input_reserve,
+            output_reserve,
+            input_weight,
+            output_weight,
+            &fee.amount_after_fee,
+        )?;
+        Ok((
+            output_amount,
+            SwapFee {
+                token: input_token.to_string(),
+                amount: fee.fee,
+            },
+        ))
+    }
+
+    // Weighted-curve output for an input already net of its fee, used by `PoolSim`
+    pub(crate) fn weighted_output(
+        input_reserve: &BigUint,
+        output_reserve: &BigUint,
+        input_weight: u64,
+        output_weight: u64,
+        amount_after_fee: &BigUint,
     ) -> Result<BigUint, SwapError> {
-        // Curve StableSwap invariant: A * n^n * sum(x_i) + D = A * D * n^n + D^(n+1) / (n^n * prod(x_i))
-        let n = self.tokens.len();
-        let a = BigUint::from(100u64); // Amplification parameter
+        if input_reserve.is_zero() || output_reserve.is_zero() {
+            return Err(SwapError::InsufficientLiquidity);
+        }
 
-        let mut balances: Vec<BigUint> = Vec::new();
-        let mut total_balance = BigUint::zero();
+        // out = out_reserve * (1 - (in_reserve / (in_reserve + in))^(w_in / w_out)).
+        // The power p/q is taken as the q-th root of the p-th power, in fixed
+        // point and rounded up, so the output rounds down.
+        let divisor = gcd(input_weight, output_weight);
+        let (p, q) = (
+            (input_weight / divisor) as u32,
+            (output_weight / divisor) as u32,
+        );
+        let numerator = input_reserve.pow(p) << (WEIGHTED_PRECISION_BITS * q as usize);
+        let scaled = ceil_div(&numerator, &(input_reserve + amount_after_fee).pow(p));
+        let mut ratio = scaled.nth_root(q);
+        if ratio.pow(q) < scaled {
+            ratio += 1u32;
+        }
 
-        for token in &self.tokens {
-            let balance = self
-                .reserves
-                .get(&token.address)
-                .unwrap_or(&BigUint::zero())
-                .clone();
-            balances.push(balance.clone());
-            total_balance += balance;
+        let one = BigUint::one() << WEIGHTED_PRECISION_BITS;
+        if ratio >= one {
+            return Ok(BigUint::zero());
         }
+        Ok((output_reserve * (one - ratio)) >> WEIGHTED_PRECISION_BITS)
+    }
 
-        let d = self.calculate_d(&balances, &a)?;
+    fn stable_swap(
+        &self,
+        input_token: &str,
+        output_token: &str,
+        input_amount: &BigUint,
+    ) -> Result<(BigUint, SwapFee), SwapError> {
+        // Borrowed in token order; quoting must not copy the reserves
+        let zero = BigUint::zero();
+        let balances: Vec<&BigUint> = self
+            .tokens
+            .iter()
+            .map(|token| self.reserves.get(&token.address).unwrap_or(&zero))
+            .collect();
 
         // Find input and output token indices
         let input_idx = self.find_token_index(input_token)?;
         let output_idx = self.find_token_index(output_token)?;
 
+        let (output_amount, fee) = Self::stable_swap_output(
+            &balances,
+            input_idx,
+            output_idx,
+            input_amount,
+            self.amplification,
+            self.fee_rate_for(input_token),
+        )?;
+        Ok((
+            output_amount,
+            SwapFee {
+                token: output_token.to_string(),
+                amount: fee,
+            },
+        ))
+    }
+
+    // StableSwap (output, fee) over balances ordered like `tokens`, shared
+    // with `PoolSim`. The fee is taken from the output
+    fn stable_swap_output<B: Borrow<BigUint>>(
+        balances: &[B],
+        input_idx: usize,
+        output_idx: usize,
+        input_amount: &BigUint,
+        amplification: u64,
+        fee_rate: u64,
+    ) -> Result<(BigUint, BigUint), SwapError> {
+        if fee_rate >= 10000 {
+            return Err(SwapError::InvalidFeeRate(fee_rate));
+        }
+
+        // Curve StableSwap invariant: A * n^n * sum(x_i) + D = A * D * n^n + D^(n+1) / (n^n * prod(x_i))
+        let a = BigUint::from(amplification);
+        let d = Self::calculate_d(balances, &a)?;
+
         // Calculate new balance after input
-        let mut new_balances = balances.clone();
-        new_balances[input_idx] += input_amount;
+        let new_input_balance = balances[input_idx].borrow() + input_amount;
+        let mut new_balances: Vec<&BigUint> = balances.iter().map(Borrow::borrow).collect();
+        new_balances[input_idx] = &new_input_balance;
 
         // Calculate what the output balance should be
-        let new_output_balance = self.calculate_y(&new_balances, output_idx, &d, &a)?;
-        let output_amount = &balances[output_idx] - &new_output_balance;
+        let new_output_balance = Self::calculate_y(&new_balances, output_idx, &d, &a)?;
+        let output_amount = balances[output_idx].borrow() - new_output_balance;
 
         // Apply fee
-        let fee_amount = (&output_amount * self.fee_rate) / BigUint::from(10000u64);
-        let output_after_fee = output_amount - fee_amount;
+        let fee_amount = &output_amount * fee_rate / 10000u64;
+        let output_after_fee = output_amount - &fee_amount;
+
+        // Newton's method that stopped short could hand out value for free:
+        // the balances after the trade must still hold D, up to rounding.
+        // D is undefined for an emptied reserve, which `apply_swap` refuses.
+        let new_output_balance = balances[output_idx].borrow() - &output_after_fee;
+        new_balances[output_idx] = &new_output_balance;
+        if !new_output_balance.is_zero()
+            && Self::calculate_d(&new_balances, &a)? + STABLE_D_TOLERANCE < d
+        {
+            return Err(SwapError::InvariantViolated);
+        }
 
-        Ok(output_after_fee)
+        Ok((output_after_fee, fee_amount))
     }
 
-    fn calculate_d(&self, balances: &[BigUint], a: &BigUint) -> Result<BigUint, SwapError> {
-        let n = BigUint::from(balances.len());
+    fn calculate_d<B: Borrow<BigUint>>(balances: &[B], a: &BigUint) -> Result<BigUint, SwapError> {
+        let n = balances.len();
         let mut s = BigUint::zero();
 
         for balance in balances {
-            s += balance;
+            s += balance.borrow();
         }
 
         if s.is_zero() {
             return Ok(BigUint::zero());
         }
 
-        let mut d = s.clone();
-        let ann = a * &n.pow(balances.len() as u32);
+        let one = BigUint::one();
+        let ann = a * n.pow(n as u32);
+        let ann_s = &ann * &s;
+        let ann_minus_one = &ann - &one;
+
+        let mut d = s;
 
         // Newton's method to solve for D
-        for _ in 0..255 {
+        for _ in 0..NEWTON_ITERATIONS {
             let mut dp = d.clone();
             for balance in balances {
-                dp = (&dp * &d) / (&n * balance);
+                dp = dp * &d / (balance.borrow() * n);
             }
 
-            let d_prev = d.clone();
-            d = ((&ann * &s + &dp * &n) * &d)
-                / ((&ann - BigUint::one()) * &d + (&n + BigUint::one()) * &dp);
+            let next = (&ann_s + &dp * n) * &d / (&ann_minus_one * &d + dp * (n + 1));
+            let d_prev = std::mem::replace(&mut d, next);
 
-            if d > d_prev {
-                if &d - &d_prev <= BigUint::one() {
-                    break;
-                }
-            } else if &d_prev - &d <= BigUint::one() {
-                break;
+            // Both arms reuse d_prev's buffer instead of allocating
+            let converged = if d > d_prev {
+                d <= d_prev + 1u32
+            } else {
+                d_prev - &d <= one
+            };
+            if converged {
+                return Ok(d);
             }
         }
 
-        Ok(d)
+        Err(SwapError::ConvergenceFailed)
     }
 
-    fn calculate_y(
-        &self,
-        balances: &[BigUint],
+    fn calculate_y<B: Borrow<BigUint>>(
+        balances: &[B],
         token_index: usize,
         d: &BigUint,
         a: &BigUint,
     ) -> Result<BigUint, SwapError> {
-        let n = BigUint::from(balances.len());
-        let ann = a * &n.pow(balances.len() as u32);
+        let n = balances.len();
+        let ann = a * n.pow(n as u32);
 
         let mut c = d.clone();
         let mut s = BigUint::zero();
 
         for (i, balance) in balances.iter().enumerate() {
             if i != token_index {
+                let balance = balance.borrow();
                 s += balance;
-                c = (&c * d) / (&n * balance);
+                c = c * d / (balance * n);
             }
         }
 
-        c = (&c * d) / (&ann * &n);
-        let b = &s + d / &ann;
+        c = c * d / (&ann * n);
+        let b = s + d / &ann;
 
+        let one = BigUint::one();
         let mut y = d.clone();
-        for _ in 0..255 {
-            let y_prev = y.clone();
-            y = (&y * &y + &c) / (&y * BigUint::from(2u32) + &b - d);
-
-            if y > y_prev {
-                if &y - &y_prev <= BigUint::one() {
-                    break;
-                }
-            } else if &y_prev - &y <= BigUint::one() {
-                break;
+        for _ in 0..NEWTON_ITERATIONS {
+            let next = (&y * &y + &c) / (&y * 2u32 + &b - d);
+            let y_prev = std::mem::replace(&mut y, next);
+
+            let converged = if y > y_prev {
+                y <= y_prev + 1u32
+            } else {
+                y_prev - &y <= one
+            };
+            if converged {
+                return Ok(y);
             }
         }
 
-        Ok(y)
+        Err(SwapError::ConvergenceFailed)
     }
 
     fn find_token_index(&self, token_address: &str) -> Result<usize, SwapError> {
@@ -454,31 +1966,116 @@ impl Pool {
         input_token: &str,
         output_token: &str,
         input_amount: &BigUint,
+        price_range: (f64, f64),
     ) -> Result<BigUint, SwapError> {
-        // Simplified Uniswap V3 style calculation
-        // In reality, this would involve complex tick calculations
+        self.range_swap_sqrt_price(input_token, output_token, input_amount, price_range)
+            .map(|(_, output)| output)
+    }
 
-        let current_price = self.get_current_price(input_token, output_token)?;
-        let sqrt_price = (current_price.sqrt() * 2f64.powi(96)) as u128;
+    // `concentrated_liquidity_swap` together with the Q64.96 sqrt price of
+    // the input in the output that it leaves
+    pub(crate) fn range_swap_sqrt_price(
+        &self,
+        input_token: &str,
+        output_token: &str,
+        input_amount: &BigUint,
+        price_range: (f64, f64),
+    ) -> Result<(BigUint, BigUint), SwapError> {
+        // Simplified Uniswap V3 style calculation: inside the range the
+        // position trades like a constant-product curve over virtual
+        // reserves with liquidity L = sqrt(x * y). `price_range` is quoted
+        // as the price of the pool's earlier token in the later one.
+        let input_is_base =
+            self.find_token_index(input_token)? < self.find_token_index(output_token)?;
+        let input_reserve = self
+            .reserves
+            .get(input_token)
+            .ok_or(SwapError::TokenNotFound)?;
+        let output_reserve = self
+            .reserves
+            .get(output_token)
+            .ok_or(SwapError::TokenNotFound)?;
+        if input_reserve.is_zero() || output_reserve.is_zero() {
+            return Err(SwapError::InsufficientLiquidity);
+        }
 
-        // Calculate price impact based on concentrated liquidity
-        let liquidity = &self.total_supply;
-        let price_impact = input_amount / liquidity;
+        // Liquidity only trades while the price sits inside its range. The
+        // price is the input's in the output, as `numerator / denominator`.
+        let lower = price_bound(price_range.0)?;
+        let upper = price_bound(price_range.1)?;
+        let in_range = |numerator: &BigUint, denominator: &BigUint| {
+            let (numerator, denominator) = if input_is_base {
+                (numerator, denominator)
+            } else {
+                (denominator, numerator)
+            };
+            lower
+                .as_ref()
+                .is_none_or(|(n, d)| n * denominator <= numerator * d)
+                && upper
+                    .as_ref()
+                    .is_none_or(|(n, d)| numerator * d <= n * denominator)
+        };
+        if !in_range(output_reserve, input_reserve) {
+            return Err(SwapError::PriceOutOfRange);
+        }
 
-        // Calculate new price after swap
-        let new_sqrt_price = sqrt_price + price_impact.to_string().parse::<u128>().unwrap_or(0);
-        let new_price = (new_sqrt_price as f64 / 2f64.powi(96)).powi(2);
+        // The reserves are the range's virtual reserves, so they fix its
+        // liquidity; LP supply can differ once tokens are minted or burned
+        let liquidity = &sqrt(&(input_reserve * output_reserve));
+
+        // Fee is taken on the way in, as for the other curves
+        let amount_after_fee = input_amount - self.swap_fee(input_token, input_amount)?;
+
+        // Selling the input lowers its price: 1/sqrt(P') = 1/sqrt(P) + dx/L,
+        // i.e. sqrt(P') = L * sqrt(P) / (L + dx * sqrt(P)). Rounding sqrt(P')
+        // up rounds the output down, in the pool's favour.
+        let sqrt_price = sqrt(&((output_reserve << (2 * Q96_BITS)) / input_reserve));
+        let numerator = (liquidity * &sqrt_price) << Q96_BITS;
+        let denominator = (liquidity << Q96_BITS) + &amount_after_fee * &sqrt_price;
+        let new_sqrt_price = ceil_div(&numerator, &denominator);
+        if !in_range(
+            &(&new_sqrt_price * &new_sqrt_price),
+            &(BigUint::one() << (2 * Q96_BITS)),
+        ) {
+            return Err(SwapError::PriceOutOfRange);
+        }
 
-        // Calculate output amount
-        let price_ratio = new_price / current_price;
-        let output_amount = input_amount * BigUint::from((1.0 / price_ratio) as u64);
+        // dy = L * (sqrt(P) - sqrt(P'))
+        let output = (liquidity * (sqrt_price - &new_sqrt_price)) >> Q96_BITS;
+        Ok((new_sqrt_price, output))
+    }
+}
 
-        // Apply fee
-        let fee_amount = (&output_amount * self.fee_rate) / BigUint::from(10000u64);
-        let output_after_fee = output_amount - fee_amount;
+// Fractional bits of the Q64.96 sqrt prices used by concentrated liquidity
+const Q96_BITS: usize = 96;
 
-        Ok(output_after_fee)
+// Exact value of a price range bound as `(numerator, denominator)`; `None`
+// when the bound does not limit the price (zero or below, or infinite).
+fn price_bound(price: f64) -> Result<Option<(BigUint, BigUint)>, SwapError> {
+    if price.is_nan() {
+        return Err(SwapError::PriceOutOfRange);
+    }
+    if price <= 0.0 || price.is_infinite() {
+        return Ok(None);
     }
+
+    // A finite f64 is exactly mantissa * 2^exponent
+    let bits = price.to_bits();
+    let biased_exponent = ((bits >> 52) & 0x7ff) as i64;
+    let fraction = bits & ((1u64 << 52) - 1);
+    let (mantissa, exponent) = if biased_exponent == 0 {
+        (fraction, -1074)
+    } else {
+        (fraction | (1u64 << 52), biased_exponent - 1075)
+    };
+
+    let mantissa = BigUint::from(mantissa);
+    Ok(Some(if exponent >= 0 {
+        (mantissa << exponent as usize, BigUint::one())
+    } else {
+        (mantissa, BigUint::one() << exponent.unsigned_abs() as usize)
+    }))
 }
 
 #[cfg(test)]
@@ -572,6 +2169,29 @@ mod tests {
         assert!(pool.fee_rate <= 1000); // Should be capped at 10%
     }
 
+    #[test]
+    fn test_gross_output_adds_back_the_fee() {
+        let mut pool = create_sample_pool();
+        let reserve = BigUint::from(10u64).pow(18);
+        pool.set_reserves(HashMap::from([
+            ("ETH".to_string(), reserve.clone()),
+            ("USDC".to_string(), reserve),
+        ]))
+        .unwrap();
+        let input = BigUint::from(10_000u64);
+
+        let gross = pool.gross_output("ETH", "USDC", &input).unwrap();
+        let net = pool.calculate_swap_output("ETH", "USDC", &input).unwrap();
+        assert!(gross >= net);
+        // At a 1:1 price and negligible impact the cost is the fee itself
+        assert_eq!(&gross - &net, pool.swap_fee("ETH", &input).unwrap());
+
+        pool.pool_type = PoolType::StableSwap;
+        let gross = pool.gross_output("ETH", "USDC", &input).unwrap();
+        assert!(gross > pool.calculate_swap_output("ETH", "USDC", &input).unwrap());
+        assert_eq!(pool.fee_rate, 300);
+    }
+
     #[test]
     fn test_price_calculation() {
         let pool = create_sample_pool();
@@ -581,6 +2201,1619 @@ mod tests {
         assert_eq!(price, 2.0); // 2000 USDC / 1000 ETH = 2.0
     }
 
+    #[test]
+    fn test_normalized_price_accounts_for_decimals() {
+        let mut pool = create_sample_pool();
+        // 1 ETH (18 decimals) against 2000 USDC (6 decimals)
+        pool.reserves
+            .insert("ETH".to_string(), BigUint::from(10u64).pow(18));
+        pool.reserves
+            .insert("USDC".to_string(), BigUint::from(2_000_000_000u64));
+
+        assert_eq!(pool.get_price_normalized("ETH", "USDC").unwrap(), 2000.0);
+        assert_eq!(pool.get_price_normalized("usdc", "eth").unwrap(), 0.0005);
+        assert!(pool.get_current_price("ETH", "USDC").unwrap() < 1e-8);
+    }
+
+    #[test]
+    fn test_biguint_to_f64_saturates_beyond_range() {
+        assert_eq!(biguint_to_f64(&BigUint::from(u64::MAX)), u64::MAX as f64);
+        let above = BigUint::from(u64::MAX) * 4u64;
+        assert_eq!(biguint_to_f64(&above), u64::MAX as f64 * 4.0);
+        assert_eq!(biguint_to_f64(&(BigUint::one() << 2000)), f64::MAX);
+    }
+
+    #[test]
+    fn test_price_with_reserves_beyond_u64() {
+        let mut pool = create_sample_pool();
+        pool.reserves
+            .insert("ETH".to_string(), BigUint::from(u64::MAX));
+        pool.reserves
+            .insert("USDC".to_string(), BigUint::from(u64::MAX) * 3u64);
+        assert_eq!(pool.get_current_price("ETH", "USDC").unwrap(), 3.0);
+
+        // Both reserves beyond f64 range still give their ratio
+        pool.reserves
+            .insert("ETH".to_string(), BigUint::one() << 1100);
+        pool.reserves
+            .insert("USDC".to_string(), BigUint::one() << 1101);
+        assert_eq!(pool.get_current_price("ETH", "USDC").unwrap(), 2.0);
+        assert_eq!(pool.get_current_price("USDC", "ETH").unwrap(), 0.5);
+    }
+
+    #[test]
+    fn test_cumulative_fees_track_executed_swaps() {
+        let mut pool = create_sample_pool();
+
+        let mut expected_fees = BigUint::zero();
+        for amount in [100u64, 37, 250] {
+            let input_amount = BigUint::from(amount);
+            // 3% fee taken from the input, rounded in the pool's favour
+            expected_fees += &input_amount - (&input_amount * 9700u64) / 10000u64;
+            pool.execute_swap("ETH", "USDC", &input_amount).unwrap();
+        }
+
+        assert_eq!(pool.cumulative_fees["ETH"], expected_fees);
+        assert!(!pool.cumulative_fees.contains_key("USDC"));
+        assert_eq!(pool.reserves["ETH"], BigUint::from(1387u64));
+    }
+
+    #[test]
+    fn test_stable_swap_fees_are_recorded_in_the_output_token() {
+        let mut pool = create_sample_pool();
+        pool.pool_type = PoolType::StableSwap;
+        pool.fee_rate = 4;
+        let input_amount = BigUint::from(100u64);
+
+        let (quoted, fee) = pool.quote_with_fee("ETH", "USDC", &input_amount).unwrap();
+        assert_eq!(fee.token, "USDC");
+        // 4 bps of the pre-fee output, which the quote is net of
+        assert_eq!(fee.amount, (&quoted + &fee.amount) * 4u64 / 10000u64);
+
+        let receipt = pool
+            .execute_swap_with_receipt("ETH", "USDC", &input_amount)
+            .unwrap();
+        assert_eq!(receipt.output_amount, quoted);
+        assert_eq!(receipt.fee, fee.amount);
+        assert_eq!(receipt.fee_token, "USDC");
+        assert_eq!(pool.cumulative_fees.get("USDC"), Some(&fee.amount));
+        assert!(!pool.cumulative_fees.contains_key("ETH"));
+    }
+
+    #[test]
+    fn test_swap_receipt_matches_executed_swap() {
+        let mut pool = create_sample_pool();
+        let input_amount = BigUint::from(100u64);
+
+        let expected_output = pool
+            .calculate_swap_output("ETH", "USDC", &input_amount)
+            .unwrap();
+        let receipt = pool
+            .execute_swap_with_receipt("ETH", "USDC", &input_amount)
+            .unwrap();
+
+        assert_eq!(receipt.pool_id, "ETH-USDC");
+        assert_eq!(receipt.input_token, "ETH");
+        assert_eq!(receipt.output_token, "USDC");
+        assert_eq!(receipt.input_amount, input_amount);
+        assert_eq!(receipt.output_amount, expected_output);
+        assert_eq!(receipt.fee, BigUint::from(3u64));
+        assert_eq!(receipt.resulting_reserves, pool.reserves);
+        assert!(receipt.timestamp > 0);
+
+        let second = pool
+            .execute_swap_with_receipt("USDC", "ETH", &input_amount)
+            .unwrap();
+        assert_ne!(receipt.id, second.id);
+    }
+
+    #[test]
+    fn test_detailed_swap_reports_post_swap_price() {
+        let mut pool = create_sample_pool();
+        let before = pool.spot_price("ETH", "USDC").unwrap();
+        let input_amount = BigUint::from(100u64);
+        let expected_output = pool
+            .calculate_swap_output("ETH", "USDC", &input_amount)
+            .unwrap();
+
+        let (output, price) = pool
+            .execute_swap_detailed("ETH", "USDC", &input_amount)
+            .unwrap();
+
+        assert_eq!(output, expected_output);
+        assert_eq!(price, pool.spot_price("ETH", "USDC").unwrap());
+        assert!(price < before);
+    }
+
+    #[test]
+    fn test_price_curve_matches_effective_price() {
+        let pool = create_sample_pool();
+        let curve = pool.price_curve("ETH", "USDC");
+
+        for amount in [1u64, 10, 100, 500, 999] {
+            let input_amount = BigUint::from(amount);
+            let expected = pool.effective_price("ETH", "USDC", &input_amount).unwrap();
+            assert_eq!(curve(&input_amount), expected);
+        }
+
+        // Larger trades execute at a worse price
+        assert!(curve(&BigUint::from(10u64)) > curve(&BigUint::from(500u64)));
+        assert_eq!(curve(&BigUint::zero()), Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_reserves_sum_normalized() {
+        let pool = create_stable_pool();
+
+        // 1000 USDC + 2000 USDT + 3000 DAI, all scaled to 18 decimals
+        let expected = BigUint::from(6000u64) * BigUint::from(10u64).pow(18);
+        assert_eq!(pool.reserves_sum_normalized(), expected);
+    }
+
+    #[test]
+    fn test_describe_formats_reserves_in_whole_tokens() {
+        let mut pool = create_sample_pool();
+        pool.reserves.insert(
+            "ETH".to_string(),
+            BigUint::from(15u64) * BigUint::from(10u64).pow(17),
+        );
+        pool.reserves
+            .insert("USDC".to_string(), BigUint::from(2_000_000_000u64));
+        pool.total_supply = BigUint::from(1414u64);
+        pool.fee_rate = 30;
+
+        assert_eq!(
+            pool.describe(),
+            "ETH-USDC [ConstantProduct, fee=30bps, reserves: ETH=1.5, USDC=2000, supply=1414]"
+        );
+    }
+
+    #[test]
+    fn test_pool_type_string_round_trip() {
+        for pool_type in [
+            PoolType::ConstantProduct,
+            PoolType::StableSwap,
+            PoolType::ConcentratedLiquidity,
+            PoolType::Weighted {
+                weights: vec![80, 20],
+            },
+        ] {
+            let parsed: PoolType = pool_type.to_string().parse().unwrap();
+            assert_eq!(parsed, pool_type);
+        }
+
+        assert_eq!(PoolType::StableSwap.to_string(), "stable_swap");
+    }
+
+    #[test]
+    fn test_try_new_rejects_fee_rate_of_100_percent_or_more() {
+        let pool = create_sample_pool();
+        let try_new = |fee_rate| {
+            Pool::try_new(
+                pool.id.clone(),
+                pool.tokens.clone(),
+                pool.reserves.clone(),
+                fee_rate,
+                PoolType::ConstantProduct,
+            )
+        };
+
+        assert!(matches!(
+            try_new(10001),
+            Err(PoolError::InvalidFeeRate(10001))
+        ));
+        assert!(matches!(
+            try_new(10000),
+            Err(PoolError::InvalidFeeRate(10000))
+        ));
+        assert_eq!(try_new(9999).unwrap().fee_rate, 9999);
+
+        // A pool corrupted past the check refuses to swap instead of
+        // keeping the whole input as fee
+        let mut corrupt = pool.clone();
+        corrupt.fee_rate = 10001;
+        let amount = BigUint::from(100u64);
+        assert!(matches!(
+            corrupt.calculate_swap_output("ETH", "USDC", &amount),
+            Err(SwapError::InvalidFeeRate(10001))
+        ));
+        assert!(corrupt
+            .calculate_input_for_output("ETH", "USDC", &amount)
+            .is_err());
+        corrupt.pool_type = PoolType::StableSwap;
+        assert!(matches!(
+            corrupt.calculate_swap_output("ETH", "USDC", &amount),
+            Err(SwapError::InvalidFeeRate(10001))
+        ));
+        corrupt.fee_rate = 10000;
+        assert!(matches!(
+            corrupt.calculate_swap_output("ETH", "USDC", &amount),
+            Err(SwapError::InvalidFeeRate(10000))
+        ));
+    }
+
+    #[test]
+    fn test_try_new_requires_a_reserve_per_token() {
+        let pool = create_sample_pool();
+        let try_new = |reserves: HashMap<String, BigUint>| {
+            Pool::try_new(
+                pool.id.clone(),
+                pool.tokens.clone(),
+                reserves,
+                30,
+                PoolType::ConstantProduct,
+            )
+        };
+
+        let mut missing = pool.reserves.clone();
+        missing.remove("USDC");
+        match try_new(missing) {
+            Err(PoolError::ReserveTokenMismatch { missing, extra }) => {
+                assert_eq!(missing, vec!["USDC"]);
+                assert!(extra.is_empty());
+            }
+            other => panic!("expected a mismatch, got {:?}", other),
+        }
+
+        let mut extra = pool.reserves.clone();
+        extra.insert("DAI".to_string(), BigUint::from(5u64));
+        match try_new(extra) {
+            Err(PoolError::ReserveTokenMismatch { missing, extra }) => {
+                assert!(missing.is_empty());
+                assert_eq!(extra, vec!["DAI"]);
+            }
+            other => panic!("expected a mismatch, got {:?}", other),
+        }
+
+        assert!(try_new(pool.reserves.clone()).is_ok());
+    }
+
+    #[test]
+    fn test_stable_swap_preserves_d() {
+        let unit = BigUint::from(10u64).pow(18);
+        let tokens: Vec<Token> = ["USDC", "USDT", "DAI"]
+            .iter()
+            .map(|symbol| Token {
+                address: symbol.to_string(),
+                symbol: symbol.to_string(),
+                decimals: 18,
+            })
+            .collect();
+        let balances = [1_000_000u64, 1_000_100, 999_900].map(|b| &unit * b);
+        let reserves = tokens
+            .iter()
+            .zip(&balances)
+            .map(|(token, balance)| (token.address.clone(), balance.clone()))
+            .collect();
+        let mut pool = Pool::new(
+            "STABLE-3".to_string(),
+            tokens,
+            reserves,
+            0,
+            PoolType::StableSwap,
+        );
+        let a = BigUint::from(pool.amplification);
+        let d_of = |pool: &Pool| {
+            let balances: Vec<&BigUint> = pool
+                .tokens
+                .iter()
+                .map(|t| &pool.reserves[&t.address])
+                .collect();
+            Pool::calculate_d(&balances, &a).unwrap()
+        };
+        let d_before = d_of(&pool);
+
+        pool.execute_swap("USDC", "DAI", &(&unit * 5_000u64))
+            .unwrap();
+
+        let d_after = d_of(&pool);
+        let drift = if d_after > d_before {
+            &d_after - &d_before
+        } else {
+            &d_before - &d_after
+        };
+        assert!(drift <= BigUint::one(), "D moved by {}", drift);
+    }
+
+    #[test]
+    fn test_default_fee_per_pool_type() {
+        assert_eq!(PoolType::ConstantProduct.default_fee_bps(), 30);
+        assert_eq!(PoolType::ConcentratedLiquidity.default_fee_bps(), 30);
+        assert_eq!(PoolType::StableSwap.default_fee_bps(), 4);
+    }
+
+    #[test]
+    fn test_pool_type_rejects_unknown_string() {
+        let err = "ConstantProduct".parse::<PoolType>().unwrap_err();
+        assert_eq!(err.0, "ConstantProduct");
+        assert!(err.to_string().contains("constant_product"));
+    }
+
+    #[test]
+    fn test_checked_biguint_to_f64_guards_precision() {
+        let limit = BigUint::one() << 53u32;
+        assert_eq!(checked_biguint_to_f64(&limit).unwrap(), 9007199254740992.0);
+
+        // A raw 18-decimal reserve is far beyond the exact range
+        let reserve = BigUint::from(10u64).pow(18) * BigUint::from(5000u64);
+        let err = checked_biguint_to_f64(&reserve).unwrap_err();
+        assert_eq!(err.bits, reserve.bits());
+
+        let just_over = &limit + BigUint::one();
+        assert!(checked_biguint_to_f64(&just_over).is_err());
+    }
+
+    #[test]
+    fn test_stable_swap_between_any_two_of_three_tokens() {
+        let unit = BigUint::from(10u64).pow(18);
+        let mut pool = create_stable_pool();
+        for reserve in pool.reserves.values_mut() {
+            *reserve = BigUint::from(1_000_000u64) * &unit;
+        }
+
+        let input_amount = BigUint::from(1000u64) * &unit;
+        let output = pool
+            .calculate_swap_output("DAI", "USDT", &input_amount)
+            .unwrap();
+
+        // Balanced stable pool: close to 1:1 minus the 4 bps fee
+        assert!(output < input_amount);
+        assert!(output > BigUint::from(9995u64) * &unit / 10u64);
+    }
+
+    #[test]
+    fn test_stable_pool_starts_with_invariant_supply() {
+        let pool = create_stable_pool();
+        let balances: Vec<&BigUint> = pool
+            .tokens
+            .iter()
+            .map(|token| &pool.reserves[&token.address])
+            .collect();
+
+        let d = Pool::calculate_d(&balances, &BigUint::from(100u64)).unwrap();
+        assert!(!d.is_zero());
+        assert_eq!(pool.total_supply, d);
+
+        let amplified = pool.clone().with_amplification(1000).unwrap();
+        let d = Pool::calculate_d(&balances, &BigUint::from(1000u64)).unwrap();
+        assert_eq!(amplified.total_supply, d);
+    }
+
+    #[test]
+    fn test_price_matrix_is_reciprocal() {
+        let pool = create_stable_pool();
+        let prices = pool.price_matrix();
+        assert_eq!(prices.len(), 6);
+
+        // Each price is truncated to DECIMAL_SCALE digits, so the product
+        // may fall short of one by that much per unit of the other price
+        let ulp = Decimal::new(1, DECIMAL_SCALE);
+        for ((base, quote), price) in &prices {
+            let inverse = prices[&(quote.clone(), base.clone())];
+            let product = *price * inverse;
+            assert!(product <= Decimal::ONE, "{base}/{quote}");
+            assert!(
+                Decimal::ONE - product <= (*price + inverse) * ulp,
+                "{base}/{quote}: {product}"
+            );
+        }
+        assert_eq!(
+            prices[&("USDC".to_string(), "USDT".to_string())],
+            Decimal::from(2)
+        );
+    }
+
+    #[test]
+    fn test_newton_reports_non_convergence() {
+        // So lopsided that Newton's method creeps toward D for far longer
+        // than its iteration budget
+        let balances = [BigUint::one(), BigUint::from(10u64).pow(200)];
+        assert!(matches!(
+            Pool::calculate_d(&balances, &BigUint::one()),
+            Err(SwapError::ConvergenceFailed)
+        ));
+
+        let tokens: Vec<Token> = ["USDC", "USDT"]
+            .iter()
+            .map(|symbol| Token {
+                address: symbol.to_string(),
+                symbol: symbol.to_string(),
+                decimals: 18,
+            })
+            .collect();
+        let reserves = tokens
+            .iter()
+            .map(|token| token.address.clone())
+            .zip(balances)
+            .collect();
+        let pool = Pool::new("USD".to_string(), tokens, reserves, 4, PoolType::StableSwap)
+            .with_amplification(1)
+            .unwrap();
+        assert!(matches!(
+            pool.calculate_swap_output("USDC", "USDT", &BigUint::from(1000u64)),
+            Err(SwapError::ConvergenceFailed)
+        ));
+    }
+
+    #[test]
+    fn test_amplification_bounds() {
+        let mut pool = create_stable_pool();
+        assert_eq!(pool.amplification, 100);
+        for reserve in pool.reserves.values_mut() {
+            *reserve = BigUint::from(1_000_000u64);
+        }
+
+        for valid in [MIN_AMPLIFICATION, MAX_AMPLIFICATION] {
+            let pool = pool.clone().with_amplification(valid).unwrap();
+            assert_eq!(pool.amplification, valid);
+        }
+        for invalid in [0, MAX_AMPLIFICATION + 1] {
+            assert!(matches!(
+                pool.clone().with_amplification(invalid),
+                Err(PoolError::InvalidAmplification(a)) if a == invalid
+            ));
+        }
+
+        // A flatter curve means less slippage on a balanced pool
+        let input = BigUint::from(100_000u64);
+        let low = pool.clone().with_amplification(1).unwrap();
+        let high = pool.clone().with_amplification(1000).unwrap();
+        assert!(
+            high.calculate_swap_output("USDC", "DAI", &input).unwrap()
+                > low.calculate_swap_output("USDC", "DAI", &input).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_higher_amplification_flattens_the_curve() {
+        let unit = BigUint::from(10u64).pow(18);
+        let tokens: Vec<Token> = ["USDC", "USDT"]
+            .iter()
+            .map(|symbol| Token {
+                address: symbol.to_string(),
+                symbol: symbol.to_string(),
+                decimals: 18,
+            })
+            .collect();
+        // Slightly off balance, as stable pools usually are
+        let reserves = [("USDC", 1_000_000u64), ("USDT", 1_010_000)]
+            .iter()
+            .map(|(token, amount)| (token.to_string(), BigUint::from(*amount) * &unit))
+            .collect();
+        let pool = Pool::new("USD".to_string(), tokens, reserves, 0, PoolType::StableSwap);
+        let input = BigUint::from(50_000u64) * &unit;
+
+        // Slippage is what the trade falls short of 1:1
+        let slippage: Vec<BigUint> = [1, 10, 100, 1000]
+            .iter()
+            .map(|&a| {
+                let pool = pool.clone().with_amplification(a).unwrap();
+                let output = pool.calculate_swap_output("USDC", "USDT", &input).unwrap();
+                &input - output
+            })
+            .collect();
+        assert!(slippage.windows(2).all(|pair| pair[1] < pair[0]));
+        // Near balance and at high A the pool trades almost 1:1
+        assert!(slippage[3] < &input / 1000u64);
+    }
+
+    #[test]
+    fn test_stable_quotes_are_pinned() {
+        let unit = BigUint::from(10u64).pow(18);
+        let tokens: Vec<Token> = ["USDC", "USDT", "DAI"]
+            .iter()
+            .map(|symbol| Token {
+                address: symbol.to_string(),
+                symbol: symbol.to_string(),
+                decimals: 18,
+            })
+            .collect();
+        let reserves = [
+            ("USDC", 1_000_000u64),
+            ("USDT", 2_500_000),
+            ("DAI", 700_000),
+        ]
+        .iter()
+        .map(|(token, amount)| (token.to_string(), BigUint::from(*amount) * &unit))
+        .collect();
+        let pool = Pool::new("USD".to_string(), tokens, reserves, 4, PoolType::StableSwap);
+        let amplified = pool.clone().with_amplification(2000).unwrap();
+
+        // Outputs of the implementation before quoting stopped copying reserves
+        let quotes = [
+            (
+                "USDC",
+                "DAI",
+                unit.clone(),
+                "998559580407274627",
+                "999547765341479589",
+            ),
+            (
+                "DAI",
+                "USDT",
+                BigUint::from(50_000u64) * &unit,
+                "50095894414479356037917",
+                "49985804565060130779726",
+            ),
+            ("USDT", "USDC", BigUint::from(12345u64), "12324", "12341"),
+        ];
+        for (input, output, amount, expected, expected_amplified) in quotes {
+            let quote = |pool: &Pool| {
+                pool.calculate_swap_output(input, output, &amount)
+                    .unwrap()
+                    .to_string()
+            };
+            assert_eq!(quote(&pool), expected);
+            assert_eq!(quote(&amplified), expected_amplified);
+        }
+    }
+
+    #[test]
+    fn test_concentrated_swap_in_range() {
+        let mut pool = create_sample_pool();
+        pool.pool_type = PoolType::ConcentratedLiquidity;
+        pool.price_range = Some((1.0, 4.0)); // current price is 2.0
+
+        let output = pool
+            .calculate_multi_asset_swap("ETH", "USDC", &BigUint::from(10u64))
+            .unwrap();
+        assert!(output > BigUint::zero() && output < BigUint::from(20u64));
+
+        // Selling the later token is checked against the same orientation
+        assert!(pool
+            .calculate_multi_asset_swap("USDC", "ETH", &BigUint::from(10u64))
+            .is_ok());
+
+        // The explicit-range entry point shares the same implementation
+        let explicit = pool
+            .calculate_concentrated_liquidity_swap("ETH", "USDC", &BigUint::from(10u64), (1.0, 4.0))
+            .unwrap();
+        assert_eq!(output, explicit);
+    }
+
+    #[test]
+    fn test_concentrated_swap_matches_reference() {
+        let concentrated = |scale: u32| {
+            let unit = BigUint::from(10u64).pow(scale);
+            let mut pool = create_sample_pool();
+            pool.pool_type = PoolType::ConcentratedLiquidity;
+            pool.fee_rate = 30;
+            let reserves = [("ETH", 1000u64), ("USDC", 2000)]
+                .iter()
+                .map(|(token, amount)| (token.to_string(), BigUint::from(*amount) * &unit))
+                .collect();
+            pool.set_reserves(reserves).unwrap();
+            pool
+        };
+
+        // L * (sqrt(P) - sqrt(P')) worked out to 80 digits is
+        // 1992013962079806432.986..., which the integer math rounds down
+        let pool = concentrated(18);
+        let input = BigUint::from(10u64).pow(18);
+        let output = pool
+            .calculate_multi_asset_swap("ETH", "USDC", &input)
+            .unwrap();
+        assert_eq!(output, BigUint::from(1_992_013_962_079_806_432u64));
+        for _ in 0..10 {
+            assert_eq!(
+                pool.calculate_multi_asset_swap("ETH", "USDC", &input)
+                    .unwrap(),
+                output
+            );
+        }
+
+        // Amounts far beyond u128 scale with the pool instead of saturating
+        let pool = concentrated(60);
+        let input = BigUint::from(10u64).pow(60);
+        let large = pool
+            .calculate_multi_asset_swap("ETH", "USDC", &input)
+            .unwrap();
+        assert_eq!(large.to_string()[..18], output.to_string()[..18]);
+    }
+
+    #[test]
+    fn test_concentrated_range_liquidity_ignores_lp_supply() {
+        let mut pool = create_sample_pool();
+        pool.pool_type = PoolType::ConcentratedLiquidity;
+        let amount = BigUint::from(10u64);
+        let output = pool
+            .calculate_multi_asset_swap("ETH", "USDC", &amount)
+            .unwrap();
+
+        // Supply that has drifted from the reserves leaves the quote alone
+        for supply in [BigUint::zero(), &pool.total_supply * 100u32] {
+            let mut drifted = pool.clone();
+            drifted.total_supply = supply;
+            assert_eq!(
+                drifted
+                    .calculate_multi_asset_swap("ETH", "USDC", &amount)
+                    .unwrap(),
+                output
+            );
+        }
+    }
+
+    #[test]
+    fn test_concentrated_swap_out_of_range() {
+        let mut pool = create_sample_pool();
+        pool.pool_type = PoolType::ConcentratedLiquidity;
+        pool.price_range = Some((3.0, 4.0));
+
+        let result = pool.calculate_multi_asset_swap("ETH", "USDC", &BigUint::from(10u64));
+        assert!(matches!(result, Err(SwapError::PriceOutOfRange)));
+
+        // A swap that would push the price below the range is rejected too
+        pool.price_range = Some((1.9, 4.0));
+        let result = pool.calculate_multi_asset_swap("ETH", "USDC", &BigUint::from(100u64));
+        assert!(matches!(result, Err(SwapError::PriceOutOfRange)));
+    }
+
+    #[test]
+    fn test_directional_fee_rates_apply_by_input_token() {
+        let mut pool = create_sample_pool();
+        let balanced = ["ETH", "USDC"]
+            .iter()
+            .map(|t| (t.to_string(), BigUint::from(1_000_000u64)))
+            .collect();
+        pool.set_reserves(balanced).unwrap();
+        let mut rates = HashMap::new();
+        rates.insert("USDC".to_string(), 30);
+        pool.directional_fee_rates = Some(rates);
+
+        assert_eq!(pool.fee_rate_for("ETH"), 300);
+        assert_eq!(pool.fee_rate_for("USDC"), 30);
+
+        let amount = BigUint::from(10_000u64);
+        let eth_to_usdc = pool
+            .clone()
+            .execute_swap_with_receipt("ETH", "USDC", &amount)
+            .unwrap();
+        let usdc_to_eth = pool
+            .clone()
+            .execute_swap_with_receipt("USDC", "ETH", &amount)
+            .unwrap();
+
+        assert_eq!(eth_to_usdc.fee, BigUint::from(300u64));
+        assert_eq!(usdc_to_eth.fee, BigUint::from(30u64));
+        assert!(eth_to_usdc.output_amount < usdc_to_eth.output_amount);
+    }
+
+    #[test]
+    fn test_fee_strategy_overrides_fee_rate() {
+        let balanced = ["ETH", "USDC"]
+            .iter()
+            .map(|t| (t.to_string(), BigUint::from(1_000_000u64)))
+            .collect();
+        let mut pool = create_sample_pool();
+        pool.set_reserves(balanced).unwrap();
+        let pool = pool.with_fee_strategy(fees::AsymmetricFee {
+            forward_bps: 100,
+            backward_bps: 5,
+        });
+
+        assert_eq!(pool.fee_rate_for("ETH"), 100);
+        assert_eq!(pool.fee_rate_for("USDC"), 5);
+
+        let amount = BigUint::from(10_000u64);
+        let eth_to_usdc = pool
+            .clone()
+            .execute_swap_with_receipt("ETH", "USDC", &amount)
+            .unwrap();
+        assert_eq!(eth_to_usdc.fee, BigUint::from(100u64));
+        // 9900 traded against balanced reserves of 1_000_000
+        assert_eq!(eth_to_usdc.output_amount, BigUint::from(9802u64));
+
+        let usdc_to_eth = pool.calculate_swap_output("USDC", "ETH", &amount).unwrap();
+        assert_eq!(
+            simulation::PoolSim::from_pool(&pool)
+                .calculate_swap_output("USDC", "ETH", &amount)
+                .unwrap(),
+            usdc_to_eth
+        );
+    }
+
+    #[test]
+    fn test_underlying_amounts_are_share_of_reserves() {
+        let mut pool = create_sample_pool();
+        let reserves: HashMap<String, BigUint> = [("ETH", 4000u64), ("USDC", 9000)]
+            .iter()
+            .map(|(t, r)| (t.to_string(), BigUint::from(*r)))
+            .collect();
+        pool.set_reserves(reserves).unwrap();
+        assert_eq!(pool.total_supply, BigUint::from(6000u64));
+
+        // A quarter of the supply is a quarter of each reserve
+        let quarter = pool.underlying_amounts(&BigUint::from(1500u64)).unwrap();
+        assert_eq!(quarter["ETH"], BigUint::from(1000u64));
+        assert_eq!(quarter["USDC"], BigUint::from(2250u64));
+        assert_eq!(pool.reserves["ETH"], BigUint::from(4000u64));
+
+        let all = pool.underlying_amounts(&pool.total_supply).unwrap();
+        assert_eq!(all, pool.reserves);
+        assert!(pool.underlying_amounts(&BigUint::from(6001u64)).is_err());
+    }
+
+    #[test]
+    fn test_remove_liquidity_partial() {
+        let mut pool = create_sample_pool();
+        assert_eq!(pool.total_supply, BigUint::from(1414u64));
+
+        let payouts = pool.remove_liquidity(&BigUint::from(707u64)).unwrap();
+        assert_eq!(payouts["ETH"], BigUint::from(500u64));
+        assert_eq!(payouts["USDC"], BigUint::from(1000u64));
+        assert_eq!(pool.reserves["ETH"], BigUint::from(500u64));
+        assert_eq!(pool.reserves["USDC"], BigUint::from(1000u64));
+        assert_eq!(pool.total_supply, BigUint::from(707u64));
+
+        // 500 * 1 / 707 rounds down to 0 rather than overpaying
+        let dust = pool.remove_liquidity(&BigUint::one()).unwrap();
+        assert_eq!(dust["ETH"], BigUint::zero());
+        assert_eq!(dust["USDC"], BigUint::one());
+
+        assert!(matches!(
+            pool.remove_liquidity(&BigUint::from(707u64)),
+            Err(LiquidityError::InsufficientLPTokens)
+        ));
+        assert_eq!(pool.total_supply, BigUint::from(706u64));
+    }
+
+    #[test]
+    fn test_remove_liquidity_debits_the_owner() {
+        let mut pool = create_sample_pool();
+        pool.lp_positions
+            .insert("alice".to_string(), BigUint::from(1000u64));
+
+        // Only the 414 tokens nobody holds can leave without an owner
+        assert!(matches!(
+            pool.remove_liquidity(&BigUint::from(415u64)),
+            Err(LiquidityError::InsufficientLPTokens)
+        ));
+        assert!(matches!(
+            pool.remove_liquidity_for("alice", &BigUint::from(1001u64)),
+            Err(LiquidityError::InsufficientLPTokens)
+        ));
+        assert!(matches!(
+            pool.remove_liquidity_for("bob", &BigUint::one()),
+            Err(LiquidityError::InsufficientLPTokens)
+        ));
+        assert_eq!(pool.total_supply, BigUint::from(1414u64));
+
+        pool.remove_liquidity_for("alice", &BigUint::from(400u64))
+            .unwrap();
+        assert_eq!(pool.lp_positions["alice"], BigUint::from(600u64));
+        pool.remove_liquidity(&BigUint::from(414u64)).unwrap();
+
+        // Positions never add up to more than the supply, which stays theirs
+        assert_eq!(pool.total_supply, BigUint::from(600u64));
+        let rewards = rewards::calculate_rewards(&pool, &BigUint::from(1000u64), "alice");
+        assert_eq!(rewards, BigUint::from(1000u64));
+    }
+
+    #[test]
+    fn test_emergency_removal_is_proportional_and_gated() {
+        let mut pool = create_stable_pool();
+        pool.total_supply = BigUint::from(6000u64);
+        pool.lp_positions
+            .insert("alice".to_string(), BigUint::from(1500u64));
+        let lp_tokens = BigUint::from(1500u64);
+
+        let normal = pool.clone().remove_liquidity(&lp_tokens).unwrap();
+        assert!(matches!(
+            pool.emergency_remove_liquidity(&lp_tokens, "alice"),
+            Err(LiquidityError::EmergencyModeDisabled)
+        ));
+
+        set_emergency_mode(true);
+        let over = pool.emergency_remove_liquidity(&BigUint::from(1501u64), "alice");
+        let emergency = pool.emergency_remove_liquidity(&lp_tokens, "alice");
+        set_emergency_mode(false);
+
+        assert!(matches!(over, Err(LiquidityError::InsufficientLPTokens)));
+        let emergency = emergency.unwrap();
+        assert_eq!(emergency, normal);
+        assert_eq!(emergency["USDC"], BigUint::from(250_000_000u64));
+        assert_eq!(pool.lp_positions["alice"], BigUint::zero());
+        assert_eq!(pool.total_supply, BigUint::from(4500u64));
+    }
+
+    #[test]
+    fn test_emergency_removal_ignores_reserve_floors() {
+        let mut pool = create_sample_pool();
+        pool.min_reserves = Some(HashMap::from([("ETH".to_string(), BigUint::from(900u64))]));
+        let supply = pool.total_supply.clone();
+        pool.lp_positions
+            .insert("alice".to_string(), supply.clone());
+
+        assert!(matches!(
+            pool.clone().remove_liquidity_for("alice", &supply),
+            Err(LiquidityError::ReserveFloorBreached)
+        ));
+
+        set_emergency_mode(true);
+        let payouts = pool.emergency_remove_liquidity(&supply, "alice");
+        set_emergency_mode(false);
+
+        assert_eq!(payouts.unwrap()["ETH"], BigUint::from(1000u64));
+        assert!(pool.reserves.values().all(|reserve| reserve.is_zero()));
+        assert!(pool.total_supply.is_zero());
+    }
+
+    #[test]
+    fn test_remove_liquidity_full() {
+        let mut pool = create_sample_pool();
+        let supply = pool.total_supply.clone();
+
+        let payouts = pool.remove_liquidity(&supply).unwrap();
+        assert_eq!(payouts["ETH"], BigUint::from(1000u64));
+        assert_eq!(payouts["USDC"], BigUint::from(2000u64));
+        assert!(pool.reserves.values().all(|reserve| reserve.is_zero()));
+        assert!(pool.total_supply.is_zero());
+        assert_eq!(pool.version, 1);
+    }
+
+    #[test]
+    fn test_migrate_constant_product_to_stable_swap() {
+        let unit = BigUint::from(10u64).pow(6);
+        let tokens = create_stable_pool().tokens[..2].to_vec();
+        let reserves: HashMap<String, BigUint> = tokens
+            .iter()
+            .map(|t| (t.address.clone(), BigUint::from(1_000_000u64) * &unit))
+            .collect();
+        let pool_with = |pool_type| {
+            Pool::new(
+                "USDC-USDT".to_string(),
+                tokens.clone(),
+                reserves.clone(),
+                4,
+                pool_type,
+            )
+        };
+        let stable = pool_with(PoolType::StableSwap);
+        let mut pool = pool_with(PoolType::ConstantProduct);
+        pool.lp_positions
+            .insert("alice".to_string(), &pool.total_supply / 4u64);
+
+        let amount = BigUint::from(10_000u64) * &unit;
+        let cp_output = pool.calculate_swap_output("USDC", "USDT", &amount).unwrap();
+        pool.migrate_to(PoolType::StableSwap).unwrap();
+
+        // Balanced reserves: D is their sum
+        assert_eq!(pool.total_supply, BigUint::from(2_000_000u64) * &unit);
+        assert_eq!(
+            pool.lp_positions["alice"],
+            BigUint::from(500_000u64) * &unit
+        );
+
+        let output = pool.calculate_swap_output("USDC", "USDT", &amount).unwrap();
+        assert_eq!(
+            output,
+            stable
+                .calculate_swap_output("USDC", "USDT", &amount)
+                .unwrap()
+        );
+        assert!(output > cp_output);
+
+        assert!(pool.migrate_to(PoolType::StableSwap).is_err());
+        // Weights must fit the token set, and positions are left alone
+        let positions = pool.lp_positions.clone();
+        assert!(matches!(
+            pool.migrate_to(PoolType::Weighted {
+                weights: vec![50, 30, 20]
+            }),
+            Err(PoolError::InvalidWeights(_))
+        ));
+        assert!(matches!(
+            pool.migrate_to(PoolType::Weighted {
+                weights: vec![0, 100]
+            }),
+            Err(PoolError::InvalidWeights(_))
+        ));
+        assert_eq!(pool.lp_positions, positions);
+        assert_eq!(pool.pool_type, PoolType::StableSwap);
+
+        pool.reserves.insert("DAI".to_string(), BigUint::from(1u64));
+        assert!(matches!(
+            pool.migrate_to(PoolType::ConstantProduct),
+            Err(PoolError::IncompatibleMigration { .. })
+        ));
+    }
+
+    #[test]
+    fn test_reserves_checksum_tracks_reserves_only() {
+        let mut pool = create_sample_pool();
+        let checksum = pool.reserves_checksum();
+
+        // Same reserves built in another order
+        let mut rebuilt = HashMap::new();
+        for token in ["USDC", "ETH"] {
+            rebuilt.insert(token.to_string(), pool.reserves[token].clone());
+        }
+        assert_eq!(reserves_checksum(&rebuilt), checksum);
+
+        pool.fee_rate = 30;
+        assert_eq!(pool.reserves_checksum(), checksum);
+        pool.execute_swap("ETH", "USDC", &BigUint::from(10u64))
+            .unwrap();
+        assert_ne!(pool.reserves_checksum(), checksum);
+    }
+
+    #[test]
+    fn test_normalized_reserves_follow_swaps() {
+        let mut pool = create_sample_pool();
+        let fresh = |pool: &Pool| {
+            let mut expected = HashMap::new();
+            for token in &pool.tokens {
+                let reserve = &pool.reserves[&token.address];
+                expected.insert(
+                    token.address.clone(),
+                    normalize_amount(reserve, token.decimals),
+                );
+            }
+            expected
+        };
+
+        // USDC has 6 decimals, so it is scaled up by 10^12
+        assert_eq!(
+            pool.normalized_reserves()["USDC"],
+            BigUint::from(2000u64) * BigUint::from(10u64).pow(12)
+        );
+        assert_eq!(*pool.normalized_reserves(), fresh(&pool));
+
+        pool.execute_swap("ETH", "USDC", &BigUint::from(100u64))
+            .unwrap();
+        assert_eq!(*pool.normalized_reserves(), fresh(&pool));
+        assert_ne!(
+            pool.normalized_reserves()["USDC"],
+            BigUint::from(2000u64) * BigUint::from(10u64).pow(12)
+        );
+    }
+
+    #[test]
+    fn test_version_bumps_on_state_changes_only() {
+        let mut pool = create_sample_pool();
+        assert_eq!(pool.version, 0);
+
+        pool.calculate_swap_output("ETH", "USDC", &BigUint::from(10u64))
+            .unwrap();
+        assert_eq!(pool.version, 0);
+
+        pool.execute_swap("ETH", "USDC", &BigUint::from(10u64))
+            .unwrap();
+        let mut deposit = HashMap::new();
+        deposit.insert("ETH".to_string(), BigUint::from(100u64));
+        deposit.insert("USDC".to_string(), BigUint::from(200u64));
+        pool.add_liquidity(deposit).unwrap();
+        assert_eq!(pool.version, 2);
+
+        // A failed swap leaves the pool, and its version, untouched
+        assert!(pool
+            .execute_swap("ETH", "DAI", &BigUint::from(10u64))
+            .is_err());
+        assert_eq!(pool.version, 2);
+    }
+
+    #[test]
+    fn test_set_reserves_requires_every_token() {
+        let mut pool = create_sample_pool();
+
+        let mut partial = HashMap::new();
+        partial.insert("ETH".to_string(), BigUint::from(4000u64));
+        assert!(pool.set_reserves(partial.clone()).is_err());
+        partial.insert("DAI".to_string(), BigUint::from(4000u64));
+        assert!(pool.set_reserves(partial.clone()).is_err());
+        assert_eq!(pool.reserves["ETH"], BigUint::from(1000u64));
+
+        partial.remove("DAI");
+        partial.insert("usdc".to_string(), BigUint::zero());
+        assert!(matches!(
+            pool.set_reserves(partial.clone()),
+            Err(LiquidityError::InsufficientLiquidity)
+        ));
+        assert_eq!(pool.reserves["USDC"], BigUint::from(2000u64));
+
+        partial.insert("usdc".to_string(), BigUint::from(9000u64));
+        pool.set_reserves(partial).unwrap();
+        assert_eq!(pool.reserves["USDC"], BigUint::from(9000u64));
+        assert_eq!(pool.total_supply, BigUint::from(6000u64));
+    }
+
+    #[test]
+    fn test_reconcile_drift_stays_within_bounds() {
+        let mut pool = create_sample_pool();
+        let unit = BigUint::from(10u64).pow(15);
+        pool.reserves.insert("ETH".to_string(), &unit * 1000u64);
+        pool.reserves.insert("USDC".to_string(), &unit * 2000u64);
+        pool.total_supply = pool.supply_implied_by(&pool.pool_type);
+
+        // Proportional deposits with odd dust that the floored mint rounds away
+        let ops = 200u64;
+        for i in 0..ops {
+            let mut amounts = HashMap::new();
+            amounts.insert("ETH".to_string(), &unit + i % 7);
+            amounts.insert("USDC".to_string(), &unit * 2u64 + i % 5);
+            pool.add_liquidity(amounts).unwrap();
+        }
+
+        let report = pool.reconcile();
+        assert!(report.is_consistent());
+        assert!(report.deficit.is_zero());
+        // Each deposit leaves the pool at most a few base units ahead
+        assert!(report.surplus <= BigUint::from(ops * 8));
+        assert_eq!(pool.total_supply, report.total_supply);
+    }
+
+    #[test]
+    fn test_reconcile_burns_dust_but_reports_real_deficit() {
+        let mut pool = create_sample_pool();
+        let implied = pool.total_supply.clone();
+
+        pool.total_supply += 1u64;
+        let report = pool.reconcile();
+        assert_eq!(report.dust_absorbed, BigUint::one());
+        assert!(report.is_consistent());
+        assert_eq!(pool.total_supply, implied);
+
+        pool.total_supply += 50u64;
+        let report = pool.reconcile();
+        assert_eq!(report.deficit, BigUint::from(50u64));
+        assert!(!report.is_consistent());
+        assert_eq!(pool.total_supply, &implied + 50u64);
+    }
+
+    #[test]
+    fn test_imbalance_ratio_of_stable_pools() {
+        let mut pool = create_stable_pool();
+        let threshold = Decimal::new(105, 2);
+
+        // 1000 / 2000 / 3000 once the 6-decimal reserves are scaled up
+        assert_eq!(pool.imbalance_ratio().unwrap(), Decimal::from(3));
+        assert!(!pool.is_stable_balanced(threshold));
+
+        pool.reserves
+            .insert("USDC".to_string(), BigUint::from(3000u64) * 10u64.pow(6));
+        pool.reserves
+            .insert("USDT".to_string(), BigUint::from(2990u64) * 10u64.pow(6));
+        pool.bump_version();
+        assert_eq!(
+            pool.imbalance_ratio().unwrap().round_dp(4),
+            Decimal::new(10033, 4)
+        );
+        assert!(pool.is_stable_balanced(threshold));
+
+        let constant_product = create_sample_pool();
+        assert!(matches!(
+            constant_product.imbalance_ratio(),
+            Err(SwapError::UnsupportedPoolType)
+        ));
+    }
+
+    #[test]
+    fn test_net_output_after_gas() {
+        let pool = create_sample_pool();
+        let input = BigUint::from(100u64);
+        let gross = pool.calculate_swap_output("ETH", "USDC", &input).unwrap();
+
+        let net = pool
+            .net_output_after_gas("ETH", "USDC", &input, &BigUint::from(10u64))
+            .unwrap();
+        assert_eq!(net, &gross - 10u64);
+
+        // Gas costing more than the trade returns floors at zero
+        let net = pool
+            .net_output_after_gas("ETH", "USDC", &input, &(&gross + 1u64))
+            .unwrap();
+        assert!(net.is_zero());
+    }
+
+    #[test]
+    fn test_supports_token_ignores_address_case() {
+        let mut pool = create_sample_pool();
+        pool.tokens[0].address = "0xAbCd".to_string();
+
+        assert!(pool.supports_token("0xabcd"));
+        assert!(pool.supports_token(" 0xABCD "));
+        assert!(!pool.supports_token("DAI"));
+        assert_eq!(pool.find_token("0XABCD").unwrap().symbol, "ETH");
+    }
+
+    #[test]
+    fn test_supports_pair() {
+        let pool = create_sample_pool();
+
+        assert!(pool.supports_pair("ETH", "usdc"));
+        assert!(pool.supports_pair("USDC", "ETH"));
+        assert!(!pool.supports_pair("ETH", "DAI"));
+    }
+
+    #[test]
+    fn test_swap_cannot_empty_a_reserve() {
+        let tokens: Vec<Token> = ["USDC", "USDT"]
+            .iter()
+            .map(|symbol| Token {
+                address: symbol.to_string(),
+                symbol: symbol.to_string(),
+                decimals: 6,
+            })
+            .collect();
+        let reserves = tokens
+            .iter()
+            .map(|t| (t.address.clone(), BigUint::from(1000u64)))
+            .collect();
+        let mut pool = Pool::new(
+            "USDC-USDT".to_string(),
+            tokens,
+            reserves,
+            0,
+            PoolType::StableSwap,
+        );
+        let before = pool.reserves.clone();
+
+        // Far past the pool's depth the stable curve rounds the output up to the whole reserve
+        let input = BigUint::from(100_000u64);
+        assert_eq!(
+            pool.calculate_swap_output("USDC", "USDT", &input).unwrap(),
+            BigUint::from(1000u64)
+        );
+        assert!(matches!(
+            pool.execute_swap("USDC", "USDT", &input),
+            Err(SwapError::WouldEmptyReserve)
+        ));
+        assert_eq!(pool.reserves, before);
+        assert_eq!(pool.version, 0);
+    }
+
+    #[test]
+    fn test_swap_blocked_by_reserve_floor() {
+        let mut pool = create_sample_pool();
+        let mut floors = HashMap::new();
+        floors.insert("USDC".to_string(), BigUint::from(1900u64));
+        pool.min_reserves = Some(floors);
+
+        // ~91 USDC out leaves ~1909, above the floor
+        pool.execute_swap("ETH", "USDC", &BigUint::from(50u64))
+            .unwrap();
+
+        // Another such swap would dip below it and leaves the pool untouched
+        let reserves = pool.reserves.clone();
+        let result = pool.execute_swap("ETH", "USDC", &BigUint::from(50u64));
+        assert!(matches!(result, Err(SwapError::ReserveFloorBreached)));
+        assert_eq!(pool.reserves, reserves);
+
+        // Tokens without a floor are unaffected
+        pool.execute_swap("USDC", "ETH", &BigUint::from(500u64))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_removal_blocked_by_reserve_floor() {
+        let mut pool = create_sample_pool();
+        let mut floors = HashMap::new();
+        floors.insert("USDC".to_string(), BigUint::from(1500u64));
+        pool.min_reserves = Some(floors);
+        let supply = pool.total_supply.clone();
+
+        // A fifth of the supply takes ~400 USDC, leaving ~1600 above the floor
+        pool.remove_liquidity(&(&supply / 5u32)).unwrap();
+        assert_eq!(pool.reserves["USDC"], BigUint::from(1602u64));
+
+        // Another fifth would leave ~1200 and leaves the pool untouched
+        let reserves = pool.reserves.clone();
+        let total_supply = pool.total_supply.clone();
+        let result = pool.remove_liquidity(&(&supply / 5u32));
+        assert!(matches!(result, Err(LiquidityError::ReserveFloorBreached)));
+        assert_eq!(pool.reserves, reserves);
+        assert_eq!(pool.total_supply, total_supply);
+    }
+
+    #[test]
+    fn test_input_for_output_round_trips() {
+        let mut pools = vec![create_sample_pool()];
+        let mut deep = create_sample_pool();
+        deep.reserves
+            .insert("ETH".to_string(), BigUint::from(10u64).pow(24));
+        deep.reserves
+            .insert("USDC".to_string(), BigUint::from(3_000_000_000_000u64));
+        deep.fee_rate = 30;
+        pools.push(deep);
+
+        for pool in &pools {
+            for (input_token, output_token) in [("ETH", "USDC"), ("USDC", "ETH")] {
+                let output_reserve = pool.reserves[output_token].clone();
+                for output in [
+                    BigUint::one(),
+                    BigUint::from(10u64),
+                    &output_reserve / 2u64,
+                    &output_reserve - 1u64,
+                ] {
+                    let input = pool
+                        .calculate_input_for_output(input_token, output_token, &output)
+                        .unwrap();
+                    // Enough for the output, and one unit less is not
+                    let quoted = |input: &BigUint| {
+                        pool.calculate_swap_output(input_token, output_token, input)
+                            .unwrap()
+                    };
+                    assert!(quoted(&input) >= output);
+                    assert!(quoted(&(&input - 1u64)) < output);
+                }
+
+                assert!(matches!(
+                    pool.calculate_input_for_output(input_token, output_token, &output_reserve),
+                    Err(SwapError::InsufficientLiquidity)
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip_swap_loses_fees() {
+        let mut pool = create_sample_pool();
+        let k_before = &pool.reserves["ETH"] * &pool.reserves["USDC"];
+
+        let usdc = pool
+            .execute_swap("ETH", "USDC", &BigUint::from(100u64))
+            .unwrap();
+        let eth = pool.execute_swap("USDC", "ETH", &usdc).unwrap();
+
+        assert!(eth < BigUint::from(100u64));
+        assert!(&pool.reserves["ETH"] * &pool.reserves["USDC"] > k_before);
+    }
+
+    #[test]
+    fn test_swap_with_min_output_enforces_slippage() {
+        let mut pool = create_sample_pool();
+        let input = BigUint::from(50u64);
+        let quoted = pool.calculate_swap_output("ETH", "USDC", &input).unwrap();
+
+        let reserves = pool.reserves.clone();
+        let too_high = &quoted + 1u64;
+        match pool.swap_with_min_output("ETH", "USDC", &input, &too_high) {
+            Err(SwapError::SlippageExceeded { expected, actual }) => {
+                assert_eq!(expected, too_high);
+                assert_eq!(actual, quoted);
+            }
+            other => panic!("expected SlippageExceeded, got {:?}", other),
+        }
+        assert_eq!(pool.reserves, reserves);
+
+        let output = pool
+            .swap_with_min_output("ETH", "USDC", &input, &quoted)
+            .unwrap();
+        assert_eq!(output, quoted);
+        assert_eq!(pool.reserves["ETH"], BigUint::from(1050u64));
+        assert_eq!(pool.reserves["USDC"], BigUint::from(2000u64) - quoted);
+    }
+
+    #[test]
+    fn test_weighted_spot_price_is_the_marginal_price() {
+        let tokens = [("WETH", 18), ("WBTC", 8)]
+            .iter()
+            .map(|(symbol, decimals)| Token {
+                address: symbol.to_string(),
+                symbol: symbol.to_string(),
+                decimals: *decimals,
+            })
+            .collect();
+        // 80/20 WETH-WBTC, 8000 WETH against 10 WBTC
+        let reserves = [
+            (
+                "WETH",
+                BigUint::from(8000u64) * BigUint::from(10u64).pow(18),
+            ),
+            ("WBTC", BigUint::from(10u64) * BigUint::from(10u64).pow(8)),
+        ]
+        .into_iter()
+        .map(|(token, amount)| (token.to_string(), amount))
+        .collect();
+        let pool = Pool::try_new(
+            "WETH-WBTC".to_string(),
+            tokens,
+            reserves,
+            0,
+            PoolType::Weighted {
+                weights: vec![80, 20],
+            },
+        )
+        .unwrap();
+
+        // (1e9 / 20) / (8e21 / 80), not the plain reserve ratio of 1.25e-13
+        let spot = pool.spot_price("WETH", "WBTC").unwrap();
+        assert_eq!(spot, Decimal::from_str("0.0000000000005").unwrap());
+        assert_eq!(
+            pool.spot_price("WBTC", "WETH").unwrap(),
+            Decimal::from(2_000_000_000_000u64)
+        );
+
+        // A small trade fills at the spot price, up to rounding
+        let input = BigUint::from(10u64).pow(16);
+        let output = pool.calculate_swap_output("WETH", "WBTC", &input).unwrap();
+        let marginal = ratio_to_decimal(&output, &input).unwrap();
+        assert!(marginal <= spot);
+        assert!(spot - marginal < spot / Decimal::from(1000));
+    }
+
+    #[test]
+    fn test_weighted_pool_impact_depends_on_direction() {
+        let unit = BigUint::from(10u64).pow(18);
+        let tokens = || {
+            ["ETH", "USDC"]
+                .iter()
+                .map(|symbol| Token {
+                    address: symbol.to_string(),
+                    symbol: symbol.to_string(),
+                    decimals: 18,
+                })
+                .collect::<Vec<_>>()
+        };
+        let pool = |eth: u64, usdc: u64, pool_type: PoolType| {
+            let reserves = [("ETH", eth), ("USDC", usdc)]
+                .iter()
+                .map(|(token, amount)| (token.to_string(), BigUint::from(*amount) * &unit))
+                .collect();
+            Pool::try_new("POOL".to_string(), tokens(), reserves, 0, pool_type).unwrap()
+        };
+        // Both price ETH at 1 USDC and hold the same value
+        let weighted = pool(
+            8000,
+            2000,
+            PoolType::Weighted {
+                weights: vec![80, 20],
+            },
+        );
+        let balanced = pool(5000, 5000, PoolType::ConstantProduct);
+        let trade = BigUint::from(1000u64) * &unit;
+        let quote = |pool: &Pool, input: &str, output: &str| {
+            pool.calculate_swap_output(input, output, &trade).unwrap()
+        };
+
+        // 2000 * (1 - (8000 / 9000)^4) and 8000 * (1 - (2000 / 3000)^(1/4)),
+        // worked out to 60 digits and rounded down
+        let sell_eth = quote(&weighted, "ETH", "USDC");
+        let sell_usdc = quote(&weighted, "USDC", "ETH");
+        assert_eq!(sell_eth.to_string(), "751409846060051821368");
+        assert_eq!(sell_usdc.to_string(), "771183971121241344302");
+
+        // The 50/50 pool is symmetric and deeper both ways
+        let balanced_output = quote(&balanced, "ETH", "USDC");
+        assert_eq!(balanced_output, quote(&balanced, "USDC", "ETH"));
+        assert!(sell_eth < sell_usdc && sell_usdc < balanced_output);
+        assert_eq!(
+            weighted
+                .calculate_multi_asset_swap("ETH", "USDC", &trade)
+                .unwrap(),
+            sell_eth
+        );
+
+        for weights in [vec![70, 20], vec![80, 20, 0], vec![100, 0], vec![100]] {
+            let reserves = tokens()
+                .iter()
+                .map(|token| (token.address.clone(), unit.clone()))
+                .collect();
+            assert!(matches!(
+                Pool::try_new(
+                    "POOL".to_string(),
+                    tokens(),
+                    reserves,
+                    0,
+                    PoolType::Weighted { weights }
+                ),
+                Err(PoolError::InvalidWeights(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_utilization_tracks_drain_from_peak() {
+        let mut pool = create_sample_pool();
+        assert_eq!(pool.utilization("USDC"), Decimal::ZERO);
+        assert_eq!(pool.utilization("BTC"), Decimal::ZERO);
+
+        // Selling ETH takes 1000 of the 2000 USDC
+        pool.execute_swap("ETH", "USDC", &BigUint::from(1031u64))
+            .unwrap();
+        assert_eq!(pool.reserves["USDC"], BigUint::from(1000u64));
+        assert_eq!(pool.utilization("USDC"), Decimal::new(5, 1));
+        // The ETH reserve grew, setting a new peak
+        assert_eq!(pool.utilization("ETH"), Decimal::ZERO);
+        assert_eq!(pool.max_reserves["ETH"], pool.reserves["ETH"]);
+
+        let deposit = [("ETH", 1031u64), ("USDC", 500)]
+            .iter()
+            .map(|(token, amount)| (token.to_string(), BigUint::from(*amount)))
+            .collect();
+        pool.add_liquidity(deposit).unwrap();
+        assert_eq!(pool.utilization("USDC"), Decimal::new(25, 2));
+    }
+
+    #[test]
+    fn test_pool_amounts_serialize_as_decimal_strings() {
+        let large: BigUint = (BigUint::from(1u8) << 255u32) + 12_345u32;
+        let mut pool = create_sample_pool();
+        pool.reserves.insert("ETH".to_string(), large.clone());
+        pool.total_supply = &large * 3u8;
+        pool.lp_positions.insert("alice".to_string(), large.clone());
+        pool.min_reserves = Some(HashMap::from([("USDC".to_string(), BigUint::from(7u8))]));
+        pool.sqrt_price_x96 = Some(large.clone());
+        pool.last_price_cumulative = large.clone();
+
+        let json = serde_json::to_value(&pool).unwrap();
+        assert_eq!(json["reserves"]["ETH"], large.to_string());
+        assert_eq!(json["reserves"]["USDC"], "2000");
+        assert_eq!(json["total_supply"], (&large * 3u8).to_string());
+        assert_eq!(json["min_reserves"]["USDC"], "7");
+        assert_eq!(json["sqrt_price_x96"], large.to_string());
+        assert_eq!(json["price_range"], serde_json::Value::Null);
+
+        let restored: Pool = serde_json::from_value(json).unwrap();
+        assert_eq!(restored.reserves, pool.reserves);
+        assert_eq!(restored.total_supply, pool.total_supply);
+        assert_eq!(restored.lp_positions, pool.lp_positions);
+        assert_eq!(restored.min_reserves, pool.min_reserves);
+        assert_eq!(restored.sqrt_price_x96, pool.sqrt_price_x96);
+        assert_eq!(restored.last_price_cumulative, large);
+        assert_eq!(restored.max_reserves, pool.max_reserves);
+
+        // Fields added since a pool was stored still default
+        let mut stored = serde_json::to_value(&pool).unwrap();
+        let fields = stored.as_object_mut().unwrap();
+        for field in [
+            "sqrt_price_x96",
+            "min_reserves",
+            "max_reserves",
+            "last_price_cumulative",
+        ] {
+            fields.remove(field);
+        }
+        let restored: Pool = serde_json::from_value(stored).unwrap();
+        assert_eq!(restored.sqrt_price_x96, None);
+        assert!(restored.last_price_cumulative.is_zero());
+    }
+
+    #[test]
+    fn test_twap_weights_prices_by_time() {
+        let mut pool = create_sample_pool();
+        pool.accumulate_price(1_000);
+        let snapshot = (
+            pool.last_price_cumulative.clone(),
+            pool.last_update_timestamp,
+        );
+        let first_price = pool.get_current_price("ETH", "USDC").unwrap();
+
+        // 10 seconds at the opening price, then 30 at the post-swap one
+        pool.accumulate_price(1_010);
+        pool.execute_swap("ETH", "USDC", &BigUint::from(100u64))
+            .unwrap();
+        let second_price = pool.get_current_price("ETH", "USDC").unwrap();
+        pool.accumulate_price(1_040);
+
+        let expected = (first_price * 10.0 + second_price * 30.0) / 40.0;
+        let twap = pool.consult_twap(snapshot.clone(), 1_040);
+        assert!(
+            (twap - expected).abs() / expected < 1e-9,
+            "{twap} vs {expected}"
+        );
+        assert!(twap < first_price && twap > second_price);
+
+        // Time since the last accumulation counts at the current price
+        let later = pool.consult_twap(snapshot, 1_080);
+        let expected = (first_price * 10.0 + second_price * 70.0) / 80.0;
+        assert!(
+            (later - expected).abs() / expected < 1e-9,
+            "{later} vs {expected}"
+        );
+    }
+
+    #[test]
+    fn test_tags_are_validated() {
+        let tags = |tags: &[&str]| tags.iter().map(|tag| tag.to_string()).collect();
+        let pool = create_sample_pool()
+            .with_tags(tags(&["blue-chip", "l2", "blue-chip"]))
+            .unwrap();
+        assert_eq!(pool.tags, vec!["blue-chip", "l2"]);
+
+        let too_long = "x".repeat(MAX_TAG_LEN + 1);
+        for invalid in ["", "Stablecoins", "blue chip", "émoji", too_long.as_str()] {
+            assert!(matches!(
+                create_sample_pool().with_tags(tags(&[invalid])),
+                Err(PoolError::InvalidTag(tag)) if tag == invalid
+            ));
+        }
+    }
+
+    #[test]
+    fn test_min_output_for_slippage_is_exact() {
+        let percent = |value: &str| Decimal::from_str(value).unwrap();
+        // 12.5 bps stays 12.5 bps instead of rounding to 13
+        assert_eq!(
+            min_output_for_slippage(&BigUint::from(80_000u64), percent("0.125")),
+            Some(BigUint::from(79_900u64))
+        );
+        let expected = BigUint::from(10u64).pow(30);
+        assert_eq!(
+            min_output_for_slippage(&expected, percent("0.3")),
+            Some(BigUint::from(997u64) * BigUint::from(10u64).pow(27))
+        );
+        assert_eq!(
+            min_output_for_slippage(&expected, Decimal::ZERO),
+            Some(expected.clone())
+        );
+        assert_eq!(
+            min_output_for_slippage(&expected, Decimal::ONE_HUNDRED),
+            Some(BigUint::zero())
+        );
+        assert_eq!(min_output_for_slippage(&expected, percent("100.01")), None);
+        assert_eq!(min_output_for_slippage(&expected, percent("-0.01")), None);
+
+        // A quote the swap lands exactly on the minimum of, and one a unit
+        // above it: the first always fills, the second never does
+        let pool = create_sample_pool();
+        let input = BigUint::from(50u64);
+        let output = pool.calculate_swap_output("ETH", "USDC", &input).unwrap();
+        let at_boundary = (&output * 800u32 + 798u32) / 799u32;
+        assert_eq!(
+            min_output_for_slippage(&at_boundary, percent("0.125")),
+            Some(output.clone())
+        );
+        let above = min_output_for_slippage(&(&at_boundary + 2u32), percent("0.125")).unwrap();
+        assert!(above > output);
+        for _ in 0..3 {
+            let mut accepted = pool.clone();
+            assert_eq!(
+                accepted
+                    .swap_with_min_output("ETH", "USDC", &input, &output)
+                    .unwrap(),
+                output
+            );
+            let mut rejected = pool.clone();
+            assert!(matches!(
+                rejected.swap_with_min_output("ETH", "USDC", &input, &above),
+                Err(SwapError::SlippageExceeded { .. })
+            ));
+        }
+    }
+
+    fn create_stable_pool() -> Pool {
+        let tokens = vec![
+            Token {
+                address: "USDC".to_string(),
+                symbol: "USDC".to_string(),
+                decimals: 6,
+            },
+            Token {
+                address: "USDT".to_string(),
+                symbol: "USDT".to_string(),
+                decimals: 6,
+            },
+            Token {
+                address: "DAI".to_string(),
+                symbol: "DAI".to_string(),
+                decimals: 18,
+            },
+        ];
+
+        let mut reserves = HashMap::new();
+        reserves.insert(
+            "USDC".to_string(),
+            BigUint::from(1000u64) * BigUint::from(10u64).pow(6),
+        );
+        reserves.insert(
+            "USDT".to_string(),
+            BigUint::from(2000u64) * BigUint::from(10u64).pow(6),
+        );
+        reserves.insert(
+            "DAI".to_string(),
+            BigUint::from(3000u64) * BigUint::from(10u64).pow(18),
+        );
+
+        Pool::new(
+            "USDC-USDT-DAI".to_string(),
+            tokens,
+            reserves,
+            4,
+            PoolType::StableSwap,
+        )
+    }
+
     fn create_sample_pool() -> Pool {
         let eth_token = Token {
             address: "ETH".to_string(),
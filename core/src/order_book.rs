@@ -0,0 +1,260 @@
+use crate::{ratio_to_decimal, Pool, SwapError};
+use num_bigint::BigUint;
+use rust_decimal::Decimal;
+
+/// A resting order to sell `input_amount` of `input_token` for at least
+/// `limit_price` units of `output_token` per unit of input (raw token units).
+#[derive(Debug, Clone, PartialEq)]
+pub struct LimitOrder {
+    pub id: u64,
+    pub owner: String,
+    pub input_token: String,
+    pub output_token: String,
+    pub input_amount: BigUint,
+    pub limit_price: Decimal,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilledOrder {
+    pub order: LimitOrder,
+    pub output_amount: BigUint,
+}
+
+impl FilledOrder {
+    /// Output received per unit of input
+    pub fn execution_price(&self) -> Option<Decimal> {
+        ratio_to_decimal(&self.output_amount, &self.order.input_amount)
+    }
+}
+
+/// Limit orders resting against a single pool, kept sorted by limit price
+/// so the most easily filled orders are checked first. The pool does not
+/// know about its book: whoever holds the book fills it, by swapping through
+/// `Pool::execute_swap_filling_orders` or by calling
+/// `Pool::check_and_fill_orders` after every other change to the reserves.
+#[derive(Debug, Clone)]
+pub struct OrderBook {
+    pub pool_id: String,
+    orders: Vec<LimitOrder>,
+    next_id: u64,
+}
+
+impl OrderBook {
+    pub fn new(pool_id: String) -> Self {
+        OrderBook {
+            pool_id,
+            orders: Vec::new(),
+            next_id: 1,
+        }
+    }
+
+    pub fn place_order(
+        &mut self,
+        owner: &str,
+        input_token: &str,
+        output_token: &str,
+        input_amount: BigUint,
+        limit_price: Decimal,
+    ) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let order = LimitOrder {
+            id,
+            owner: owner.to_string(),
+            input_token: input_token.to_string(),
+            output_token: output_token.to_string(),
+            input_amount,
+            limit_price,
+        };
+        let position = self
+            .orders
+            .partition_point(|o| o.limit_price <= order.limit_price);
+        self.orders.insert(position, order);
+
+        id
+    }
+
+    pub fn cancel_order(&mut self, id: u64) -> Option<LimitOrder> {
+        let position = self.orders.iter().position(|o| o.id == id)?;
+        Some(self.orders.remove(position))
+    }
+
+    pub fn orders(&self) -> &[LimitOrder] {
+        &self.orders
+    }
+}
+
+impl Pool {
+    /// `execute_swap`, then fills the orders of `book` the swap's price move
+    /// crossed. Returns the swap's output and the filled orders.
+    pub fn execute_swap_filling_orders(
+        &mut self,
+        input_token: &str,
+        output_token: &str,
+        input_amount: &BigUint,
+        book: &mut OrderBook,
+    ) -> Result<(BigUint, Vec<FilledOrder>), SwapError> {
+        let output_amount = self.execute_swap(input_token, output_token, input_amount)?;
+        Ok((output_amount, self.check_and_fill_orders(book)))
+    }
+
+    /// Fills every resting order whose limit price is crossed by the current
+    /// spot price, executing each one as a swap against the pool. Nothing
+    /// calls this on its own: the book's holder must, after each swap not
+    /// made through `execute_swap_filling_orders`. An order is only filled
+    /// if its own execution still meets the limit once price impact is
+    /// included.
+    pub fn check_and_fill_orders(&mut self, book: &mut OrderBook) -> Vec<FilledOrder> {
+        let mut filled = Vec::new();
+        if book.pool_id != self.id {
+            return filled;
+        }
+
+        let mut i = 0;
+        while i < book.orders.len() {
+            let order = &book.orders[i];
+            let crossed = self
+                .spot_price(&order.input_token, &order.output_token)
+                .is_ok_and(|price| price >= order.limit_price);
+            let meets_limit = crossed
+                && self
+                    .effective_price(&order.input_token, &order.output_token, &order.input_amount)
+                    .is_ok_and(|price| price >= order.limit_price);
+
+            if !meets_limit {
+                i += 1;
+                continue;
+            }
+
+            let order = book.orders.remove(i);
+            match self.execute_swap(&order.input_token, &order.output_token, &order.input_amount) {
+                Ok(output_amount) => filled.push(FilledOrder {
+                    order,
+                    output_amount,
+                }),
+                Err(_) => {
+                    book.orders.insert(i, order);
+                    i += 1;
+                }
+            }
+        }
+
+        filled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PoolType, Token};
+    use std::collections::HashMap;
+
+    fn create_sample_pool() -> Pool {
+        let tokens = ["ETH", "USDC"]
+            .iter()
+            .map(|symbol| Token {
+                address: symbol.to_string(),
+                symbol: symbol.to_string(),
+                decimals: 18,
+            })
+            .collect();
+
+        let mut reserves = HashMap::new();
+        reserves.insert("ETH".to_string(), BigUint::from(1000u64));
+        reserves.insert("USDC".to_string(), BigUint::from(2000u64));
+
+        Pool::new(
+            "ETH-USDC".to_string(),
+            tokens,
+            reserves,
+            300,
+            PoolType::ConstantProduct,
+        )
+    }
+
+    #[test]
+    fn test_orders_kept_sorted_by_limit_price() {
+        let mut book = OrderBook::new("ETH-USDC".to_string());
+        book.place_order(
+            "alice",
+            "USDC",
+            "ETH",
+            BigUint::from(10u64),
+            Decimal::new(9, 1),
+        );
+        book.place_order(
+            "bob",
+            "USDC",
+            "ETH",
+            BigUint::from(10u64),
+            Decimal::new(55, 2),
+        );
+
+        let owners: Vec<&str> = book.orders().iter().map(|o| o.owner.as_str()).collect();
+        assert_eq!(owners, vec!["bob", "alice"]);
+    }
+
+    #[test]
+    fn test_swap_crossing_limit_fills_order() {
+        let mut pool = create_sample_pool();
+        let mut book = OrderBook::new(pool.id.clone());
+
+        // Spot is 0.5 ETH per USDC; both orders rest above it
+        let crossed = book.place_order(
+            "alice",
+            "USDC",
+            "ETH",
+            BigUint::from(10u64),
+            Decimal::new(55, 2),
+        );
+        let resting = book.place_order(
+            "bob",
+            "USDC",
+            "ETH",
+            BigUint::from(10u64),
+            Decimal::new(9, 1),
+        );
+        assert!(pool.check_and_fill_orders(&mut book).is_empty());
+
+        // Selling ETH into the pool makes ETH cheaper in USDC terms
+        pool.execute_swap("ETH", "USDC", &BigUint::from(200u64))
+            .unwrap();
+        let filled = pool.check_and_fill_orders(&mut book);
+
+        assert_eq!(filled.len(), 1);
+        assert_eq!(filled[0].order.id, crossed);
+        assert!(filled[0].execution_price().unwrap() >= Decimal::new(55, 2));
+        assert_eq!(book.orders().len(), 1);
+        assert_eq!(book.orders()[0].id, resting);
+    }
+
+    #[test]
+    fn test_swap_through_the_book_fills_crossed_orders() {
+        let mut pool = create_sample_pool();
+        let mut book = OrderBook::new(pool.id.clone());
+        let crossed = book.place_order(
+            "alice",
+            "USDC",
+            "ETH",
+            BigUint::from(10u64),
+            Decimal::new(55, 2),
+        );
+
+        let (output, filled) = pool
+            .execute_swap_filling_orders("ETH", "USDC", &BigUint::from(200u64), &mut book)
+            .unwrap();
+
+        assert!(output > BigUint::from(0u64));
+        assert_eq!(filled.len(), 1);
+        assert_eq!(filled[0].order.id, crossed);
+        assert!(book.orders().is_empty());
+
+        // A failed swap fills nothing
+        book.place_order("bob", "USDC", "ETH", BigUint::from(10u64), Decimal::ZERO);
+        assert!(pool
+            .execute_swap_filling_orders("ETH", "DAI", &BigUint::from(200u64), &mut book)
+            .is_err());
+        assert_eq!(book.orders().len(), 1);
+    }
+}
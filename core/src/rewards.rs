@@ -0,0 +1,152 @@
+use crate::{ratio_to_decimal, Pool};
+use num_bigint::BigUint;
+use num_traits::Zero;
+use rust_decimal::Decimal;
+
+pub const SECONDS_PER_YEAR: u64 = 365 * 24 * 60 * 60;
+
+/// Liquidity-mining program paying `per_year` of `token` to a pool's LPs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RewardProgram {
+    pub token: String,
+    pub per_year: BigUint,
+}
+
+/// Share of `total_reward` owed to `owner`, pro-rata to the LP tokens they
+/// hold out of the pool's `total_supply`. Rounds down, so the sum over all
+/// LPs can fall short of `total_reward` by a few units of dust.
+pub fn calculate_rewards(pool: &Pool, total_reward: &BigUint, owner: &str) -> BigUint {
+    if pool.total_supply.is_zero() {
+        return BigUint::zero();
+    }
+
+    match pool.lp_positions.get(owner) {
+        Some(lp_tokens) => total_reward * lp_tokens / &pool.total_supply,
+        None => BigUint::zero(),
+    }
+}
+
+/// Annualised fee yield of the pool's LPs as a fraction (0.05 = 5%), from
+/// the fees accrued since `fees_since`. Each side of a constant-product pool
+/// holds the same value, so the yield is the mean of `fees_i / reserve_i` and
+/// needs no external prices.
+pub fn fee_apy(pool: &Pool, now: u64) -> Decimal {
+    let elapsed = now.saturating_sub(pool.fees_since);
+    if elapsed == 0 || pool.tokens.is_empty() {
+        return Decimal::ZERO;
+    }
+
+    let period_yield: Decimal = pool
+        .tokens
+        .iter()
+        .filter_map(|token| {
+            let fees = pool.cumulative_fees.get(&token.address)?;
+            let reserve = pool.reserves.get(&token.address)?;
+            ratio_to_decimal(fees, reserve)
+        })
+        .sum::<Decimal>()
+        / Decimal::from(pool.tokens.len());
+
+    period_yield * Decimal::from(SECONDS_PER_YEAR) / Decimal::from(elapsed)
+}
+
+/// Annualised yield of a reward program as a fraction, valuing the pool at
+/// `n` times its reserve of the reward token (as in `fee_apy`).
+pub fn reward_apy(pool: &Pool, program: &RewardProgram) -> Decimal {
+    let pool_value = match pool.reserves.get(&program.token) {
+        Some(reserve) => reserve * BigUint::from(pool.tokens.len()),
+        None => return Decimal::ZERO,
+    };
+    ratio_to_decimal(&program.per_year, &pool_value).unwrap_or(Decimal::ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PoolType, Token};
+    use std::collections::HashMap;
+
+    fn create_sample_pool() -> Pool {
+        let tokens = ["ETH", "USDC"]
+            .iter()
+            .map(|symbol| Token {
+                address: symbol.to_string(),
+                symbol: symbol.to_string(),
+                decimals: 18,
+            })
+            .collect();
+
+        let mut reserves = HashMap::new();
+        reserves.insert("ETH".to_string(), BigUint::from(1000u64));
+        reserves.insert("USDC".to_string(), BigUint::from(2000u64));
+
+        let mut pool = Pool::new(
+            "ETH-USDC".to_string(),
+            tokens,
+            reserves,
+            300,
+            PoolType::ConstantProduct,
+        );
+        // The initial supply belongs to whoever seeded the pool
+        pool.lp_positions
+            .insert("creator".to_string(), pool.total_supply.clone());
+        pool
+    }
+
+    fn deposit(pool: &mut Pool, owner: &str, eth: u64, usdc: u64) {
+        let mut amounts = HashMap::new();
+        amounts.insert("ETH".to_string(), BigUint::from(eth));
+        amounts.insert("USDC".to_string(), BigUint::from(usdc));
+        pool.add_liquidity_for(owner, amounts).unwrap();
+    }
+
+    #[test]
+    fn test_rewards_split_pro_rata() {
+        let mut pool = create_sample_pool();
+        deposit(&mut pool, "alice", 100, 200);
+        deposit(&mut pool, "bob", 333, 666);
+
+        let total_reward = BigUint::from(1_000_000u64);
+        let owners = ["creator", "alice", "bob"];
+        let distributed: BigUint = owners
+            .iter()
+            .map(|owner| calculate_rewards(&pool, &total_reward, owner))
+            .sum();
+
+        assert!(distributed <= total_reward);
+        assert!(&total_reward - &distributed < BigUint::from(owners.len()));
+        assert!(
+            calculate_rewards(&pool, &total_reward, "bob")
+                > calculate_rewards(&pool, &total_reward, "alice")
+        );
+    }
+
+    #[test]
+    fn test_fee_and_reward_apy() {
+        let mut pool = create_sample_pool();
+        // Half a year of fees worth 1% of the ETH side and 2% of the USDC side
+        pool.fees_since = 1_000;
+        pool.cumulative_fees
+            .insert("ETH".to_string(), BigUint::from(10u64));
+        pool.cumulative_fees
+            .insert("USDC".to_string(), BigUint::from(40u64));
+        let now = pool.fees_since + SECONDS_PER_YEAR / 2;
+
+        assert_eq!(fee_apy(&pool, now), Decimal::new(3, 2));
+        assert_eq!(fee_apy(&pool, pool.fees_since), Decimal::ZERO);
+
+        // 1000 USDC a year against a pool worth 4000 USDC
+        let program = RewardProgram {
+            token: "USDC".to_string(),
+            per_year: BigUint::from(1000u64),
+        };
+        assert_eq!(reward_apy(&pool, &program), Decimal::new(25, 2));
+    }
+
+    #[test]
+    fn test_rewards_for_unknown_owner_are_zero() {
+        let pool = create_sample_pool();
+        let reward = calculate_rewards(&pool, &BigUint::from(1000u64), "nobody");
+        assert!(reward.is_zero());
+    }
+}
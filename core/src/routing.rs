@@ -0,0 +1,661 @@
+use crate::simulation::PoolSim;
+use crate::{Pool, SwapError};
+use num_bigint::BigUint;
+use num_traits::Zero;
+use rust_decimal::Decimal;
+use std::borrow::Borrow;
+use std::collections::HashSet;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RouteError {
+    #[error("Route path does not match its pools")]
+    InvalidPath,
+    #[error(transparent)]
+    Swap(#[from] SwapError),
+}
+
+/// A swap path through one or more pools. Hop `i` swaps `path[i]` for
+/// `path[i + 1]` in the pool `pools[i]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    pub pools: Vec<String>,
+    pub path: Vec<String>,
+    pub expected_output: BigUint,
+    pub fee_rates: Vec<u64>, // basis points, one per hop
+}
+
+impl Route {
+    /// Quotes `input_amount` hop by hop through `pools` along `path`. Hops
+    /// run against simulated reserves, so a pool used twice sees the effect
+    /// of its earlier hop.
+    pub fn quote(
+        pools: &[&Pool],
+        path: &[&str],
+        input_amount: &BigUint,
+    ) -> Result<Self, SwapError> {
+        if pools.is_empty() || path.len() != pools.len() + 1 {
+            return Err(SwapError::TokenNotFound);
+        }
+
+        let mut sims: Vec<PoolSim> = Vec::with_capacity(pools.len());
+        let mut amount = input_amount.clone();
+        for (pool, hop) in pools.iter().zip(path.windows(2)) {
+            let index = match sims.iter().position(|sim| sim.id == pool.id) {
+                Some(index) => index,
+                None => {
+                    sims.push(pool.clone_for_simulation());
+                    sims.len() - 1
+                }
+            };
+            amount = sims[index].swap(hop[0], hop[1], &amount)?;
+        }
+
+        Ok(Route {
+            pools: pools.iter().map(|p| p.id.clone()).collect(),
+            path: path.iter().map(|t| t.to_string()).collect(),
+            expected_output: amount,
+            fee_rates: pools
+                .iter()
+                .zip(path)
+                .map(|(pool, input)| pool.fee_rate_for(input))
+                .collect(),
+        })
+    }
+
+    /// Effective fee of the whole route in basis points. Each hop only
+    /// charges its fee on what is left after the previous hops, so the
+    /// total is `1 - prod(1 - fee_i)` rather than the plain sum.
+    pub fn total_fee_bps(&self) -> Decimal {
+        let bps = Decimal::from(10000u64);
+        let retained = self.fee_rates.iter().fold(Decimal::ONE, |acc, fee| {
+            acc * (Decimal::ONE - Decimal::from(*fee) / bps)
+        });
+
+        (Decimal::ONE - retained) * bps
+    }
+}
+
+/// Operator restrictions on which pools routes may use, by pool id.
+/// The default allows every pool.
+#[derive(Debug, Clone, Default)]
+pub struct RoutingConfig {
+    pub excluded_pool_ids: HashSet<String>,
+    pub only_pool_ids: Option<HashSet<String>>, // when set, no pool outside it
+}
+
+impl RoutingConfig {
+    /// Whether routes may go through `pool`. Exclusion wins over the
+    /// only-list.
+    pub fn allows(&self, pool: &Pool) -> bool {
+        !self.excluded_pool_ids.contains(&pool.id)
+            && self
+                .only_pool_ids
+                .as_ref()
+                .is_none_or(|only| only.contains(&pool.id))
+    }
+}
+
+/// Input of `path[0]` needed to receive at least `desired_output` of the last
+/// token, working backwards from the final hop. Hop `i` swaps `path[i]` for
+/// `path[i + 1]` in `pools[i]`, as in `Route::quote`.
+pub fn input_for_output(
+    pools: &[&Pool],
+    path: &[&str],
+    desired_output: &BigUint,
+) -> Result<BigUint, RouteError> {
+    if pools.is_empty() || path.len() != pools.len() + 1 {
+        return Err(RouteError::InvalidPath);
+    }
+
+    // Each hop must produce the input the next one needs
+    let mut amount = desired_output.clone();
+    for (pool, hop) in pools.iter().zip(path.windows(2)).rev() {
+        amount = pool.calculate_input_for_output(hop[0], hop[1], &amount)?;
+    }
+
+    Ok(amount)
+}
+
+/// Path of at most `max_hops` pools turning `amount` of `input` into the
+/// most `output`. Paths never revisit a token or reuse a pool, so each hop
+/// quotes against untouched reserves.
+pub fn find_best_route(
+    pools: &[Pool],
+    input: &str,
+    output: &str,
+    amount: &BigUint,
+    max_hops: usize,
+) -> Option<Route> {
+    find_best_route_with(
+        pools,
+        input,
+        output,
+        amount,
+        max_hops,
+        &RoutingConfig::default(),
+    )
+}
+
+/// `find_best_route` over only the pools `config` allows. Takes owned or
+/// borrowed pools, so callers holding pools elsewhere need not copy them.
+pub fn find_best_route_with<P: Borrow<Pool>>(
+    pools: &[P],
+    input: &str,
+    output: &str,
+    amount: &BigUint,
+    max_hops: usize,
+    config: &RoutingConfig,
+) -> Option<Route> {
+    let candidates: Vec<usize> = (0..pools.len())
+        .filter(|&index| config.allows(pools[index].borrow()))
+        .collect();
+    let mut search = RouteSearch {
+        pools,
+        candidates: &candidates,
+        output,
+        max_hops,
+        hops: Vec::new(),
+        path: vec![input],
+        best: None,
+    };
+    search.extend(amount);
+
+    let (hops, path, _) = search.best?;
+    let hops: Vec<&Pool> = hops.iter().map(|&index| pools[index].borrow()).collect();
+    Route::quote(&hops, &path, amount).ok()
+}
+
+// Depth-first search state for `find_best_route`
+struct RouteSearch<'a, P> {
+    pools: &'a [P],
+    candidates: &'a [usize], // indices into `pools` that hops may use
+    output: &'a str,
+    max_hops: usize,
+    hops: Vec<usize>,   // indices into `pools`
+    path: Vec<&'a str>, // tokens visited so far, starting with the input
+    best: Option<(Vec<usize>, Vec<&'a str>, BigUint)>,
+}
+
+impl<'a, P: Borrow<Pool>> RouteSearch<'a, P> {
+    fn extend(&mut self, amount: &BigUint) {
+        if self.hops.len() == self.max_hops {
+            return;
+        }
+        let current = self.path[self.path.len() - 1];
+
+        let (pools, candidates) = (self.pools, self.candidates);
+        for &index in candidates {
+            let pool: &Pool = pools[index].borrow();
+            if self.hops.contains(&index) || !pool.tokens.iter().any(|t| t.address == current) {
+                continue;
+            }
+            for token in &pool.tokens {
+                let next = token.address.as_str();
+                if self.path.contains(&next) {
+                    continue;
+                }
+                let Ok(received) = pool.calculate_swap_output(current, next, amount) else {
+                    continue;
+                };
+
+                self.hops.push(index);
+                self.path.push(next);
+                if next == self.output {
+                    if self
+                        .best
+                        .as_ref()
+                        .is_none_or(|(_, _, best)| received > *best)
+                    {
+                        self.best = Some((self.hops.clone(), self.path.clone(), received));
+                    }
+                } else {
+                    self.extend(&received);
+                }
+                self.hops.pop();
+                self.path.pop();
+            }
+        }
+    }
+}
+
+/// Splits `amount` of `input` across pools trading it for `output` to
+/// maximize the total received. The order goes out in `max_splits` equal
+/// chunks, each to the pool paying the most for it on top of what it was
+/// already given. Returns `(index into pools, amount)` for every pool that
+/// got a share, summing exactly to `amount`; empty if no pool can quote.
+pub fn optimal_split(
+    pools: &[&Pool],
+    input: &str,
+    output: &str,
+    amount: &BigUint,
+    max_splits: usize,
+) -> Vec<(usize, BigUint)> {
+    let splits = max_splits.max(1);
+    let chunk = amount / splits;
+    // The remainder rides on the first chunk so the shares add up
+    let first_chunk = &chunk + amount % splits;
+    let sizes = std::iter::once(first_chunk).chain(std::iter::repeat_n(chunk, splits - 1));
+
+    let mut allocated = vec![BigUint::zero(); pools.len()];
+    let mut received = vec![BigUint::zero(); pools.len()];
+    for size in sizes.filter(|size| !size.is_zero()) {
+        // Output only grows with input, so the marginal gain is never negative
+        let best = pools
+            .iter()
+            .enumerate()
+            .filter_map(|(index, pool)| {
+                let total = &allocated[index] + &size;
+                let total = pool.calculate_swap_output(input, output, &total).ok()?;
+                Some((index, total))
+            })
+            .max_by(|(a, total_a), (b, total_b)| {
+                (total_a - &received[*a]).cmp(&(total_b - &received[*b]))
+            });
+        let Some((index, total)) = best else {
+            return Vec::new();
+        };
+        allocated[index] += size;
+        received[index] = total;
+    }
+
+    allocated
+        .into_iter()
+        .enumerate()
+        .filter(|(_, share)| !share.is_zero())
+        .collect()
+}
+
+/// Picks the pool giving the most `token_b` for `amount` of `token_a`, by
+/// evaluating each pool's actual output at that size. Small trades tend to
+/// favour low fees and large trades deep reserves.
+pub fn best_pool_for_size<'a>(
+    pools: &'a [Pool],
+    token_a: &str,
+    token_b: &str,
+    amount: &BigUint,
+) -> Option<&'a Pool> {
+    let pools: Vec<&Pool> = pools.iter().collect();
+    Pool::best_quote_among(&pools, token_a, token_b, amount).map(|(pool, _)| pool)
+}
+
+impl Pool {
+    /// The pool paying the most `output_token` for `amount` of
+    /// `input_token`, with that output, whatever each pool's curve. For
+    /// single-pair UIs choosing between, say, a stable and a
+    /// constant-product pool. `None` if no pool can quote the trade.
+    pub fn best_quote_among<'a>(
+        pools: &[&'a Pool],
+        input_token: &str,
+        output_token: &str,
+        amount: &BigUint,
+    ) -> Option<(&'a Pool, BigUint)> {
+        pools
+            .iter()
+            .filter_map(|&pool| {
+                pool.calculate_swap_output(input_token, output_token, amount)
+                    .ok()
+                    .map(|output| (pool, output))
+            })
+            .max_by(|(_, a), (_, b)| a.cmp(b))
+    }
+}
+
+/// Distinct `fee_rate`s, lowest first, of the pools trading `token_a`
+/// for `token_b`.
+pub fn available_fee_tiers(pools: &[Pool], token_a: &str, token_b: &str) -> Vec<u64> {
+    let mut tiers: Vec<u64> = pools
+        .iter()
+        .filter(|pool| pool.supports_pair(token_a, token_b))
+        .map(|pool| pool.fee_rate)
+        .collect();
+    tiers.sort_unstable();
+    tiers.dedup();
+    tiers
+}
+
+/// Combined depth of every pool trading `token_a` for `token_b`: for each
+/// price level, how much `token_a` the pools absorb together before its
+/// price falls to that level. Pools without a depth curve are left out.
+pub fn aggregate_depth(
+    pools: &[Pool],
+    token_a: &str,
+    token_b: &str,
+    price_levels: &[Decimal],
+) -> Vec<(Decimal, BigUint)> {
+    price_levels
+        .iter()
+        .map(|&price| {
+            let depth = pools
+                .iter()
+                .filter_map(|pool| {
+                    let base = &pool.find_token(token_a)?.address;
+                    let quote = &pool.find_token(token_b)?.address;
+                    pool.depth_at_price(base, quote, price).ok()
+                })
+                .sum();
+            (price, depth)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PoolType, Token};
+    use std::collections::HashMap;
+
+    fn create_pool(id: &str, token_a: &str, token_b: &str, reserve_a: u64, reserve_b: u64) -> Pool {
+        create_pool_with_fee(id, token_a, token_b, reserve_a.into(), reserve_b.into(), 30)
+    }
+
+    fn create_pool_with_fee(
+        id: &str,
+        token_a: &str,
+        token_b: &str,
+        reserve_a: u128,
+        reserve_b: u128,
+        fee_rate: u64,
+    ) -> Pool {
+        let tokens = [token_a, token_b]
+            .iter()
+            .map(|symbol| Token {
+                address: symbol.to_string(),
+                symbol: symbol.to_string(),
+                decimals: 18,
+            })
+            .collect();
+
+        let mut reserves = HashMap::new();
+        reserves.insert(token_a.to_string(), BigUint::from(reserve_a));
+        reserves.insert(token_b.to_string(), BigUint::from(reserve_b));
+
+        Pool::new(
+            id.to_string(),
+            tokens,
+            reserves,
+            fee_rate,
+            PoolType::ConstantProduct,
+        )
+    }
+
+    #[test]
+    fn test_two_hop_route_compounds_fees() {
+        let eth_usdc = create_pool("ETH-USDC", "ETH", "USDC", 1_000_000, 2_000_000);
+        let usdc_dai = create_pool("USDC-DAI", "USDC", "DAI", 5_000_000, 5_000_000);
+
+        let route = Route::quote(
+            &[&eth_usdc, &usdc_dai],
+            &["ETH", "USDC", "DAI"],
+            &BigUint::from(1000u64),
+        )
+        .unwrap();
+
+        assert_eq!(route.pools, vec!["ETH-USDC", "USDC-DAI"]);
+        assert!(route.expected_output > BigUint::from(0u64));
+        assert_eq!(route.total_fee_bps(), Decimal::new(5991, 2)); // 59.91 bps, not 60
+    }
+
+    #[test]
+    fn test_route_reusing_a_pool_sees_its_earlier_hop() {
+        let eth_usdc = create_pool("ETH-USDC", "ETH", "USDC", 1_000_000, 2_000_000);
+        let amount = BigUint::from(100_000u64);
+
+        // Selling into the pool and straight back cannot gain anything
+        let route =
+            Route::quote(&[&eth_usdc, &eth_usdc], &["ETH", "USDC", "ETH"], &amount).unwrap();
+        assert!(route.expected_output < amount);
+    }
+
+    #[test]
+    fn test_route_rejects_mismatched_path() {
+        let eth_usdc = create_pool("ETH-USDC", "ETH", "USDC", 1000, 2000);
+
+        let result = Route::quote(&[&eth_usdc], &["ETH"], &BigUint::from(10u64));
+        assert!(matches!(result, Err(SwapError::TokenNotFound)));
+    }
+
+    #[test]
+    fn test_best_route_goes_through_an_intermediate_pool() {
+        let pools = [
+            create_pool("ETH-USDC", "ETH", "USDC", 1_000_000, 2_000_000),
+            create_pool("USDC-DAI", "USDC", "DAI", 5_000_000, 5_000_000),
+            create_pool("ETH-WBTC", "ETH", "WBTC", 1_000_000, 50_000),
+        ];
+        let amount = BigUint::from(1000u64);
+
+        let route = find_best_route(&pools, "ETH", "DAI", &amount, 3).unwrap();
+        assert_eq!(route.pools, vec!["ETH-USDC", "USDC-DAI"]);
+        assert_eq!(route.path, vec!["ETH", "USDC", "DAI"]);
+        let quoted = Route::quote(&[&pools[0], &pools[1]], &["ETH", "USDC", "DAI"], &amount);
+        assert_eq!(route.expected_output, quoted.unwrap().expected_output);
+
+        // A direct pool wins when it pays more than the detour
+        let mut with_direct = pools.to_vec();
+        with_direct.push(create_pool("ETH-DAI", "ETH", "DAI", 1_000_000, 3_000_000));
+        let route = find_best_route(&with_direct, "ETH", "DAI", &amount, 3).unwrap();
+        assert_eq!(route.pools, vec!["ETH-DAI"]);
+
+        assert!(find_best_route(&pools, "ETH", "DAI", &amount, 1).is_none());
+        assert!(find_best_route(&pools, "ETH", "LINK", &amount, 3).is_none());
+    }
+
+    #[test]
+    fn test_best_route_through_a_weighted_pool() {
+        let mut weighted = create_pool("WEIGHTED", "ETH", "DAI", 2_000_000, 6_000_000);
+        weighted.pool_type = PoolType::Weighted {
+            weights: vec![50, 50],
+        };
+        let pools = [
+            create_pool("CP", "ETH", "DAI", 1_000_000, 3_000_000),
+            weighted,
+        ];
+        let amount = BigUint::from(1000u64);
+
+        // The deeper weighted pool pays more, and its route is quoted too
+        let route = find_best_route(&pools, "ETH", "DAI", &amount, 3).unwrap();
+        assert_eq!(route.pools, vec!["WEIGHTED"]);
+        assert_eq!(
+            route.expected_output,
+            pools[1]
+                .calculate_swap_output("ETH", "DAI", &amount)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_routing_config_filters_candidate_pools() {
+        let pools = [
+            create_pool("ETH-DAI", "ETH", "DAI", 1_000_000, 3_000_000),
+            create_pool("ETH-USDC", "ETH", "USDC", 1_000_000, 2_000_000),
+            create_pool("USDC-DAI", "USDC", "DAI", 5_000_000, 5_000_000),
+            create_pool("ETH-DAI-OLD", "ETH", "DAI", 1_000_000, 2_000_000),
+        ];
+        let amount = BigUint::from(1000u64);
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect();
+        let route = |config: &RoutingConfig| {
+            find_best_route_with(&pools, "ETH", "DAI", &amount, 3, config).map(|route| route.pools)
+        };
+        assert_eq!(route(&RoutingConfig::default()).unwrap(), vec!["ETH-DAI"]);
+
+        // With the direct pools excluded the router falls back to the
+        // detour, never to an excluded pool
+        let excluded = RoutingConfig {
+            excluded_pool_ids: ids(&["ETH-DAI", "ETH-DAI-OLD"]),
+            ..RoutingConfig::default()
+        };
+        assert_eq!(route(&excluded).unwrap(), vec!["ETH-USDC", "USDC-DAI"]);
+
+        // The only-list leaves just the pools named in it
+        let only = RoutingConfig {
+            only_pool_ids: Some(ids(&["ETH-DAI-OLD", "ETH-USDC"])),
+            ..RoutingConfig::default()
+        };
+        assert_eq!(route(&only).unwrap(), vec!["ETH-DAI-OLD"]);
+
+        let both = RoutingConfig {
+            excluded_pool_ids: ids(&["ETH-DAI-OLD"]),
+            only_pool_ids: Some(ids(&["ETH-DAI-OLD", "ETH-USDC"])),
+        };
+        assert_eq!(route(&both), None);
+    }
+
+    #[test]
+    fn test_split_beats_either_pool_alone() {
+        let deep = create_pool("DEEP", "ETH", "USDC", 1_000_000, 2_000_000);
+        let shallow = create_pool("SHALLOW", "ETH", "USDC", 300_000, 600_000);
+        let pools = [&deep, &shallow];
+        let amount = BigUint::from(200_001u64);
+
+        let split = optimal_split(&pools, "ETH", "USDC", &amount, 20);
+        assert_eq!(split.len(), 2);
+        assert_eq!(
+            split.iter().map(|(_, share)| share).sum::<BigUint>(),
+            amount
+        );
+        // The deeper pool takes the larger share
+        assert!(split[0].1 > split[1].1);
+
+        let total: BigUint = split
+            .iter()
+            .map(|(index, share)| {
+                pools[*index]
+                    .calculate_swap_output("ETH", "USDC", share)
+                    .unwrap()
+            })
+            .sum();
+        for pool in pools {
+            assert!(total > pool.calculate_swap_output("ETH", "USDC", &amount).unwrap());
+        }
+
+        assert!(optimal_split(&pools, "ETH", "DAI", &amount, 20).is_empty());
+    }
+
+    #[test]
+    fn test_input_for_output_covers_two_hops() {
+        let eth_usdc = create_pool("ETH-USDC", "ETH", "USDC", 1_000_000, 2_000_000);
+        let usdc_dai = create_pool("USDC-DAI", "USDC", "DAI", 5_000_000, 5_000_000);
+        let pools = [&eth_usdc, &usdc_dai];
+        let path = ["ETH", "USDC", "DAI"];
+        let desired = BigUint::from(12_345u64);
+
+        let input = input_for_output(&pools, &path, &desired).unwrap();
+
+        // Swapping the computed input forward reaches the target...
+        let route = Route::quote(&pools, &path, &input).unwrap();
+        assert!(route.expected_output >= desired);
+        // ...and one unit less falls short, so it is not overpaying
+        let route = Route::quote(&pools, &path, &(input - 1u64)).unwrap();
+        assert!(route.expected_output < desired);
+    }
+
+    #[test]
+    fn test_input_for_output_rejects_bad_paths() {
+        let eth_usdc = create_pool("ETH-USDC", "ETH", "USDC", 1000, 2000);
+
+        let result = input_for_output(&[&eth_usdc], &["ETH"], &BigUint::from(10u64));
+        assert!(matches!(result, Err(RouteError::InvalidPath)));
+
+        let result = input_for_output(&[&eth_usdc], &["ETH", "USDC"], &BigUint::from(2000u64));
+        assert!(matches!(
+            result,
+            Err(RouteError::Swap(SwapError::InsufficientLiquidity))
+        ));
+    }
+
+    #[test]
+    fn test_best_pool_depends_on_trade_size() {
+        let unit = 10u128.pow(18);
+        let pools = vec![
+            create_pool_with_fee("SHALLOW", "ETH", "USDC", 10_000 * unit, 20_000 * unit, 5),
+            create_pool_with_fee(
+                "DEEP",
+                "ETH",
+                "USDC",
+                1_000_000 * unit,
+                2_000_000 * unit,
+                100,
+            ),
+        ];
+
+        // A small trade barely moves either pool, so the lower fee wins
+        let small = BigUint::from(unit);
+        let best = best_pool_for_size(&pools, "ETH", "USDC", &small).unwrap();
+        assert_eq!(best.id, "SHALLOW");
+
+        // A trade worth 10% of the shallow pool pays more in impact than in fees
+        let large = BigUint::from(1000 * unit);
+        let best = best_pool_for_size(&pools, "ETH", "USDC", &large).unwrap();
+        assert_eq!(best.id, "DEEP");
+
+        assert!(best_pool_for_size(&pools, "ETH", "DAI", &small).is_none());
+    }
+
+    #[test]
+    fn test_best_quote_picks_the_curve_that_pays_most() {
+        let mut stable = create_pool_with_fee("STABLE", "USDC", "DAI", 100_000, 100_000, 4);
+        stable.pool_type = PoolType::StableSwap;
+        let volatile = create_pool_with_fee("VOLATILE", "USDC", "DAI", 1_000_000, 1_000_000, 30);
+        let pools = [&stable, &volatile];
+        let best = |amount: u64| {
+            let amount = BigUint::from(amount);
+            let (pool, output) = Pool::best_quote_among(&pools, "USDC", "DAI", &amount).unwrap();
+            assert_eq!(
+                output,
+                pool.calculate_swap_output("USDC", "DAI", &amount).unwrap()
+            );
+            pool.id.clone()
+        };
+
+        // A balanced trade stays on the flat part of the stable curve
+        assert_eq!(best(1_000), "STABLE");
+        // One that drains most of the shallow stable pool pays more in the
+        // deeper constant-product pool
+        assert_eq!(best(200_000), "VOLATILE");
+
+        assert!(Pool::best_quote_among(&pools, "USDC", "ETH", &BigUint::from(1u8)).is_none());
+    }
+
+    #[test]
+    fn test_available_fee_tiers_are_distinct_and_sorted() {
+        let pools = vec![
+            create_pool_with_fee("ETH-USDC-100", "ETH", "USDC", 1000, 2000, 100),
+            create_pool_with_fee("ETH-USDC-5", "ETH", "USDC", 1000, 2000, 5),
+            create_pool_with_fee("USDC-ETH-30", "USDC", "ETH", 2000, 1000, 30),
+            create_pool_with_fee("ETH-USDC-30", "ETH", "USDC", 1000, 2000, 30),
+            create_pool_with_fee("ETH-DAI-1", "ETH", "DAI", 1000, 2000, 1),
+        ];
+
+        assert_eq!(available_fee_tiers(&pools, "ETH", "USDC"), vec![5, 30, 100]);
+        assert_eq!(available_fee_tiers(&pools, "USDC", "ETH"), vec![5, 30, 100]);
+        assert!(available_fee_tiers(&pools, "USDC", "DAI").is_empty());
+    }
+
+    #[test]
+    fn test_aggregate_depth_sums_pools() {
+        // Both USDC pools price ETH at 4 USDC
+        let pools = vec![
+            create_pool("ETH-USDC-1", "ETH", "USDC", 1000, 4000),
+            create_pool("ETH-USDC-2", "ETH", "USDC", 500, 2000),
+            create_pool("ETH-DAI", "ETH", "DAI", 1000, 1000),
+        ];
+        let levels = [
+            Decimal::from(5),
+            Decimal::from(4),
+            Decimal::ONE,
+            Decimal::new(25, 2),
+        ];
+
+        // 1000 ETH at k = 4e6 falls to a price of 1 once 2000 ETH are in the pool
+        let depth = |price| pools[0].depth_at_price("ETH", "USDC", price).unwrap();
+        assert_eq!(depth(Decimal::from(4)), BigUint::zero());
+        assert_eq!(depth(Decimal::ONE), BigUint::from(1000u64));
+
+        let combined = aggregate_depth(&pools, "eth", "USDC", &levels);
+        let expected: Vec<(Decimal, BigUint)> = levels
+            .iter()
+            .copied()
+            .zip([0u64, 0, 1500, 4500].into_iter().map(BigUint::from))
+            .collect();
+        assert_eq!(combined, expected);
+    }
+}
@@ -0,0 +1,190 @@
+//! `BigUint` as a decimal string, for `#[serde(with = "serde_biguint")]`.
+//! Any size round-trips exactly; JSON numbers cannot carry 256-bit values.
+//! Only canonical strings are accepted: ASCII digits without sign,
+//! whitespace or leading zeros. The submodules do the same for the
+//! containers `Pool` keeps amounts in.
+
+use num_bigint::BigUint;
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt;
+
+pub fn serialize<S: Serializer>(value: &BigUint, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BigUint, D::Error> {
+    deserializer.deserialize_str(DecimalVisitor)
+}
+
+/// Parses a canonical decimal string as produced by `serialize`.
+pub fn parse(value: &str) -> Result<BigUint, String> {
+    if value.is_empty() {
+        return Err("empty amount".to_string());
+    }
+    if !value.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(format!("amount {:?} is not a plain decimal integer", value));
+    }
+    if value.len() > 1 && value.starts_with('0') {
+        return Err(format!("amount {:?} has leading zeros", value));
+    }
+    BigUint::parse_bytes(value.as_bytes(), 10).ok_or_else(|| format!("invalid amount {:?}", value))
+}
+
+// Borrowed and owned amounts that go through this module's format, so the
+// container modules can lean on serde's own impls
+struct Ref<'a>(&'a BigUint);
+
+impl Serialize for Ref<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize(self.0, serializer)
+    }
+}
+
+struct Owned(BigUint);
+
+impl<'de> Deserialize<'de> for Owned {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize(deserializer).map(Owned)
+    }
+}
+
+/// `Option<BigUint>`, with `None` as `null`.
+pub mod option {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<BigUint>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        value.as_ref().map(Ref).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<BigUint>, D::Error> {
+        Ok(Option::<Owned>::deserialize(deserializer)?.map(|amount| amount.0))
+    }
+}
+
+/// `HashMap<String, BigUint>`, such as reserves by token.
+pub mod map {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        value: &HashMap<String, BigUint>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(value.iter().map(|(key, amount)| (key, Ref(amount))))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<String, BigUint>, D::Error> {
+        let map = HashMap::<String, Owned>::deserialize(deserializer)?;
+        Ok(map
+            .into_iter()
+            .map(|(key, amount)| (key, amount.0))
+            .collect())
+    }
+}
+
+/// `Option<HashMap<String, BigUint>>`, with `None` as `null`.
+pub mod option_map {
+    use super::*;
+
+    struct MapRef<'a>(&'a HashMap<String, BigUint>);
+
+    impl Serialize for MapRef<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            map::serialize(self.0, serializer)
+        }
+    }
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<HashMap<String, BigUint>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        value.as_ref().map(MapRef).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<HashMap<String, BigUint>>, D::Error> {
+        let map = Option::<HashMap<String, Owned>>::deserialize(deserializer)?;
+        Ok(map.map(|map| {
+            map.into_iter()
+                .map(|(key, amount)| (key, amount.0))
+                .collect()
+        }))
+    }
+}
+
+struct DecimalVisitor;
+
+impl Visitor<'_> for DecimalVisitor {
+    type Value = BigUint;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a non-negative integer as a decimal string")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<BigUint, E> {
+        parse(value).map_err(E::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Reserve {
+        #[serde(with = "super")]
+        amount: BigUint,
+    }
+
+    const U256_MAX: &str =
+        "115792089237316195423570985008687907853269984665640564039457584007913129639935";
+
+    #[test]
+    fn test_round_trips_u256_max_and_beyond() {
+        let max: BigUint = (BigUint::from(1u8) << 256) - 1u8;
+        let json = serde_json::to_string(&Reserve {
+            amount: max.clone(),
+        })
+        .unwrap();
+        assert_eq!(json, format!("{{\"amount\":\"{}\"}}", U256_MAX));
+        assert_eq!(serde_json::from_str::<Reserve>(&json).unwrap().amount, max);
+
+        let beyond = max * 1000u32 + 7u32;
+        let json = serde_json::to_string(&Reserve {
+            amount: beyond.clone(),
+        })
+        .unwrap();
+        assert_eq!(
+            serde_json::from_str::<Reserve>(&json).unwrap().amount,
+            beyond
+        );
+
+        let zero: Reserve = serde_json::from_str(r#"{"amount":"0"}"#).unwrap();
+        assert_eq!(zero.amount, BigUint::from(0u8));
+    }
+
+    #[test]
+    fn test_rejects_non_canonical_strings() {
+        for malformed in [
+            "", "007", "+1", "-1", " 1", "1 ", "1e18", "1.0", "0x10", "1_000",
+        ] {
+            let json = format!("{{\"amount\":{:?}}}", malformed);
+            assert!(
+                serde_json::from_str::<Reserve>(&json).is_err(),
+                "accepted {:?}",
+                malformed
+            );
+        }
+        // Numbers are refused too: large ones would already have lost precision
+        assert!(serde_json::from_str::<Reserve>(r#"{"amount":1000}"#).is_err());
+    }
+}
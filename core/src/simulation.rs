@@ -0,0 +1,272 @@
+use crate::fees::{Direction, FeeStrategy, FlatFee};
+use crate::{valid_weights, Pool, PoolType, SwapError};
+use num_bigint::BigUint;
+
+/// Just the state the swap math needs, so route candidates can be simulated
+/// without cloning a whole `Pool` (reserve map, fee history, LP positions).
+/// Reserves are indexed like `tokens`. Concentrated pools price off their
+/// tick map rather than their reserves, so they are simulated on a copy.
+#[derive(Debug, Clone)]
+pub struct PoolSim {
+    pub id: String,
+    tokens: Vec<String>,
+    reserves: Vec<BigUint>,
+    fee_rates: Vec<u64>, // by input token
+    pool_type: PoolType,
+    amplification: u64,
+    min_reserves: Vec<Option<BigUint>>, // `Pool::min_reserves`, by token
+    concentrated: Option<Box<Pool>>,
+}
+
+impl PoolSim {
+    pub fn from_pool(pool: &Pool) -> Self {
+        PoolSim {
+            id: pool.id.clone(),
+            tokens: pool.tokens.iter().map(|t| t.address.clone()).collect(),
+            reserves: pool
+                .tokens
+                .iter()
+                .map(|t| pool.reserves.get(&t.address).cloned().unwrap_or_default())
+                .collect(),
+            fee_rates: pool
+                .tokens
+                .iter()
+                .map(|t| pool.fee_rate_for(&t.address))
+                .collect(),
+            pool_type: pool.pool_type.clone(),
+            amplification: pool.amplification,
+            min_reserves: pool
+                .tokens
+                .iter()
+                .map(|t| {
+                    pool.min_reserves
+                        .as_ref()
+                        .and_then(|floors| floors.get(&t.address))
+                        .cloned()
+                })
+                .collect(),
+            concentrated: (pool.pool_type == PoolType::ConcentratedLiquidity)
+                .then(|| Box::new(pool.clone())),
+        }
+    }
+
+    pub fn reserve(&self, token: &str) -> Option<&BigUint> {
+        self.index_of(token).ok().map(|i| &self.reserves[i])
+    }
+
+    /// Same result as `Pool::calculate_swap_output` on the source pool.
+    pub fn calculate_swap_output(
+        &self,
+        input_token: &str,
+        output_token: &str,
+        input_amount: &BigUint,
+    ) -> Result<BigUint, SwapError> {
+        let input_idx = self.index_of(input_token)?;
+        let output_idx = self.index_of(output_token)?;
+
+        match self.pool_type {
+            PoolType::ConstantProduct => Pool::constant_product_output(
+                &self.reserves[input_idx],
+                &self.reserves[output_idx],
+                input_amount,
+                self.fee_rates[input_idx],
+            ),
+            PoolType::StableSwap => Pool::stable_swap_output(
+                &self.reserves,
+                input_idx,
+                output_idx,
+                input_amount,
+                self.amplification,
+                self.fee_rates[input_idx],
+            )
+            .map(|(output_amount, _)| output_amount),
+            PoolType::Weighted { ref weights } if valid_weights(weights, self.tokens.len()) => {
+                let amount_after_fee = FlatFee::new(self.fee_rates[input_idx])
+                    .apply(input_amount, Direction::Forward)?
+                    .amount_after_fee;
+                Pool::weighted_output(
+                    &self.reserves[input_idx],
+                    &self.reserves[output_idx],
+                    weights[input_idx],
+                    weights[output_idx],
+                    &amount_after_fee,
+                )
+            }
+            PoolType::ConcentratedLiquidity => match &self.concentrated {
+                Some(pool) => pool.calculate_swap_output(input_token, output_token, input_amount),
+                None => Err(SwapError::UnsupportedPoolType),
+            },
+            _ => Err(SwapError::UnsupportedPoolType),
+        }
+    }
+
+    /// Applies a swap to the simulated reserves, like `Pool::execute_swap`.
+    pub fn swap(
+        &mut self,
+        input_token: &str,
+        output_token: &str,
+        input_amount: &BigUint,
+    ) -> Result<BigUint, SwapError> {
+        let input_idx = self.index_of(input_token)?;
+        let output_idx = self.index_of(output_token)?;
+        if let Some(pool) = &mut self.concentrated {
+            let output_amount = pool.execute_swap(input_token, output_token, input_amount)?;
+            self.reserves[input_idx] += input_amount;
+            self.reserves[output_idx] -= &output_amount;
+            return Ok(output_amount);
+        }
+        let output_amount = self.calculate_swap_output(input_token, output_token, input_amount)?;
+
+        if output_amount > self.reserves[output_idx] {
+            return Err(SwapError::InsufficientLiquidity);
+        }
+        if output_amount == self.reserves[output_idx] {
+            return Err(SwapError::WouldEmptyReserve);
+        }
+        // The same checks as `Pool::apply_swap`
+        let remaining = &self.reserves[output_idx] - &output_amount;
+        if self.min_reserves[output_idx]
+            .as_ref()
+            .is_some_and(|floor| remaining < *floor)
+        {
+            return Err(SwapError::ReserveFloorBreached);
+        }
+        if self.pool_type == PoolType::ConstantProduct {
+            let input_reserve = &self.reserves[input_idx];
+            let k_before = input_reserve * &self.reserves[output_idx];
+            if (input_reserve + input_amount) * &remaining < k_before {
+                return Err(SwapError::InvariantViolated);
+            }
+        }
+        self.reserves[input_idx] += input_amount;
+        self.reserves[output_idx] -= &output_amount;
+
+        Ok(output_amount)
+    }
+
+    fn index_of(&self, token: &str) -> Result<usize, SwapError> {
+        self.tokens
+            .iter()
+            .position(|t| t == token)
+            .ok_or(SwapError::TokenNotFound)
+    }
+}
+
+impl Pool {
+    /// Lightweight copy of the pool for simulating swaps.
+    pub fn clone_for_simulation(&self) -> PoolSim {
+        PoolSim::from_pool(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Token;
+    use std::collections::HashMap;
+    use std::time::Instant;
+
+    fn create_pool(pool_type: PoolType, fee_rate: u64) -> Pool {
+        let tokens: Vec<Token> = ["ETH", "USDC", "DAI"]
+            .iter()
+            .map(|symbol| Token {
+                address: symbol.to_string(),
+                symbol: symbol.to_string(),
+                decimals: 18,
+            })
+            .collect();
+        let reserves: HashMap<String, BigUint> = tokens
+            .iter()
+            .zip([1_000_000u64, 2_000_000, 3_000_000])
+            .map(|(t, r)| (t.address.clone(), BigUint::from(r) * 10u64.pow(12)))
+            .collect();
+
+        Pool::new("POOL".to_string(), tokens, reserves, fee_rate, pool_type)
+    }
+
+    #[test]
+    fn test_sim_swaps_match_executed_swaps() {
+        let weighted = PoolType::Weighted {
+            weights: vec![50, 30, 20],
+        };
+        for (pool_type, fee_rate) in [
+            (PoolType::ConstantProduct, 30),
+            (PoolType::StableSwap, 4),
+            (weighted, 30),
+        ] {
+            let mut pool = create_pool(pool_type, fee_rate);
+            let mut sim = pool.clone_for_simulation();
+
+            for (input, output, amount) in [
+                ("ETH", "USDC", 5_000u64),
+                ("USDC", "DAI", 70_000),
+                ("DAI", "ETH", 12_345),
+            ] {
+                let amount = BigUint::from(amount) * 10u64.pow(12);
+                let expected = pool.execute_swap(input, output, &amount).unwrap();
+                assert_eq!(sim.swap(input, output, &amount).unwrap(), expected);
+                assert_eq!(sim.reserve(input), pool.reserves.get(input));
+                assert_eq!(sim.reserve(output), pool.reserves.get(output));
+            }
+        }
+
+        // Floors refuse the same swaps in both
+        let floor = BigUint::from(1_990_000u64) * 10u64.pow(12);
+        let mut pool = create_pool(PoolType::ConstantProduct, 30);
+        pool.min_reserves = Some(HashMap::from([("USDC".to_string(), floor)]));
+        let mut sim = pool.clone_for_simulation();
+        let amount = |units: u64| BigUint::from(units) * 10u64.pow(12);
+
+        // ~7968 USDC out leaves ~1992032, above the floor; ~3950 more does not
+        assert_eq!(
+            sim.swap("ETH", "USDC", &amount(4_000)).unwrap(),
+            pool.execute_swap("ETH", "USDC", &amount(4_000)).unwrap()
+        );
+        assert!(matches!(
+            pool.execute_swap("ETH", "USDC", &amount(2_000)),
+            Err(SwapError::ReserveFloorBreached)
+        ));
+        assert!(matches!(
+            sim.swap("ETH", "USDC", &amount(2_000)),
+            Err(SwapError::ReserveFloorBreached)
+        ));
+        assert_eq!(sim.reserve("USDC"), pool.reserves.get("USDC"));
+    }
+
+    #[test]
+    fn test_sim_rejects_unknown_tokens() {
+        let mut sim = create_pool(PoolType::ConstantProduct, 30).clone_for_simulation();
+        let result = sim.swap("ETH", "WBTC", &BigUint::from(1u64));
+        assert!(matches!(result, Err(SwapError::TokenNotFound)));
+    }
+
+    // Run with `cargo test -- --ignored --nocapture` to compare timings
+    #[test]
+    #[ignore]
+    fn bench_sim_vs_pool_clone() {
+        let mut pool = create_pool(PoolType::ConstantProduct, 30);
+        for i in 0..1_000 {
+            pool.lp_positions
+                .insert(format!("lp-{}", i), BigUint::from(1u64));
+        }
+        let amount = BigUint::from(1_000u64);
+        let rounds = 10_000;
+
+        let start = Instant::now();
+        for _ in 0..rounds {
+            let mut candidate = pool.clone();
+            candidate.execute_swap("ETH", "USDC", &amount).unwrap();
+        }
+        let with_pool = start.elapsed();
+
+        let start = Instant::now();
+        for _ in 0..rounds {
+            let mut candidate = pool.clone_for_simulation();
+            candidate.swap("ETH", "USDC", &amount).unwrap();
+        }
+        let with_sim = start.elapsed();
+
+        println!("Pool clone: {:?}, PoolSim: {:?}", with_pool, with_sim);
+        assert!(with_sim < with_pool);
+    }
+}
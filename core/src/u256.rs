@@ -0,0 +1,152 @@
+use crate::{Pool, SwapError};
+use ethers_core::types::U256;
+use num_bigint::BigUint;
+
+/// Exact conversion; every U256 fits in a BigUint.
+pub fn u256_to_biguint(value: U256) -> BigUint {
+    let mut bytes = [0u8; 32];
+    value.to_little_endian(&mut bytes);
+    BigUint::from_bytes_le(&bytes)
+}
+
+/// Exact conversion, or `AmountOverflow` for values above `U256::MAX`.
+pub fn biguint_to_u256(value: &BigUint) -> Result<U256, SwapError> {
+    if value.bits() > 256 {
+        return Err(SwapError::AmountOverflow);
+    }
+    Ok(U256::from_little_endian(&value.to_bytes_le()))
+}
+
+impl Pool {
+    /// `calculate_swap_output` in the contracts' integer type, so quotes
+    /// cross the on-chain boundary without a lossy conversion.
+    pub fn calculate_swap_output_u256(
+        &self,
+        input_token: &str,
+        output_token: &str,
+        input_amount: U256,
+    ) -> Result<U256, SwapError> {
+        let output =
+            self.calculate_swap_output(input_token, output_token, &u256_to_biguint(input_amount))?;
+        biguint_to_u256(&output)
+    }
+
+    /// Sells `input_amount` of `input_token` on a concentrated pool for its
+    /// other token, returning the Q64.96 sqrt price of token0 in token1
+    /// the swap leaves, as V3 tooling reads `sqrtPriceX96`, and the output.
+    pub fn quote_concentrated_sqrt(
+        &self,
+        input_token: &str,
+        input_amount: &BigUint,
+    ) -> Result<(U256, BigUint), SwapError> {
+        let (sqrt_price, output) = self.concentrated_swap_sqrt_price(input_token, input_amount)?;
+        Ok((biguint_to_u256(&sqrt_price)?, output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PoolType, Token};
+    use std::collections::HashMap;
+
+    fn create_pool(eth: BigUint, usdc: BigUint) -> Pool {
+        let tokens = ["ETH", "USDC"]
+            .iter()
+            .map(|symbol| Token {
+                address: symbol.to_string(),
+                symbol: symbol.to_string(),
+                decimals: 18,
+            })
+            .collect();
+        let mut reserves = HashMap::new();
+        reserves.insert("ETH".to_string(), eth);
+        reserves.insert("USDC".to_string(), usdc);
+
+        Pool::new(
+            "ETH-USDC".to_string(),
+            tokens,
+            reserves,
+            30,
+            PoolType::ConstantProduct,
+        )
+    }
+
+    #[test]
+    fn test_u256_quote_matches_biguint_quote() {
+        let eth = BigUint::from(10u64).pow(30);
+        let pool = create_pool(eth.clone(), eth * 2000u64);
+
+        for amount in [1u64, 12_345, 10u64.pow(18)] {
+            let expected = pool
+                .calculate_swap_output("ETH", "USDC", &BigUint::from(amount))
+                .unwrap();
+            let output = pool
+                .calculate_swap_output_u256("ETH", "USDC", U256::from(amount))
+                .unwrap();
+            assert_eq!(u256_to_biguint(output), expected);
+        }
+
+        assert_eq!(
+            u256_to_biguint(U256::MAX),
+            (BigUint::from(1u8) << 256) - 1u8
+        );
+        assert_eq!(
+            biguint_to_u256(&u256_to_biguint(U256::MAX)).unwrap(),
+            U256::MAX
+        );
+    }
+
+    #[test]
+    fn test_concentrated_sqrt_quote_matches_output() {
+        let unit = BigUint::from(10u64).pow(12);
+        let mut pool = create_pool(&unit * 1_000_000u64, &unit * 1_000_000u64);
+        pool.pool_type = PoolType::ConcentratedLiquidity;
+        let liquidity = &unit * 10_000u64;
+        pool.mint_position("lp", -600, 600, &liquidity).unwrap();
+        let q96 = BigUint::from(1u8) << 96;
+        let start = pool.sqrt_price_x96.clone().unwrap();
+
+        // Selling token0 lowers the price; the drop pays out
+        // dy = L * (sqrt(P) - sqrt(P')), rounded down
+        let input = &unit * 10u64;
+        let (sqrt_price, output) = pool.quote_concentrated_sqrt("ETH", &input).unwrap();
+        let sqrt_price = u256_to_biguint(sqrt_price);
+        assert!(sqrt_price < start);
+        assert_eq!(output, (&liquidity * (&start - &sqrt_price)) >> 96);
+        assert_eq!(
+            output,
+            pool.calculate_multi_asset_swap("ETH", "USDC", &input)
+                .unwrap()
+        );
+        // and takes in dx = L * (1/sqrt(P') - 1/sqrt(P)), the input less its fee
+        let after_fee = &input - pool.swap_fee("ETH", &input).unwrap();
+        let taken = ((&liquidity * (&start - &sqrt_price)) << 96) / (&start * &sqrt_price);
+        assert!(taken <= after_fee && &after_fee - &taken <= BigUint::from(1u8));
+
+        // Selling token1 raises it by dy / L
+        let (sqrt_price, output) = pool.quote_concentrated_sqrt("USDC", &input).unwrap();
+        let sqrt_price = u256_to_biguint(sqrt_price);
+        let after_fee = &input - pool.swap_fee("USDC", &input).unwrap();
+        assert_eq!(sqrt_price, &start + ((&after_fee * &q96) / &liquidity));
+        assert_eq!(
+            output,
+            ((&liquidity * (&sqrt_price - &start)) << 96) / (&start * &sqrt_price)
+        );
+
+        let constant_product = create_pool(unit.clone(), unit);
+        assert!(matches!(
+            constant_product.quote_concentrated_sqrt("ETH", &BigUint::from(1u8)),
+            Err(SwapError::UnsupportedPoolType)
+        ));
+    }
+
+    #[test]
+    fn test_u256_quote_rejects_outputs_beyond_256_bits() {
+        let one = BigUint::from(1u8);
+        let pool = create_pool(&one << 200, &one << 400);
+
+        let result = pool.calculate_swap_output_u256("ETH", "USDC", U256::one() << 100);
+        assert!(matches!(result, Err(SwapError::AmountOverflow)));
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{
+    biguint_ratio_to_f64, normalize_address, ratio_to_decimal, Pool, PoolType, WEIGHT_DENOMINATOR,
+};
+use num_bigint::BigUint;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+/// Source of token prices in a common unit (e.g. USD per whole token).
+pub trait PriceFeed {
+    fn price(&self, token: &str) -> Option<Decimal>;
+}
+
+/// Fixed prices keyed by token address, compared case-insensitively.
+#[derive(Debug, Clone, Default)]
+pub struct StaticPriceFeed {
+    prices: HashMap<String, Decimal>,
+}
+
+impl StaticPriceFeed {
+    pub fn new(prices: HashMap<String, Decimal>) -> Self {
+        let mut feed = StaticPriceFeed::default();
+        for (token, price) in prices {
+            feed.insert(&token, price);
+        }
+        feed
+    }
+
+    pub fn insert(&mut self, token: &str, price: Decimal) {
+        self.prices.insert(normalize_address(token), price);
+    }
+}
+
+impl PriceFeed for StaticPriceFeed {
+    fn price(&self, token: &str) -> Option<Decimal> {
+        self.prices.get(&normalize_address(token)).copied()
+    }
+}
+
+impl Pool {
+    /// Value of all reserves in the feed's unit: each reserve in whole
+    /// tokens times its price. `None` if any token has no price.
+    pub fn total_value(&self, feed: &dyn PriceFeed) -> Option<Decimal> {
+        self.tokens.iter().try_fold(Decimal::ZERO, |total, token| {
+            let reserve = self.reserves.get(&token.address)?;
+            let unit = BigUint::from(10u64).pow(token.decimals as u32);
+            let amount = ratio_to_decimal(reserve, &unit)?;
+            total.checked_add(amount.checked_mul(feed.price(&token.address)?)?)
+        })
+    }
+
+    /// Percent by which providing `entry_reserves` as liquidity trails
+    /// holding them, once arbitrage has moved the pool to
+    /// `current_external_prices` (per whole token, keyed by address). A
+    /// 2-token constant-product pool gives `2*sqrt(p)/(1+p) - 1` where
+    /// `p` is the change in the price ratio; fees are not counted. NaN for
+    /// pool types without a closed form and when a token has no price or
+    /// no entry reserve.
+    pub fn impermanent_loss(
+        &self,
+        entry_reserves: &HashMap<String, BigUint>,
+        current_external_prices: &HashMap<String, f64>,
+    ) -> f64 {
+        let weights: Vec<f64> = match &self.pool_type {
+            PoolType::ConstantProduct => vec![1.0 / self.tokens.len() as f64; self.tokens.len()],
+            PoolType::Weighted { weights } => weights
+                .iter()
+                .map(|&weight| weight as f64 / WEIGHT_DENOMINATOR as f64)
+                .collect(),
+            PoolType::StableSwap | PoolType::ConcentratedLiquidity => return f64::NAN,
+        };
+
+        // Arbitrage keeps the invariant and leaves each token holding its
+        // weight of the value, so the position is worth prod((v_i / w_i)^w_i)
+        // where v_i is the entry value of token i
+        let mut held_value = 0.0;
+        let mut log_lp_value = 0.0;
+        for (token, weight) in self.tokens.iter().zip(weights) {
+            let (Some(reserve), Some(&price)) = (
+                entry_reserves.get(&token.address),
+                current_external_prices.get(&token.address),
+            ) else {
+                return f64::NAN;
+            };
+            let unit = BigUint::from(10u64).pow(token.decimals as u32);
+            let value = biguint_ratio_to_f64(reserve, &unit) * price;
+            held_value += value;
+            log_lp_value += weight * (value / weight).ln();
+        }
+
+        (log_lp_value.exp() / held_value - 1.0) * 100.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PoolType, Token};
+
+    fn create_pool() -> Pool {
+        let tokens = vec![
+            Token {
+                address: "ETH".to_string(),
+                symbol: "ETH".to_string(),
+                decimals: 18,
+            },
+            Token {
+                address: "USDC".to_string(),
+                symbol: "USDC".to_string(),
+                decimals: 6,
+            },
+        ];
+        let mut reserves = HashMap::new();
+        reserves.insert("ETH".to_string(), BigUint::from(10u64).pow(18) * 2u64);
+        reserves.insert("USDC".to_string(), BigUint::from(4_000_000_000u64));
+
+        Pool::new(
+            "ETH-USDC".to_string(),
+            tokens,
+            reserves,
+            30,
+            PoolType::ConstantProduct,
+        )
+    }
+
+    fn prices(eth: f64, usdc: f64) -> HashMap<String, f64> {
+        HashMap::from([("ETH".to_string(), eth), ("USDC".to_string(), usdc)])
+    }
+
+    #[test]
+    fn test_impermanent_loss_of_a_doubled_price() {
+        // Deposited at 2000 USDC per ETH, which then doubles
+        let pool = create_pool();
+        let loss = pool.impermanent_loss(&pool.reserves, &prices(4000.0, 1.0));
+
+        let expected = (2.0 * 2f64.sqrt() / 3.0 - 1.0) * 100.0;
+        assert!((loss - expected).abs() < 1e-9, "{loss} vs {expected}");
+        assert!((loss + 5.72).abs() < 0.01);
+
+        // Only the ratio matters, so a halving loses the same
+        let halved = pool.impermanent_loss(&pool.reserves, &prices(1000.0, 1.0));
+        assert!((halved - expected).abs() < 1e-9);
+        assert!(pool
+            .impermanent_loss(&pool.reserves, &HashMap::new())
+            .is_nan());
+    }
+
+    #[test]
+    fn test_no_impermanent_loss_without_a_price_change() {
+        let pool = create_pool();
+        let loss = pool.impermanent_loss(&pool.reserves, &prices(2000.0, 1.0));
+        assert!(loss.abs() < 1e-9, "{loss}");
+
+        // Both tokens moving together keeps the ratio
+        let loss = pool.impermanent_loss(&pool.reserves, &prices(3000.0, 1.5));
+        assert!(loss.abs() < 1e-9, "{loss}");
+    }
+
+    #[test]
+    fn test_total_value_needs_every_price() {
+        let pool = create_pool();
+        let mut feed = StaticPriceFeed::default();
+        feed.insert("eth", Decimal::from(2000));
+        assert_eq!(pool.total_value(&feed), None);
+
+        // 2 ETH at 2000 plus 4000 USDC at 1
+        feed.insert("USDC", Decimal::ONE);
+        assert_eq!(pool.total_value(&feed), Some(Decimal::from(8000)));
+    }
+}